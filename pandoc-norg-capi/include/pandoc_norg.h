@@ -0,0 +1,77 @@
+#ifndef PANDOC_NORG_H
+#define PANDOC_NORG_H
+
+/* This file is generated by cbindgen from src/lib.rs, don't edit it manually */
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+/*
+ The result of a call to the library.
+ */
+typedef enum PandocNorgStatus {
+  /*
+   The call succeeded.
+   */
+  PANDOC_NORG_STATUS_OK = 0,
+  /*
+   A required pointer argument was null.
+   */
+  PANDOC_NORG_STATUS_NULL_POINTER = 1,
+  /*
+   The input isn't valid UTF-8.
+   */
+  PANDOC_NORG_STATUS_INVALID_UTF8 = 2,
+  /*
+   The input is bigger than 4 GiB.
+   */
+  PANDOC_NORG_STATUS_INPUT_TOO_BIG = 3,
+  /*
+   The converter panicked (which is a bug in the converter).
+   */
+  PANDOC_NORG_STATUS_PANIC = 4,
+} PandocNorgStatus;
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+/*
+ Converts the neorg source code (`length` bytes of UTF-8 at `source`, which doesn't need to be
+ null terminated) to pandoc json.
+
+ On success `*out_json` is set to a null terminated string with the json, which must be released
+ with [`pandoc_norg_free`], otherwise it's set to null.
+
+ # Safety
+
+ `source` must point to at least `length` readable bytes (it can be null if `length` is 0) and
+ `out_json` must be a valid pointer to write to.
+ */
+PandocNorgStatus pandoc_norg_convert(const char *source, size_t length, char **out_json);
+
+/*
+ Releases a string returned by the library, does nothing if `string` is null.
+
+ # Safety
+
+ `string` must be null or a string returned by the library that wasn't released yet.
+ */
+void pandoc_norg_free(char *string);
+
+/*
+ Returns a static null terminated description of the status, which must not be released.
+
+ The status is taken as an integer (since C doesn't restrict the values of enums), values that
+ aren't a [`PandocNorgStatus`] are described as an unknown status.
+ */
+const char *pandoc_norg_status_message(int status);
+
+#ifdef __cplusplus
+}  // extern "C"
+#endif  // __cplusplus
+
+#endif  /* PANDOC_NORG_H */
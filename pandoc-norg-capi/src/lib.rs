@@ -0,0 +1,174 @@
+//! C interface of [`pandoc_norg_converter`], to embed the converter in programs written in other
+//! languages (for example a Lua module for Neovim or a Python binding).
+//!
+//! The header of the interface is `include/pandoc_norg.h`, it's generated with `cbindgen` (see
+//! `cbindgen.toml`) and must be regenerated when the interface changes.
+//!
+//! # Memory ownership
+//!
+//! The input is only borrowed during the call. The strings returned by the library are owned by
+//! the caller and must be released with [`pandoc_norg_free`] (not with `free`), the messages
+//! returned by [`pandoc_norg_status_message`] are static and must not be released.
+
+use std::ffi::{c_char, c_int, CString};
+use std::panic::{self, AssertUnwindSafe};
+use std::ptr;
+
+use pandoc_norg_converter::Frontend;
+
+/// The maximum size of the input in bytes, tree-sitter uses 32 bit byte offsets so bigger inputs
+/// can't be parsed.
+const MAX_INPUT_SIZE: usize = u32::MAX as usize;
+
+/// The result of a call to the library.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PandocNorgStatus {
+    /// The call succeeded.
+    Ok = 0,
+    /// A required pointer argument was null.
+    NullPointer = 1,
+    /// The input isn't valid UTF-8.
+    InvalidUtf8 = 2,
+    /// The input is bigger than 4 GiB.
+    InputTooBig = 3,
+    /// The converter panicked (which is a bug in the converter).
+    Panic = 4,
+}
+
+impl PandocNorgStatus {
+    const ALL: [Self; 5] = [
+        Self::Ok,
+        Self::NullPointer,
+        Self::InvalidUtf8,
+        Self::InputTooBig,
+        Self::Panic,
+    ];
+
+    /// Returns the status with the value, or `None` if no status has it.
+    fn from_raw(status: c_int) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|&variant| variant as c_int == status)
+    }
+}
+
+/// Converts the neorg source code (`length` bytes of UTF-8 at `source`, which doesn't need to be
+/// null terminated) to pandoc json.
+///
+/// On success `*out_json` is set to a null terminated string with the json, which must be released
+/// with [`pandoc_norg_free`], otherwise it's set to null.
+///
+/// # Safety
+///
+/// `source` must point to at least `length` readable bytes (it can be null if `length` is 0) and
+/// `out_json` must be a valid pointer to write to.
+#[no_mangle]
+pub unsafe extern "C" fn pandoc_norg_convert(
+    source: *const c_char,
+    length: usize,
+    out_json: *mut *mut c_char,
+) -> PandocNorgStatus {
+    if out_json.is_null() || (source.is_null() && length != 0) {
+        return PandocNorgStatus::NullPointer;
+    }
+    *out_json = ptr::null_mut();
+
+    let bytes = match length {
+        0 => &[],
+        _ => std::slice::from_raw_parts(source.cast::<u8>(), length),
+    };
+    let Ok(source) = std::str::from_utf8(bytes) else {
+        return PandocNorgStatus::InvalidUtf8;
+    };
+    if source.len() > MAX_INPUT_SIZE {
+        return PandocNorgStatus::InputTooBig;
+    }
+
+    // Unwinding into the caller is undefined behavior, so panics are reported as an error
+    let result = panic::catch_unwind(AssertUnwindSafe(|| {
+        let mut json = Vec::new();
+        Frontend::default()
+            .convert_to_writer(source, &mut json)
+            .expect("Writing to a vector can't fail");
+        json
+    }));
+
+    let Ok(json) = result else {
+        return PandocNorgStatus::Panic;
+    };
+
+    // The json never contains null bytes since they are escaped in strings
+    let json = CString::new(json).expect("The json contains a null byte");
+    *out_json = json.into_raw();
+    PandocNorgStatus::Ok
+}
+
+/// Releases a string returned by the library, does nothing if `string` is null.
+///
+/// # Safety
+///
+/// `string` must be null or a string returned by the library that wasn't released yet.
+#[no_mangle]
+pub unsafe extern "C" fn pandoc_norg_free(string: *mut c_char) {
+    if !string.is_null() {
+        drop(CString::from_raw(string));
+    }
+}
+
+/// Returns a static null terminated description of the status, which must not be released.
+///
+/// The status is taken as an integer (since C doesn't restrict the values of enums), values that
+/// aren't a [`PandocNorgStatus`] are described as an unknown status.
+#[no_mangle]
+pub extern "C" fn pandoc_norg_status_message(status: c_int) -> *const c_char {
+    let message: &'static [u8] = match PandocNorgStatus::from_raw(status) {
+        Some(PandocNorgStatus::Ok) => b"Success\0",
+        Some(PandocNorgStatus::NullPointer) => b"A required pointer argument was null\0",
+        Some(PandocNorgStatus::InvalidUtf8) => b"The input isn't valid UTF-8\0",
+        Some(PandocNorgStatus::InputTooBig) => b"The input is bigger than 4 GiB\0",
+        Some(PandocNorgStatus::Panic) => b"The converter panicked\0",
+        None => b"Unknown status\0",
+    };
+
+    message.as_ptr().cast()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        pandoc_norg_convert, pandoc_norg_free, pandoc_norg_status_message, PandocNorgStatus,
+    };
+    use std::ffi::{c_int, CStr};
+    use std::ptr;
+
+    #[test]
+    fn invalid_arguments() {
+        let mut json = ptr::null_mut();
+
+        let status = unsafe { pandoc_norg_convert(ptr::null(), 1, &mut json) };
+        assert_eq!(status, PandocNorgStatus::NullPointer);
+
+        let source = [0xffu8, 0xfe];
+        let status = unsafe { pandoc_norg_convert(source.as_ptr().cast(), 2, &mut json) };
+        assert_eq!(status, PandocNorgStatus::InvalidUtf8);
+        assert!(json.is_null());
+
+        unsafe { pandoc_norg_free(json) };
+    }
+
+    #[test]
+    fn status_messages() {
+        let message = |status| unsafe { CStr::from_ptr(pandoc_norg_status_message(status)) };
+
+        for status in PandocNorgStatus::ALL {
+            assert_ne!(message(status as c_int).to_bytes(), b"Unknown status");
+        }
+        assert_eq!(
+            message(PandocNorgStatus::Ok as c_int).to_bytes(),
+            b"Success"
+        );
+        assert_eq!(message(-1).to_bytes(), b"Unknown status");
+        assert_eq!(message(5).to_bytes(), b"Unknown status");
+    }
+}
@@ -0,0 +1,40 @@
+use criterion::{black_box, criterion_group, criterion_main, Criterion, Throughput};
+use pandoc_norg_converter::Frontend;
+
+/// Returns a document with `sections` sections of paragraphs, like a big note.
+fn generate_document(sections: usize) -> String {
+    let mut source = String::new();
+
+    for section in 0..sections {
+        source.push_str(&format!("* Section {section}\n\n"));
+
+        for paragraph in 0..10 {
+            source.push_str(&format!(
+                "Paragraph {paragraph} has plain words, some *bold* and /italic/ text,\n\
+                 a {{https://example.com}}[link] and `inline code` spread over\n\
+                 a few lines so that it looks like a real note.\n\n"
+            ));
+        }
+
+        source.push_str("- A list item\n- Another list item\n\n");
+    }
+
+    source
+}
+
+fn convert(c: &mut Criterion) {
+    let mut group = c.benchmark_group("convert");
+
+    // About 10000 lines
+    let source = generate_document(220);
+    group.throughput(Throughput::Bytes(source.len() as u64));
+
+    group.bench_function("paragraphs", |b| {
+        b.iter(|| Frontend::default().convert(black_box(&source)))
+    });
+
+    group.finish();
+}
+
+criterion_group!(benches, convert);
+criterion_main!(benches);
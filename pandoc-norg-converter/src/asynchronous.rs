@@ -0,0 +1,174 @@
+//! Conversion of documents from async code (requires the `async` feature)
+
+use std::error::Error;
+use std::fmt;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+use pandoc_types::definition::Pandoc;
+
+use crate::Frontend;
+
+/// A token used to cancel a conversion started with [`Frontend::convert_async`].
+///
+/// The token can be cloned and cancelled from any thread, all the clones share the same state.
+#[derive(Debug, Clone, Default)]
+pub struct CancellationToken(Arc<AtomicBool>);
+
+impl CancellationToken {
+    /// Creates a new token that isn't cancelled.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Cancels the conversions using this token.
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Relaxed)
+    }
+
+    /// Returns whether the token was cancelled.
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+/// The error returned by [`Frontend::convert_async`] when the conversion was cancelled.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Cancelled;
+
+impl fmt::Display for Cancelled {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("The conversion was cancelled")
+    }
+}
+
+impl Error for Cancelled {}
+
+impl Frontend {
+    /// Converts the passed neorg source code to it's pandoc representation in tokio's blocking
+    /// thread pool, so that big documents don't block the async runtime.
+    ///
+    /// The conversion stops as soon as possible once `token` is cancelled. If the returned future
+    /// is dropped before completing, the conversion still runs to the end (unless cancelled) and
+    /// the information kept from the previous conversions (like the identifiers already used) is
+    /// lost, as if [`Frontend::reset`] was called. The configuration and the transforms are kept.
+    ///
+    /// # Panics
+    ///
+    /// Panics if called outside of a tokio runtime.
+    pub async fn convert_async(
+        &mut self,
+        source: String,
+        token: CancellationToken,
+    ) -> Result<Pandoc, Cancelled> {
+        // Only the state of the conversions is moved to the blocking task, the configuration and
+        // the transforms are shared so they stay in `self` even if this future is dropped
+        let mut frontend = Frontend {
+            config: Arc::clone(&self.config),
+            state: std::mem::take(&mut self.state),
+            #[cfg(feature = "ir")]
+            transforms: Arc::clone(&self.transforms),
+            project: self.project.clone(),
+            parser: self.parser.take(),
+        };
+
+        let (frontend, document) = tokio::task::spawn_blocking(move || {
+            frontend.state.cancellation = Some(token.clone());
+            let document = frontend.convert(&source);
+            frontend.state.cancellation = None;
+
+            let document = match token.is_cancelled() {
+                true => Err(Cancelled),
+                false => Ok(document),
+            };
+            (frontend, document)
+        })
+        .await
+        .unwrap_or_else(|err| std::panic::resume_unwind(err.into_panic()));
+
+        self.state = frontend.state;
+        self.parser = frontend.parser;
+        document
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::future::Future;
+
+    use super::{CancellationToken, Cancelled};
+    use crate::{Config, DuplicateIds, Frontend};
+
+    #[test]
+    fn shared_cancellation() {
+        let token = CancellationToken::new();
+        let clone = token.clone();
+        assert!(!token.is_cancelled());
+
+        clone.cancel();
+        assert!(token.is_cancelled());
+    }
+
+    fn runtime() -> tokio::runtime::Runtime {
+        tokio::runtime::Builder::new_current_thread()
+            .build()
+            .expect("Failed to build the runtime")
+    }
+
+    fn config() -> Config {
+        Config {
+            duplicate_ids: DuplicateIds {
+                format: String::from("{id}_{n}"),
+                start: 0,
+            },
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn convert() {
+        let source = "* Heading\n\nSome text\n";
+        let mut frontend = Frontend::new(config());
+        let mut sequential = Frontend::new(config());
+
+        let document = runtime()
+            .block_on(frontend.convert_async(source.to_string(), CancellationToken::new()));
+        assert_eq!(document, Ok(sequential.convert(source)));
+
+        // The identifiers of the first conversion are kept
+        assert_eq!(frontend.convert(source), sequential.convert(source));
+    }
+
+    #[test]
+    fn cancel() {
+        let mut frontend = Frontend::default();
+        let token = CancellationToken::new();
+        token.cancel();
+
+        let document = runtime().block_on(frontend.convert_async("* Heading".repeat(100), token));
+        assert_eq!(document, Err(Cancelled));
+    }
+
+    #[test]
+    fn dropped_conversion_keeps_config() {
+        let source = "* Heading\n* Heading\n";
+        let mut frontend = Frontend::new(config());
+
+        runtime().block_on(async {
+            let mut conversion =
+                Box::pin(frontend.convert_async(source.to_string(), CancellationToken::new()));
+            // Starts the conversion and drops it before it completes
+            std::future::poll_fn(|cx| {
+                let _ = conversion.as_mut().poll(cx);
+                std::task::Poll::Ready(())
+            })
+            .await;
+        });
+
+        // Only the identifiers of the dropped conversion are lost
+        assert_eq!(
+            frontend.convert(source),
+            Frontend::new(config()).convert(source)
+        );
+    }
+}
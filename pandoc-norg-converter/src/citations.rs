@@ -0,0 +1,147 @@
+//! Citations (links with the citation prefix, like `{cite:smith2020}`) and the `@bibliography` tag
+//!
+//! The citations are converted to pandoc `Cite` elements, which are rendered by pandoc's
+//! `--citeproc` using the files in the `bibliography` metadata field (set by the
+//! `@bibliography` tag or directly in the document's metadata).
+
+use std::borrow::Cow;
+
+use pandoc_types::definition::{Citation, CitationMode, Inline as PandocInline, MetaValue};
+#[cfg(feature = "serde")]
+use serde::Deserialize;
+
+use crate::document::DocumentContext;
+use crate::ir::{convert_inlines_to_pandoc, Inline};
+use crate::stringify::text_to_inlines;
+use crate::Builder;
+
+/// Configuration of the citations, see [`Config::citations`].
+///
+/// Links whose target starts with the `prefix` are converted to citations of the keys after it,
+/// multiple keys are separated by `;` (`{cite:a;b}`) and keys starting with `-` suppress the
+/// author (like pandoc's `-@key`). The description of the link (if any) is used as the suffix of
+/// the last citation (for example `{cite:smith2020}[p. 33]`).
+///
+/// [`Config::citations`]: crate::Config::citations
+#[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
+pub struct Citations {
+    /// The prefix of the link targets that are citations (default: `cite:`).
+    pub prefix: String,
+}
+
+impl Default for Citations {
+    fn default() -> Self {
+        Self {
+            prefix: String::from("cite:"),
+        }
+    }
+}
+
+impl Citations {
+    /// Returns the citation keys of the link target, or `None` if it isn't a citation.
+    pub(crate) fn keys<'a>(&self, target: &'a str) -> Option<Vec<&'a str>> {
+        let keys: Vec<_> = target
+            .strip_prefix(self.prefix.as_str())?
+            .split(';')
+            .map(str::trim)
+            .filter(|key| !key.is_empty())
+            .collect();
+
+        (!keys.is_empty()).then_some(keys)
+    }
+}
+
+/// Returns the pandoc `Cite` of the keys, with `suffix` as the suffix of the last citation.
+pub(crate) fn cite_to_pandoc(
+    keys: Vec<&str>,
+    suffix: Vec<Inline>,
+    context: &DocumentContext,
+) -> PandocInline {
+    let text = keys
+        .iter()
+        .map(|key| match key.strip_prefix('-') {
+            Some(key) => format!("-@{key}"),
+            None => format!("@{key}"),
+        })
+        .collect::<Vec<_>>()
+        .join("; ");
+
+    let mut citations: Vec<_> = keys
+        .into_iter()
+        .map(|key| {
+            let (key, mode) = match key.strip_prefix('-') {
+                Some(key) => (key, CitationMode::SuppressAuthor),
+                None => (key, CitationMode::NormalCitation),
+            };
+
+            Citation {
+                citation_id: key.to_string(),
+                citation_prefix: Vec::new(),
+                citation_suffix: Vec::new(),
+                citation_mode: mode,
+                citation_note_num: 0,
+                citation_hash: 0,
+            }
+        })
+        .collect();
+
+    if let Some(last) = citations.last_mut() {
+        last.citation_suffix = convert_inlines_to_pandoc(suffix, context);
+    }
+
+    PandocInline::Cite(citations, text_to_inlines(&format!("[{text}]")))
+}
+
+impl<'builder, 'source> Builder<'builder, 'source>
+where
+    'source: 'builder,
+{
+    /// Parses a `@bibliography` tag, the paths of the bibliography files are the tag's parameters
+    /// and the lines of it's content.
+    pub(crate) fn handle_bibliography_block(&mut self, parameters: &[Cow<'source, str>]) {
+        log::debug!("Parsing bibliography");
+
+        let text = self
+            .cursor
+            .node()
+            .utf8_text(self.source.as_bytes())
+            .expect("Invalid text");
+
+        let mut paths: Vec<_> = parameters
+            .iter()
+            .map(|path| MetaValue::MetaString(path.to_string()))
+            .collect();
+        paths.extend(
+            text.lines()
+                .map(str::trim)
+                .filter(|line| !line.is_empty())
+                .map(|path| MetaValue::MetaString(path.to_string())),
+        );
+
+        let value = match paths.len() {
+            0 => return self.warn("Bibliography without any files"),
+            1 => paths.remove(0),
+            _ => MetaValue::MetaList(paths),
+        };
+
+        self.document
+            .extend_meta([(String::from("bibliography"), value)]);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Citations;
+
+    #[test]
+    fn citation_keys() {
+        let citations = Citations::default();
+
+        assert_eq!(citations.keys("cite:smith2020"), Some(vec!["smith2020"]));
+        assert_eq!(citations.keys("cite:a; -b"), Some(vec!["a", "-b"]));
+        assert_eq!(citations.keys("cite:"), None);
+        assert_eq!(citations.keys("https://example.com"), None);
+    }
+}
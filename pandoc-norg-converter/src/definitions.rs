@@ -1,6 +1,26 @@
-use crate::ir::Block;
+use crate::document::DocumentLinkType;
+use crate::ir::{Block, Inline};
 use crate::Builder;
 
+/// Returns whether a node is a block that can be part of the content of a ranged definition.
+fn is_definition_content(kind: &str) -> bool {
+    matches!(
+        kind,
+        "paragraph"
+            | "generic_list"
+            | "quote"
+            | "table"
+            | "ranged_tag"
+            | "ranged_verbatim_tag"
+            | "strong_carryover_set"
+            | "weak_carryover_set"
+            | "infirm_tag"
+            | "definition_list"
+            | "footnote_list"
+            | "horizontal_line"
+    )
+}
+
 impl<'builder, 'source> Builder<'builder, 'source> {
     pub fn handle_definition_list(&mut self) {
         log::debug!("Parsing definition list");
@@ -27,10 +47,46 @@ impl<'builder, 'source> Builder<'builder, 'source> {
                     this.document.push_scope();
                 }
 
-                inlines.append(&mut this.document.take_inlines_collector());
-                this.handle_segment(&mut inlines);
-            } else if this.cursor.field_id() == this.field_ids.state {
-                this.handle_detached_ext();
+                // The rendered detached modifier extensions are placed before the term
+                let mut status = this.document.take_inlines_collector();
+                if !status.is_empty() {
+                    inlines.append(&mut status);
+                    inlines.push(Inline::Space);
+                }
+
+                if !this.config.definition_ids {
+                    this.handle_segment(&mut inlines);
+                    return;
+                }
+
+                // The term gets an identifier so that it can be linked to (`{$ term}`)
+                let mut term = Vec::new();
+                this.handle_segment(&mut term);
+
+                let mut term_text = String::from("term ");
+                for inline in &term {
+                    inline.push_text(&mut term_text);
+                }
+                let term_text = term_text.split_whitespace().collect::<Vec<_>>().join(" ");
+                let identifier = this.frontend.generate_id(&term_text, this.config);
+
+                let node = this.cursor.node();
+                let text = &this.source[node.byte_range()];
+                this.context.add_document_link(
+                    text,
+                    DocumentLinkType::Definition,
+                    format!("#{}", identifier),
+                );
+
+                inlines.push(Inline::Identified(term, identifier));
+            } else if this.cursor.field_id() == this.field_ids.state
+                || this.cursor.node().kind() == "detached_modifier_extension"
+            {
+                this.handle_detached_ext(false);
+            } else if is_definition_content(this.cursor.node().kind()) {
+                // The content of ranged definitions (`$$ Term ... $$`) might not be in the
+                // content field
+                this.handle_node();
             }
         });
 
@@ -0,0 +1,181 @@
+//! Reporting of the problems found while converting a document
+
+use std::ops::Range;
+use std::sync::Arc;
+
+use crate::document::DocumentContext;
+use crate::{Builder, Config};
+
+/// A callback that receives the diagnostics produced by the converter, see
+/// [`Config::on_diagnostic`].
+pub type DiagnosticHandler = Arc<dyn Fn(&Diagnostic) + Send + Sync>;
+
+/// How serious a [`Diagnostic`] is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    /// The element was converted but the result might not be the expected one.
+    Warning,
+    /// The element isn't supported or is invalid, and was skipped.
+    Error,
+}
+
+/// A problem found while converting a document.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub message: String,
+    /// The byte range in the source of the element that caused the diagnostic (if known).
+    pub range: Option<Range<usize>>,
+    /// The byte range of another element related to the diagnostic (for example the previous
+    /// definition of a duplicated anchor).
+    pub related: Option<Range<usize>>,
+}
+
+/// Passes the diagnostic to the handler in the configuration, or to the `log` crate if there's
+/// no handler.
+pub(crate) fn report(config: &Config, diagnostic: Diagnostic) {
+    if let Some(ref handler) = config.on_diagnostic {
+        return handler(&diagnostic);
+    }
+
+    match diagnostic.severity {
+        Severity::Warning => log::warn!("{}", diagnostic.message),
+        Severity::Error => log::error!("{}", diagnostic.message),
+    }
+}
+
+impl<'builder, 'source> Builder<'builder, 'source>
+where
+    'source: 'builder,
+{
+    /// Reports a warning about the node under the cursor.
+    pub(crate) fn warn(&self, message: impl Into<String>) {
+        self.report(Severity::Warning, message.into())
+    }
+
+    /// Reports an error about the node under the cursor.
+    pub(crate) fn error(&self, message: impl Into<String>) {
+        self.report(Severity::Error, message.into())
+    }
+
+    fn report(&self, severity: Severity, message: String) {
+        let node = self.cursor.node();
+
+        report(
+            self.config,
+            Diagnostic {
+                severity,
+                message,
+                range: Some(node.byte_range()),
+                related: None,
+            },
+        )
+    }
+}
+
+impl DocumentContext<'_> {
+    /// Reports a warning found while building the pandoc document.
+    pub(crate) fn warn(&self, message: impl Into<String>) {
+        report(
+            self.config,
+            Diagnostic {
+                severity: Severity::Warning,
+                message: message.into(),
+                range: None,
+                related: None,
+            },
+        )
+    }
+
+    /// Reports a warning about an element at `range` that duplicates the element at `previous`.
+    pub(crate) fn warn_duplicate(
+        &self,
+        message: String,
+        range: Range<usize>,
+        previous: Range<usize>,
+    ) {
+        report(
+            self.config,
+            Diagnostic {
+                severity: Severity::Warning,
+                message,
+                range: Some(range),
+                related: Some(previous),
+            },
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{report, Diagnostic, Severity};
+    use crate::document::{DefinitionKind, DocumentContext};
+    use crate::Config;
+    use std::sync::{Arc, Mutex};
+
+    /// Returns a configuration whose diagnostics are collected in the returned list.
+    fn collecting_config() -> (Config, Arc<Mutex<Vec<Diagnostic>>>) {
+        let received = Arc::new(Mutex::new(Vec::new()));
+        let sink = Arc::clone(&received);
+        let config = Config {
+            on_diagnostic: Some(Arc::new(move |diagnostic: &Diagnostic| {
+                sink.lock().unwrap().push(diagnostic.clone())
+            })),
+            ..Default::default()
+        };
+
+        (config, received)
+    }
+
+    #[test]
+    fn handler_receives_diagnostics() {
+        let (config, received) = collecting_config();
+
+        let diagnostic = Diagnostic {
+            severity: Severity::Warning,
+            message: String::from("Something happened"),
+            range: Some(0..4),
+            related: None,
+        };
+        report(&config, diagnostic.clone());
+
+        assert_eq!(*received.lock().unwrap(), vec![diagnostic]);
+    }
+
+    #[test]
+    fn duplicate_definitions() {
+        let (config, received) = collecting_config();
+
+        let mut context = DocumentContext::new(&config);
+        context.define(DefinitionKind::Anchor, String::from("a"), 0..3);
+        context.define(DefinitionKind::Heading(1), String::from("a"), 5..8);
+        assert!(received.lock().unwrap().is_empty());
+
+        context.define(DefinitionKind::Anchor, String::from("a"), 10..13);
+        let received = received.lock().unwrap();
+        assert_eq!(received.len(), 1);
+        assert_eq!(received[0].range, Some(10..13));
+        assert_eq!(received[0].related, Some(0..3));
+    }
+
+    #[test]
+    fn ambiguous_headings() {
+        let (config, received) = collecting_config();
+
+        let mut context = DocumentContext::new(&config);
+        context.define(DefinitionKind::Heading(2), String::from("Notes"), 0..5);
+        context.define(DefinitionKind::Heading(2), String::from("Notes"), 10..15);
+        context.define(DefinitionKind::Heading(3), String::from("Other"), 20..25);
+        assert!(received.lock().unwrap().is_empty());
+
+        context.warn_ambiguous_heading("Other", 3);
+        context.warn_ambiguous_heading("Notes", 3);
+        assert!(received.lock().unwrap().is_empty());
+
+        context.warn_ambiguous_heading("Notes", 2);
+        let received = received.lock().unwrap();
+        assert_eq!(received.len(), 1);
+        assert_eq!(received[0].range, Some(10..15));
+        assert_eq!(received[0].related, Some(0..5));
+    }
+}
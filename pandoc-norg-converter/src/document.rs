@@ -1,31 +1,261 @@
-use crate::ir::{convert_blocks_to_pandoc, convert_inlines_to_pandoc, Block, Inline, LinkType};
-use pandoc_types::definition::{Block as PandocBlock, MetaValue, Pandoc};
+use crate::epub;
+use crate::extensions::TodoStatus;
+use crate::footnotes::Footnotes;
+use crate::ir::{
+    convert_blocks_to_pandoc, convert_inlines_to_pandoc, get_link_url, Block, Inline, LinkType,
+};
+use crate::project::ProjectIndex;
+use crate::slides::{insert_slide_breaks, SlideSplit};
+use crate::source_map::{add_source_positions, BlockPath, SourceMap, SourceRange};
+use crate::timestamps::Timestamp;
+use crate::title::take_title;
+use crate::Config;
+use pandoc_types::definition::{MetaValue, Pandoc};
+use std::borrow::Cow;
 use std::collections::HashMap;
+use std::ops::Range;
 
+/// The kind of element a link inside the document points to.
 #[derive(PartialEq, Eq, Hash, Debug)]
 pub enum DocumentLinkType {
+    /// A heading of the given level.
     Heading(i32),
+    /// The term of a definition.
+    Definition,
+    /// The identifier of an equation (a `@math` block).
+    Equation,
+}
+
+/// The kinds of names that must be unique in a document, see [`DocumentContext::define`].
+#[derive(PartialEq, Eq, Hash, Debug, Clone, Copy)]
+pub enum DefinitionKind {
+    /// A normalized anchor name.
+    Anchor,
+    /// The title of a heading (as written in the source) with the heading's level. Headings can
+    /// repeat titles (their identifiers are made unique), only links to them are ambiguous.
+    Heading(i32),
+    /// A normalized footnote title.
+    Footnote,
+    /// The name of a variable defined with a macro tag (`=name`).
+    Variable,
+}
+
+/// A task defined with neorg's TODO status extension.
+pub struct Task {
+    pub status: TodoStatus,
+    /// The first line of the task's text as written in the source.
+    pub text: String,
+    /// The index of the top level section containing the task (if any).
+    pub section: Option<usize>,
+    /// The index in [`DocumentContext::headings`] of the heading containing the task, or of the
+    /// heading itself if the status belongs to one (if any).
+    pub heading: Option<usize>,
+    /// The start date (`(> 2024-06-01)`) set on the task's detached modifier (if any).
+    pub(crate) start: Option<Timestamp>,
+    /// The due date (`(< 2024-06-01)`) set on the task's detached modifier (if any).
+    pub(crate) due: Option<Timestamp>,
+}
+
+/// A heading of the document, see [`Frontend::convert_with_outline`].
+///
+/// [`Frontend::convert_with_outline`]: crate::Frontend::convert_with_outline
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Heading {
+    /// The plain text of the heading's title.
+    pub title: String,
+    /// The identifier of the heading.
+    pub id: String,
+    /// The level of the heading in the source (before applying [`Config::heading_offset`]).
+    pub level: i32,
 }
 
-#[derive(Default)]
 pub struct DocumentContext<'source> {
-    pub anchors: HashMap<&'source str, LinkType<'source>>,
-    document_links: HashMap<&'source str, HashMap<DocumentLinkType, String>>,
+    pub config: &'source Config,
+    /// Maps normalized anchor names (see [`normalize_anchor_name`]) to their targets.
+    ///
+    /// [`normalize_anchor_name`]: crate::inlines::normalize_anchor_name
+    pub anchors: HashMap<String, LinkType<'source>>,
+    /// The URLs of the anchors, see [`DocumentContext::resolve_anchors`].
+    pub(crate) anchor_urls: HashMap<String, String>,
+    /// The link targets of the other documents when converting a [`Project`].
+    ///
+    /// [`Project`]: crate::Project
+    pub(crate) project: Option<&'source ProjectIndex>,
+    pub tasks: Vec<Task>,
+    pub headings: Vec<Heading>,
+    document_links: HashMap<Cow<'source, str>, HashMap<DocumentLinkType, String>>,
+    pub footnotes: Footnotes<'source>,
+    /// The values of the variables defined in the document with macro tags (`=name`).
+    pub(crate) variables: HashMap<String, String>,
+    /// The source range of the first definition of each name.
+    definitions: HashMap<(DefinitionKind, String), Range<usize>>,
+    /// The source ranges of the second and first definitions of the heading titles that are
+    /// defined multiple times, see [`DocumentContext::warn_ambiguous_heading`].
+    ambiguous_headings: HashMap<(DefinitionKind, String), (Range<usize>, Range<usize>)>,
 }
 
 impl<'source> DocumentContext<'source> {
-    pub fn add_document_link(&mut self, text: &'source str, ty: DocumentLinkType, id: String) {
-        let entry = self.document_links.entry(text);
+    pub fn new(config: &'source Config) -> Self {
+        Self {
+            config,
+            anchors: HashMap::default(),
+            anchor_urls: HashMap::default(),
+            project: None,
+            tasks: Vec::new(),
+            headings: Vec::new(),
+            document_links: HashMap::default(),
+            footnotes: Footnotes::default(),
+            variables: HashMap::default(),
+            definitions: HashMap::default(),
+            ambiguous_headings: HashMap::default(),
+        }
+    }
+
+    /// Records the definition of `name` at `range`, and warns if it was already defined (except
+    /// for headings, see [`DefinitionKind::Heading`]).
+    pub fn define(&mut self, kind: DefinitionKind, name: String, range: Range<usize>) {
+        let Some(previous) = self.definitions.get(&(kind, name.clone())) else {
+            self.definitions.insert((kind, name), range);
+            return;
+        };
+
+        let message = match kind {
+            DefinitionKind::Anchor => format!("Anchor '{name}' is defined multiple times"),
+            DefinitionKind::Heading(_) => {
+                let previous = previous.clone();
+                let entry = self.ambiguous_headings.entry((kind, name));
+                entry.or_insert((range, previous));
+                return;
+            }
+            DefinitionKind::Footnote => format!("Footnote '{name}' is defined multiple times"),
+            DefinitionKind::Variable => format!("Variable '{name}' is defined multiple times"),
+        };
+        self.warn_duplicate(message, range, previous.clone());
+    }
+
+    /// Warns if multiple headings with the level have the title, which makes a link to it
+    /// ambiguous (the link goes to the last of them).
+    pub fn warn_ambiguous_heading(&self, text: &str, level: i32) {
+        let key = (DefinitionKind::Heading(level), text.to_string());
+        if let Some((range, previous)) = self.ambiguous_headings.get(&key) {
+            let message =
+                format!("Multiple headings are titled '{text}', the link goes to the last");
+            self.warn_duplicate(message, range.clone(), previous.clone());
+        }
+    }
+
+    pub fn add_document_link(
+        &mut self,
+        text: impl Into<Cow<'source, str>>,
+        ty: DocumentLinkType,
+        id: String,
+    ) {
+        let entry = self.document_links.entry(text.into());
         let ty_map = entry.or_default();
         ty_map.insert(ty, id);
     }
 
-    pub fn get_document_link(&self, text: &'source str, ty: &DocumentLinkType) -> Option<&String> {
+    pub fn get_document_link(&self, text: &str, ty: &DocumentLinkType) -> Option<&String> {
         let ty_map = self.document_links.get(text)?;
         let res = ty_map.get(ty);
         log::debug!("Fetching link for {} (ty: {:?}) = {:?}", text, ty, res);
         res
     }
+
+    /// Adds the elements collected in `other` (which was built after `self`) to `self`.
+    ///
+    /// `ids` maps the identifiers generated while building `other` to the identifiers that should
+    /// be used instead.
+    pub fn merge(&mut self, other: DocumentContext<'source>, ids: &HashMap<String, String>) {
+        let rename = |id: String| ids.get(&id).cloned().unwrap_or(id);
+        let heading_offset = self.headings.len();
+
+        self.anchors.extend(other.anchors);
+        self.footnotes.merge(other.footnotes);
+        for (name, value) in other.variables {
+            self.variables.entry(name).or_insert(value);
+        }
+
+        let mut definitions: Vec<_> = other.definitions.into_iter().collect();
+        definitions.sort_by_key(|(_, range)| range.start);
+        for ((kind, name), range) in definitions {
+            self.define(kind, name, range);
+        }
+        for (key, ranges) in other.ambiguous_headings {
+            self.ambiguous_headings.entry(key).or_insert(ranges);
+        }
+
+        for (text, links) in other.document_links {
+            let entry = self.document_links.entry(text).or_default();
+
+            for (ty, url) in links {
+                let url = match url.strip_prefix('#') {
+                    Some(id) => format!("#{}", rename(id.to_string())),
+                    None => url,
+                };
+                entry.insert(ty, url);
+            }
+        }
+
+        self.tasks.extend(other.tasks.into_iter().map(|task| Task {
+            heading: task.heading.map(|idx| idx + heading_offset),
+            ..task
+        }));
+
+        self.headings
+            .extend(other.headings.into_iter().map(|heading| Heading {
+                id: rename(heading.id),
+                ..heading
+            }));
+    }
+
+    /// Returns the link to a heading with the given text of any level.
+    ///
+    /// If multiple headings match, the one with the highest level (lowest number) is returned.
+    pub fn find_heading_link(&self, text: &str) -> Option<&String> {
+        (1..=6).find_map(|level| {
+            let link = self.get_document_link(text, &DocumentLinkType::Heading(level))?;
+            self.warn_ambiguous_heading(text, level);
+            Some(link)
+        })
+    }
+
+    /// Resolves the targets of the anchors, this must be called once the whole document was
+    /// walked (and before the footnotes are built) so that anchors can be used before they're
+    /// defined.
+    pub fn resolve_anchors(&mut self) {
+        // Sorted so that the diagnostics of the links are always reported in the same order
+        let mut anchors: Vec<_> = self.anchors.iter().collect();
+        anchors.sort_by_key(|(name, _)| *name);
+
+        let anchor_urls = anchors
+            .into_iter()
+            .map(|(name, ty)| (name.clone(), get_link_url(ty, self)))
+            .collect();
+        self.anchor_urls = anchor_urls;
+    }
+
+    /// Returns the URL of the anchor with the given normalized name, or warns if it isn't
+    /// defined.
+    pub fn get_anchor_url(&self, name: &str) -> String {
+        let project_url = || self.project.and_then(|project| project.anchor_url(name));
+
+        match self.anchor_urls.get(name).or_else(project_url) {
+            Some(url) => url.clone(),
+            None => {
+                self.warn(format!("Undefined anchor {}", name));
+                String::new()
+            }
+        }
+    }
+
+    /// Returns the link to any element with the given text (the `#` link target), headings are
+    /// preferred over definitions and definitions over equations.
+    pub fn find_generic_link(&self, text: &str) -> Option<&String> {
+        self.find_heading_link(text)
+            .or_else(|| self.get_document_link(text, &DocumentLinkType::Definition))
+            .or_else(|| self.get_document_link(text, &DocumentLinkType::Equation))
+    }
 }
 
 /// Interface for building pandoc documents.
@@ -34,6 +264,8 @@ impl<'source> DocumentContext<'source> {
 /// their correct usage trough it's API.
 pub struct DocumentBuilder<'source> {
     scopes: Vec<Vec<Block<'source>>>,
+    /// The source range of each block in the root scope (if known).
+    sources: Vec<Option<SourceRange>>,
     metadata: HashMap<String, MetaValue>,
     inlines_collector: Vec<Inline<'source>>,
 }
@@ -51,6 +283,34 @@ impl<'source> DocumentBuilder<'source> {
         }
 
         scope.push(block);
+
+        if self.scopes.len() == 1 {
+            self.sources.resize(self.scopes[0].len(), None);
+        }
+    }
+
+    /// Adds a block to the root scope with it's source range.
+    pub fn add_root_block(&mut self, block: Block<'source>, source: Option<SourceRange>) {
+        debug_assert_eq!(self.scopes.len(), 1, "Only the root scope should remain");
+        let start = self.root_len();
+        self.add_block(block);
+
+        if let Some(source) = source {
+            self.set_root_sources(start, source);
+        }
+    }
+
+    /// Returns the number of blocks in the root scope.
+    pub fn root_len(&self) -> usize {
+        self.scopes[0].len()
+    }
+
+    /// Sets the source range of the blocks in the root scope starting at `start` (that have no
+    /// range yet).
+    pub fn set_root_sources(&mut self, start: usize, source: SourceRange) {
+        for range in self.sources.iter_mut().skip(start).filter(|r| r.is_none()) {
+            *range = Some(source.clone());
+        }
     }
 
     /// Pushes a new scope
@@ -88,6 +348,15 @@ impl<'source> DocumentBuilder<'source> {
         self.inlines_collector.push(inline)
     }
 
+    /// Adds the values of `meta` whose keys aren't in the metadata of the document yet.
+    pub fn add_default_meta(&mut self, meta: &HashMap<String, MetaValue>) {
+        for (key, value) in meta {
+            self.metadata
+                .entry(key.clone())
+                .or_insert_with(|| value.clone());
+        }
+    }
+
     /// Returns the contents of the inline collector and resets it.
     pub fn take_inlines_collector(&mut self) -> Vec<Inline<'source>> {
         let mut inlines = Vec::new();
@@ -95,23 +364,108 @@ impl<'source> DocumentBuilder<'source> {
         inlines
     }
 
-    /// Returns the built document.
-    pub fn build(mut self, context: &DocumentContext) -> Pandoc {
+    /// Returns the blocks of the root scope (including the contents of the inlines collector)
+    /// with their source ranges and the metadata of the document.
+    #[allow(clippy::type_complexity)]
+    pub fn finish(
+        mut self,
+    ) -> (
+        Vec<(Block<'source>, Option<SourceRange>)>,
+        HashMap<String, MetaValue>,
+    ) {
+        debug_assert_eq!(self.scopes.len(), 1, "Only the root scope should remain");
+        let mut root_scope = self.scopes.remove(0);
+
+        if !self.inlines_collector.is_empty() {
+            root_scope.push(Block::Plain(self.inlines_collector));
+        }
+
+        self.sources.resize(root_scope.len(), None);
+        let blocks = root_scope.into_iter().zip(self.sources).collect();
+
+        (blocks, self.metadata)
+    }
+
+    /// Applies the transform to the blocks of the root scope (and the contents of the inlines
+    /// collector).
+    #[cfg(feature = "ir")]
+    pub fn transform(&mut self, transform: &mut dyn crate::Transform) {
+        crate::transform::walk_blocks(transform, &mut self.scopes[0]);
+        crate::transform::walk_inlines(transform, &mut self.inlines_collector);
+    }
+
+    /// Returns the built document and the source map of it's blocks.
+    pub fn build(self, context: &DocumentContext) -> (Pandoc, SourceMap) {
+        let (blocks, metadata, source_map) = self.into_root_blocks(context);
+
+        let mut blocks = convert_blocks_to_pandoc(blocks, context);
+        if context.config.emit_source_positions {
+            add_source_positions(&mut blocks, &source_map);
+        }
+
+        let pandoc = Pandoc {
+            meta: metadata,
+            blocks,
+        };
+
+        (pandoc, source_map)
+    }
+
+    /// Returns the blocks of the root scope (including the contents of the inlines collector)
+    /// wrapped in sections if the [`EpubSemantics`] or the section `Div`s are enabled, the
+    /// metadata of the document and the source map of the blocks.
+    ///
+    /// [`EpubSemantics`]: crate::EpubSemantics
+    pub fn into_root_blocks(
+        mut self,
+        context: &DocumentContext,
+    ) -> (Vec<Block<'source>>, HashMap<String, MetaValue>, SourceMap) {
         debug_assert_eq!(self.scopes.len(), 1, "Only the root scope should remain");
-        let root_scope = self.scopes.remove(0);
+        let mut root_scope = self.scopes.remove(0);
 
-        let mut pandoc = Pandoc {
-            meta: self.metadata,
-            blocks: convert_blocks_to_pandoc(root_scope, context),
+        let config = context.config;
+        if config.promote_title && !self.metadata.contains_key("title") {
+            if let Some(title) = take_title(&mut root_scope, &mut self.sources) {
+                let title = MetaValue::MetaInlines(convert_inlines_to_pandoc(title, context));
+                self.metadata.insert(String::from("title"), title);
+            }
+        }
+
+        let slides = config.slides.as_ref();
+        if let Some(slides) = slides.filter(|s| s.split == SlideSplit::HorizontalRule) {
+            let sources = std::mem::take(&mut self.sources);
+            (root_scope, self.sources) = insert_slide_breaks(root_scope, sources, slides.level);
+        }
+
+        let slide_sections = slides.is_some_and(|s| s.split == SlideSplit::Sections);
+        let paths = if config.epub_semantics.is_some() || config.section_divs || slide_sections {
+            let (blocks, paths) = epub::wrap_sections(
+                root_scope,
+                config.epub_semantics.as_ref(),
+                config.section_divs,
+                &self.metadata,
+            );
+            root_scope = blocks;
+            paths
+        } else {
+            (0..root_scope.len())
+                .map(|idx| BlockPath(vec![idx]))
+                .collect()
         };
 
+        let mut source_map: SourceMap = paths
+            .into_iter()
+            .zip(self.sources)
+            .filter_map(|(path, source)| Some((path, source?)))
+            .collect();
+        source_map.sort_by(|(a, _), (b, _)| a.cmp(b));
+
         // Flush the inlines collector
         if !self.inlines_collector.is_empty() {
-            let inlines = convert_inlines_to_pandoc(self.inlines_collector, context);
-            pandoc.blocks.push(PandocBlock::Plain(inlines));
+            root_scope.push(Block::Plain(self.inlines_collector));
         }
 
-        pandoc
+        (root_scope, self.metadata, source_map)
     }
 }
 
@@ -119,6 +473,7 @@ impl Default for DocumentBuilder<'_> {
     fn default() -> Self {
         Self {
             scopes: vec![Vec::new()],
+            sources: Vec::new(),
             metadata: Default::default(),
             inlines_collector: Default::default(),
         }
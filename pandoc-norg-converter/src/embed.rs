@@ -0,0 +1,126 @@
+//! Video, audio and HTML embeds (`@embed video`, `@embed audio` and `@embed html`)
+//!
+//! Videos and audios are converted to raw HTML5 `<video>` and `<audio>` elements (with the
+//! parameters after the type as their attributes) and the content of `@embed html` tags is passed
+//! through as raw HTML, unless [`Config::disable_raw_html`] is set.
+//!
+//! [`Config::disable_raw_html`]: crate::Config::disable_raw_html
+
+use std::borrow::Cow;
+
+use pandoc_types::definition::{Attr, Format};
+
+use crate::ir::{Block, Inline, LinkType};
+use crate::tags::parameters_attr;
+use crate::Builder;
+
+/// Escapes the characters with a special meaning in HTML attributes and text.
+fn html_escape(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+
+    for char in text.chars() {
+        match char {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            char => out.push(char),
+        }
+    }
+
+    out
+}
+
+/// Returns the HTML of a `<video>` or `<audio>` element (the `element`) with controls.
+fn media_html(element: &str, url: &str, attr: &Attr) -> String {
+    let mut html = format!("<{element} src=\"{}\"", html_escape(url));
+
+    if !attr.identifier.is_empty() {
+        html.push_str(&format!(" id=\"{}\"", html_escape(&attr.identifier)));
+    }
+    if !attr.classes.is_empty() {
+        html.push_str(&format!(
+            " class=\"{}\"",
+            html_escape(&attr.classes.join(" "))
+        ));
+    }
+    for (key, value) in &attr.attributes {
+        html.push_str(&format!(" {}=\"{}\"", html_escape(key), html_escape(value)));
+    }
+
+    html.push_str(&format!(" controls></{element}>"));
+    html
+}
+
+impl<'builder, 'source> Builder<'builder, 'source>
+where
+    'source: 'builder,
+{
+    /// Adds a video or audio (the `element`) embed, which is a link to the file if raw HTML is
+    /// disabled.
+    pub(crate) fn add_media(&mut self, element: &str, url: &'source str, params: &[Cow<str>]) {
+        log::debug!("Parsing {} embed", element);
+
+        if self.config.disable_raw_html {
+            let link = Inline::Link(vec![Inline::Str(url)], LinkType::Href(url));
+            return self.document.add_block(Block::Plain(vec![link]));
+        }
+
+        let mut attr = self.take_carryover_attr();
+        let mut params = parameters_attr(params);
+        attr.classes.append(&mut params.classes);
+        attr.attributes.append(&mut params.attributes);
+        if !params.identifier.is_empty() {
+            attr.identifier = params.identifier;
+        }
+
+        let html = media_html(element, url, &attr);
+        self.document
+            .add_block(Block::Raw(Format(String::from("html")), html));
+    }
+
+    /// Adds the content of an `@embed html` tag as a raw block.
+    pub(crate) fn add_raw_html(&mut self) {
+        log::debug!("Parsing html embed");
+
+        if self.config.disable_raw_html {
+            return self.warn("Raw HTML is disabled, the html embed was removed");
+        }
+
+        let content = self.code_content();
+        self.document
+            .add_block(Block::Raw(Format(String::from("html")), content));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{html_escape, media_html};
+    use pandoc_types::definition::Attr;
+
+    #[test]
+    fn escaping() {
+        assert_eq!(
+            html_escape(r#"a "b" <c> & d"#),
+            "a &quot;b&quot; &lt;c&gt; &amp; d"
+        );
+    }
+
+    #[test]
+    fn media() {
+        let attr = Attr {
+            identifier: String::from("intro"),
+            classes: vec![String::from("wide")],
+            attributes: vec![(String::from("width"), String::from("640"))],
+        };
+
+        assert_eq!(
+            media_html("video", "clip.mp4", &attr),
+            r#"<video src="clip.mp4" id="intro" class="wide" width="640" controls></video>"#
+        );
+        assert_eq!(
+            media_html("audio", "a&b.ogg", &Attr::default()),
+            r#"<audio src="a&amp;b.ogg" controls></audio>"#
+        );
+    }
+}
@@ -0,0 +1,242 @@
+//! EPUB structural semantics (`epub:type` attributes on the sections of the document)
+
+use std::collections::HashMap;
+
+use pandoc_types::definition::{Attr, MetaValue};
+#[cfg(feature = "serde")]
+use serde::Deserialize;
+
+use crate::ir::Block;
+use crate::source_map::BlockPath;
+
+/// Configuration of the EPUB structural semantics profile.
+///
+/// When enabled through [`Config::epub_semantics`] every heading and its content are wrapped in a
+/// section `Div` with an `epub:type` attribute, the type is chosen as follows:
+///
+/// - If the heading's title (case insensitive) is in [`titles`], the mapped type is used.
+/// - Top level sections use the `epub-type` field of the document's metadata, or `chapter` if
+///   it's not present.
+/// - Other sections use `subchapter`.
+///
+/// [`Config::epub_semantics`]: crate::Config::epub_semantics
+/// [`titles`]: EpubSemantics::titles
+#[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
+pub struct EpubSemantics {
+    /// Maps lowercase heading titles to their `epub:type` (default: common titles like
+    /// `appendix`, `contents` or `glossary` mapped to their type).
+    pub titles: HashMap<String, String>,
+}
+
+impl Default for EpubSemantics {
+    fn default() -> Self {
+        let titles = [
+            ("appendix", "appendix"),
+            ("bibliography", "bibliography"),
+            ("contents", "toc"),
+            ("epilogue", "epilogue"),
+            ("foreword", "foreword"),
+            ("glossary", "glossary"),
+            ("index", "index"),
+            ("introduction", "introduction"),
+            ("preface", "preface"),
+            ("prologue", "prologue"),
+            ("table of contents", "toc"),
+        ];
+
+        Self {
+            titles: titles
+                .into_iter()
+                .map(|(title, ty)| (title.to_string(), ty.to_string()))
+                .collect(),
+        }
+    }
+}
+
+impl EpubSemantics {
+    /// Returns the `epub:type` of a section started by a heading.
+    fn section_type(&self, title: &str, level: i32, document_type: Option<&str>) -> String {
+        if let Some(ty) = self.titles.get(&title.to_lowercase()) {
+            return ty.clone();
+        }
+
+        match level {
+            1 => document_type.unwrap_or("chapter").to_string(),
+            _ => String::from("subchapter"),
+        }
+    }
+}
+
+/// Wraps each heading and the blocks that follow it (up until the next heading of the same or
+/// higher level) in a section `Div`, tagged with it's `epub:type` if `semantics` are passed.
+///
+/// If `move_ids` is set the identifier of the heading is moved to the section.
+///
+/// Returns the wrapped blocks and the new path of each of the passed blocks.
+pub(crate) fn wrap_sections<'source>(
+    blocks: Vec<Block<'source>>,
+    semantics: Option<&EpubSemantics>,
+    move_ids: bool,
+    metadata: &HashMap<String, MetaValue>,
+) -> (Vec<Block<'source>>, Vec<BlockPath>) {
+    let document_type = match metadata.get("epub-type") {
+        Some(MetaValue::MetaString(ty)) => Some(ty.as_str()),
+        _ => None,
+    };
+
+    let mut output = Vec::new();
+    let mut paths = Vec::with_capacity(blocks.len());
+    // The sections that are still open
+    let mut sections: Vec<Section> = Vec::new();
+
+    let close_section = |sections: &mut Vec<Section<'source>>, output: &mut Vec<Block<'source>>| {
+        if let Some(section) = sections.pop() {
+            let parent = sections
+                .last_mut()
+                .map_or(output, |parent| &mut parent.blocks);
+            parent.push(Block::Div(section.attr, section.blocks));
+        }
+    };
+
+    // Returns the path of the next block added to the innermost open section
+    let next_path = |sections: &[Section], output: &[Block]| {
+        let mut path: Vec<_> = sections.iter().map(|section| section.index).collect();
+        path.push(
+            sections
+                .last()
+                .map_or(output.len(), |section| section.blocks.len()),
+        );
+        BlockPath(path)
+    };
+
+    for mut block in blocks {
+        let Block::Header(level, ref mut header_attr, ref title) = block else {
+            paths.push(next_path(&sections, &output));
+
+            match sections.last_mut() {
+                Some(section) => section.blocks.push(block),
+                None => output.push(block),
+            }
+
+            continue;
+        };
+
+        while sections.last().is_some_and(|open| open.level >= level) {
+            close_section(&mut sections, &mut output);
+        }
+
+        let mut attr = Attr {
+            classes: vec![String::from("section")],
+            ..Default::default()
+        };
+
+        if let Some(semantics) = semantics {
+            let mut text = String::new();
+            for inline in title {
+                inline.push_text(&mut text);
+            }
+            let text = text.split_whitespace().collect::<Vec<_>>().join(" ");
+
+            attr.attributes.push((
+                String::from("epub:type"),
+                semantics.section_type(&text, level, document_type),
+            ));
+        }
+
+        if move_ids {
+            attr.identifier = std::mem::take(&mut header_attr.identifier);
+        }
+
+        // The section is added to it's parent when closed, and nothing else is added to the parent
+        // until then, so it's index is the current length of the parent
+        let index = sections
+            .last()
+            .map_or(output.len(), |section| section.blocks.len());
+        sections.push(Section {
+            level,
+            index,
+            attr,
+            blocks: Vec::new(),
+        });
+
+        paths.push(next_path(&sections, &output));
+        if let Some(section) = sections.last_mut() {
+            section.blocks.push(block);
+        }
+    }
+
+    while !sections.is_empty() {
+        close_section(&mut sections, &mut output);
+    }
+
+    (output, paths)
+}
+
+/// A section that is still being built.
+struct Section<'source> {
+    level: i32,
+    /// The index of the section in it's parent.
+    index: usize,
+    attr: Attr,
+    blocks: Vec<Block<'source>>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{wrap_sections, EpubSemantics};
+    use crate::ir::{Block, Inline};
+    use crate::source_map::BlockPath;
+    use pandoc_types::definition::{Attr, MetaValue};
+    use std::collections::HashMap;
+
+    /// Returns the `epub:type` of a section `Div` and it's blocks.
+    fn section<'a, 'source>(block: &'a Block<'source>) -> (&'a str, &'a [Block<'source>]) {
+        match block {
+            Block::Div(attr, blocks) => (&attr.attributes[0].1, blocks),
+            block => panic!("Expected a section, got {block:?}"),
+        }
+    }
+
+    #[test]
+    fn nested_sections() {
+        let blocks = vec![
+            Block::Plain(vec![Inline::Str("Before")]),
+            Block::Header(1, Attr::default(), vec![Inline::Str("One")]),
+            Block::Header(2, Attr::default(), vec![Inline::Str("Two")]),
+            Block::Plain(vec![Inline::Str("Content")]),
+            Block::Header(1, Attr::default(), vec![Inline::Str("Appendix")]),
+        ];
+        let metadata = HashMap::from([(
+            String::from("epub-type"),
+            MetaValue::MetaString(String::from("part")),
+        )]);
+
+        let (output, paths) =
+            wrap_sections(blocks, Some(&EpubSemantics::default()), false, &metadata);
+        assert_eq!(
+            paths,
+            vec![
+                BlockPath(vec![0]),
+                BlockPath(vec![1, 0]),
+                BlockPath(vec![1, 1, 0]),
+                BlockPath(vec![1, 1, 1]),
+                BlockPath(vec![2, 0]),
+            ]
+        );
+        assert_eq!(output.len(), 3);
+        assert!(matches!(output[0], Block::Plain(_)));
+
+        let (ty, blocks) = section(&output[1]);
+        assert_eq!(ty, "part");
+        assert_eq!(blocks.len(), 2);
+
+        let (ty, blocks) = section(&blocks[1]);
+        assert_eq!(ty, "subchapter");
+        assert_eq!(blocks.len(), 2);
+
+        let (ty, _) = section(&output[2]);
+        assert_eq!(ty, "appendix");
+    }
+}
@@ -1,6 +1,8 @@
+use crate::document::Task;
 use crate::ir::Inline;
+use crate::timestamps::Timestamp;
 use crate::Builder;
-#[cfg(serde)]
+#[cfg(feature = "serde")]
 use serde::Deserialize;
 
 /// Contains the symbols used to represent neorg's TODO status extension.
@@ -12,8 +14,8 @@ use serde::Deserialize;
 ///
 /// [`default`]: TodoSymbols::default
 #[derive(Debug)]
-#[cfg_attr(serde, derive(Deserialize))]
-#[cfg_attr(serde, serde(default))]
+#[cfg_attr(feature = "serde", derive(Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
 pub struct TodoSymbols {
     /// Task put down/cancelled `(_)` (default: ❌)
     pub cancelled: String,
@@ -48,16 +50,245 @@ impl Default for TodoSymbols {
     }
 }
 
+impl TodoSymbols {
+    /// Returns the symbol used for the given status.
+    pub fn symbol(&self, status: TodoStatus) -> &str {
+        match status {
+            TodoStatus::Cancelled => &self.cancelled,
+            TodoStatus::Done => &self.done,
+            TodoStatus::OnHold => &self.on_hold,
+            TodoStatus::Pending => &self.pending,
+            TodoStatus::Recurring => &self.recurring,
+            TodoStatus::Uncertain => &self.uncertain,
+            TodoStatus::Undone => &self.undone,
+            TodoStatus::Urgent => &self.urgent,
+        }
+    }
+}
+
+/// Contains the keywords used to represent a heading's TODO status in org-mode style.
+///
+/// When enabled through [`Config::heading_todo_keywords`] the status of headings is rendered as a
+/// leading keyword in the header text (for example `TODO Write docs`) instead of the symbols in
+/// [`TodoSymbols`], this allows pandoc's org-mode writer to output native org tasks.
+///
+/// [`Config::heading_todo_keywords`]: crate::Config::heading_todo_keywords
+#[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
+pub struct TodoKeywords {
+    /// Task put down/cancelled `(_)` (default: CANCELLED)
+    pub cancelled: String,
+    /// Task done `(x)` (default: DONE)
+    pub done: String,
+    /// Task on hold `(=)` (default: HOLD)
+    pub on_hold: String,
+    /// Task in-progress/pending `(-)` (default: STARTED)
+    pub pending: String,
+    /// Task recurring `(+)` (default: TODO)
+    pub recurring: String,
+    /// Task needs further input/clarification `(?)` (default: WAITING)
+    pub uncertain: String,
+    /// Task undone `( )` (default: TODO)
+    pub undone: String,
+    /// Task urgent `(!)` (default: TODO)
+    pub urgent: String,
+}
+
+impl Default for TodoKeywords {
+    fn default() -> Self {
+        Self {
+            cancelled: String::from("CANCELLED"),
+            done: String::from("DONE"),
+            on_hold: String::from("HOLD"),
+            pending: String::from("STARTED"),
+            recurring: String::from("TODO"),
+            uncertain: String::from("WAITING"),
+            undone: String::from("TODO"),
+            urgent: String::from("TODO"),
+        }
+    }
+}
+
+impl TodoKeywords {
+    /// Returns the keyword used for the given status.
+    pub fn keyword(&self, status: TodoStatus) -> &str {
+        match status {
+            TodoStatus::Cancelled => &self.cancelled,
+            TodoStatus::Done => &self.done,
+            TodoStatus::OnHold => &self.on_hold,
+            TodoStatus::Pending => &self.pending,
+            TodoStatus::Recurring => &self.recurring,
+            TodoStatus::Uncertain => &self.uncertain,
+            TodoStatus::Undone => &self.undone,
+            TodoStatus::Urgent => &self.urgent,
+        }
+    }
+}
+
+/// How the TODO statuses of items are rendered (the statuses of headings use the
+/// [`TodoKeywords`] instead if they're enabled).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum TodoOutputMode {
+    /// The symbol of the status in [`TodoSymbols`] (default).
+    #[default]
+    Symbols,
+    /// A `Span` containing the symbol, with the `todo-<status>` class (for example `todo-done` or
+    /// `todo-on-hold`) and the status in the `data-todo` attribute, so that the statuses can be
+    /// styled with CSS or changed by filters.
+    Spans,
+    /// The ballot box characters (`☐` and `☒` for done tasks) that pandoc recognizes as task
+    /// list checkboxes, so that list items are written as GitHub style task list items
+    /// (`- [ ] item` and `- [x] item`) in markdown.
+    ///
+    /// The statuses other than done can't be told apart, so uncertain items aren't extracted as
+    /// flashcards in this mode.
+    Checkbox,
+}
+
+/// The status of a task as defined by neorg's TODO status extension.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TodoStatus {
+    /// Task put down/cancelled `(_)`
+    Cancelled,
+    /// Task done `(x)`
+    Done,
+    /// Task on hold `(=)`
+    OnHold,
+    /// Task in-progress/pending `(-)`
+    Pending,
+    /// Task recurring `(+)`
+    Recurring,
+    /// Task needs further input/clarification `(?)`
+    Uncertain,
+    /// Task undone `( )`
+    Undone,
+    /// Task urgent `(!)`
+    Urgent,
+}
+
+impl TodoStatus {
+    /// Returns the name of the status in kebab case (for example `on-hold`).
+    pub fn name(self) -> &'static str {
+        match self {
+            TodoStatus::Cancelled => "cancelled",
+            TodoStatus::Done => "done",
+            TodoStatus::OnHold => "on-hold",
+            TodoStatus::Pending => "pending",
+            TodoStatus::Recurring => "recurring",
+            TodoStatus::Uncertain => "uncertain",
+            TodoStatus::Undone => "undone",
+            TodoStatus::Urgent => "urgent",
+        }
+    }
+
+    /// Returns the status with the given name (the name of the variant in snake case, `-` is
+    /// also accepted as a separator).
+    pub(crate) fn from_name(name: &str) -> Option<Self> {
+        Some(match name.replace('-', "_").as_str() {
+            "cancelled" => TodoStatus::Cancelled,
+            "done" => TodoStatus::Done,
+            "on_hold" => TodoStatus::OnHold,
+            "pending" => TodoStatus::Pending,
+            "recurring" => TodoStatus::Recurring,
+            "uncertain" => TodoStatus::Uncertain,
+            "undone" => TodoStatus::Undone,
+            "urgent" => TodoStatus::Urgent,
+            _ => return None,
+        })
+    }
+
+    /// Returns the status represented by a treesitter node kind.
+    fn from_node_kind(kind: &str) -> Option<Self> {
+        Some(match kind {
+            "todo_item_cancelled" => TodoStatus::Cancelled,
+            "todo_item_done" => TodoStatus::Done,
+            "todo_item_on_hold" => TodoStatus::OnHold,
+            "todo_item_pending" => TodoStatus::Pending,
+            "todo_item_recurring" => TodoStatus::Recurring,
+            "todo_item_uncertain" => TodoStatus::Uncertain,
+            "todo_item_undone" => TodoStatus::Undone,
+            "todo_item_urgent" => TodoStatus::Urgent,
+            _ => return None,
+        })
+    }
+}
+
+/// The kinds of detached modifier extensions, see [`ExtensionOrder`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum ExtensionKind {
+    /// The TODO status (`( )`, `(x)`, ...)
+    TodoStatus,
+    /// The priority (`(# A)`)
+    Priority,
+    /// The timestamp (`(@ 2024-06-01)`)
+    Timestamp,
+    /// The due date (`(< 2024-06-01)`)
+    DueDate,
+    /// The start date (`(> 2024-06-01)`)
+    StartDate,
+}
+
+/// The order in which the extensions of a detached modifier are rendered.
+///
+/// Neorg allows chaining multiple extensions on the same item (`( |# A|< 2024-06-01)`), they
+/// are rendered in this order separated by spaces no matter the order they were written in, the
+/// kinds missing from the order aren't rendered (but TODO statuses are still collected as tasks).
+///
+/// The priority and dates are rendered as `Span`s with the `priority`, `timestamp`, `due-date`
+/// and `start-date` classes respectively.
+#[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(Deserialize))]
+#[cfg_attr(feature = "serde", serde(transparent))]
+pub struct ExtensionOrder(pub Vec<ExtensionKind>);
+
+impl Default for ExtensionOrder {
+    fn default() -> Self {
+        Self(vec![
+            ExtensionKind::TodoStatus,
+            ExtensionKind::Priority,
+            ExtensionKind::Timestamp,
+            ExtensionKind::StartDate,
+            ExtensionKind::DueDate,
+        ])
+    }
+}
+
+/// A parsed detached modifier extension.
+enum Extension<'source> {
+    TodoStatus(TodoStatus),
+    /// The kind of extension, the class of the span used to render it and it's value.
+    Value(ExtensionKind, &'static str, &'source str),
+}
+
+impl Extension<'_> {
+    fn kind(&self) -> ExtensionKind {
+        match *self {
+            Extension::TodoStatus(_) => ExtensionKind::TodoStatus,
+            Extension::Value(kind, _, _) => kind,
+        }
+    }
+}
+
 impl<'builder, 'source> Builder<'builder, 'source>
 where
     'source: 'builder,
 {
-    pub fn handle_detached_ext(&mut self) {
+    /// Handles a detached modifier extension, `heading` should be set if the extension belongs to
+    /// a heading.
+    pub fn handle_detached_ext(&mut self, heading: bool) {
+        let mut extensions = Vec::new();
+        let first_task = self.context.tasks.len();
+
         self.visit_children(|this| {
             let node = this.cursor.node();
 
-            match node.kind() {
-                "_begin" | "_end" | "_delimiter" => {}
+            let (kind, class) = match node.kind() {
+                "_begin" | "_end" | "_delimiter" => return,
 
                 "todo_item_cancelled"
                 | "todo_item_done"
@@ -66,26 +297,117 @@ where
                 | "todo_item_recurring"
                 | "todo_item_uncertain"
                 | "todo_item_undone"
-                | "todo_item_urgent" => this.add_todo_status(node.kind()),
-                kind => log::error!("Unknown detached modifier extension: {kind}"),
-            }
+                | "todo_item_urgent" => {
+                    if let Some(status) = this.add_task(node.kind()) {
+                        extensions.push(Extension::TodoStatus(status));
+                    }
+                    return;
+                }
+                "priority" => (ExtensionKind::Priority, "priority"),
+                "timestamp" => (ExtensionKind::Timestamp, "timestamp"),
+                "due_date" => (ExtensionKind::DueDate, "due-date"),
+                "start_date" => (ExtensionKind::StartDate, "start-date"),
+                kind => return this.error(format!("Unknown detached modifier extension: {kind}")),
+            };
+
+            // The value is everything after the extension's symbol
+            let text = node
+                .utf8_text(this.source.as_bytes())
+                .expect("Invalid text");
+            let mut chars = text.chars();
+            chars.next();
+
+            extensions.push(Extension::Value(kind, class, chars.as_str().trim()));
         });
+
+        if self.context.tasks.len() > first_task {
+            self.add_task_dates(first_task, &extensions);
+        }
+
+        let order = &self.config.extension_order.0;
+        let mut extensions: Vec<_> = extensions
+            .into_iter()
+            .filter_map(|ext| Some((order.iter().position(|kind| *kind == ext.kind())?, ext)))
+            .collect();
+        extensions.sort_by_key(|(position, _)| *position);
+
+        let keywords = match heading {
+            true => self.config.heading_todo_keywords.as_ref(),
+            false => None,
+        };
+
+        let rendered = !extensions.is_empty();
+        for (idx, (_, extension)) in extensions.into_iter().enumerate() {
+            if idx != 0 {
+                self.document.push_inlines_collector(Inline::Space);
+            }
+
+            let inline = match extension {
+                Extension::TodoStatus(status) => match keywords {
+                    Some(keywords) => Inline::Str(keywords.keyword(status)),
+                    None => Inline::TodoStatus(status),
+                },
+                Extension::Value(_, class, value) => Inline::Span(vec![Inline::Str(value)], class),
+            };
+            self.document.push_inlines_collector(inline);
+        }
+
+        // Heading titles have their leading spaces removed
+        if rendered && heading {
+            self.document.push_inlines_collector(Inline::Space);
+        }
     }
 
-    fn add_todo_status(&mut self, status: &str) {
-        let todo_symbols = &self.config.todo_symbols;
-        let icon = match status {
-            "todo_item_cancelled" => todo_symbols.cancelled.as_str(),
-            "todo_item_done" => todo_symbols.done.as_str(),
-            "todo_item_on_hold" => todo_symbols.on_hold.as_str(),
-            "todo_item_pending" => todo_symbols.pending.as_str(),
-            "todo_item_recurring" => todo_symbols.recurring.as_str(),
-            "todo_item_uncertain" => todo_symbols.uncertain.as_str(),
-            "todo_item_undone" => todo_symbols.undone.as_str(),
-            "todo_item_urgent" => todo_symbols.urgent.as_str(),
-            status => return log::error!("Unknown todo status: {status}"),
+    /// Adds the task defined by a TODO status node to the context, and returns it's status.
+    fn add_task(&mut self, kind: &str) -> Option<TodoStatus> {
+        let Some(status) = TodoStatus::from_node_kind(kind) else {
+            self.error(format!("Unknown todo status: {kind}"));
+            return None;
         };
 
-        self.document.push_inlines_collector(Inline::Str(icon));
+        // The task's text is the rest of the line after the detached modifier extension
+        let text = self
+            .cursor
+            .node()
+            .parent()
+            .and_then(|ext| self.source[ext.end_byte()..].lines().next())
+            .unwrap_or_default();
+
+        self.context.tasks.push(Task {
+            status,
+            text: text.split_whitespace().collect::<Vec<_>>().join(" "),
+            section: self.section,
+            heading: self.heading,
+            start: None,
+            due: None,
+        });
+
+        Some(status)
+    }
+
+    /// Sets the start and due dates of the task at `idx` from the extensions of it's detached
+    /// modifier, dates that can't be parsed are reported and ignored.
+    fn add_task_dates(&mut self, idx: usize, extensions: &[Extension]) {
+        for extension in extensions {
+            let Extension::Value(
+                kind @ (ExtensionKind::StartDate | ExtensionKind::DueDate),
+                _,
+                value,
+            ) = *extension
+            else {
+                continue;
+            };
+
+            let Some(date) = Timestamp::parse(value) else {
+                self.warn(format!("Invalid date of task: {value}"));
+                continue;
+            };
+
+            let task = &mut self.context.tasks[idx];
+            match kind {
+                ExtensionKind::StartDate => task.start = Some(date),
+                _ => task.due = Some(date),
+            }
+        }
     }
 }
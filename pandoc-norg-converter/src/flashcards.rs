@@ -0,0 +1,159 @@
+//! Extraction of flashcards from converted documents
+
+use pandoc_types::definition::{Block, Inline, Pandoc};
+
+use crate::document::DocumentContext;
+use crate::stringify::{inlines_to_string, push_blocks_text};
+
+/// A question/answer pair extracted from a document.
+///
+/// Flashcards are created from:
+/// - Definitions, the term is the front of the card and the definition the back.
+/// - List items with the uncertain TODO status (`(?)`) and nested content, the item's text is the
+///   front of the card and the nested content (for example a sub list) the back.
+///
+/// This is returned by [`Frontend::convert_with_flashcards`].
+///
+/// [`Frontend::convert_with_flashcards`]: crate::Frontend::convert_with_flashcards
+#[derive(Debug, Clone, PartialEq)]
+pub struct Flashcard {
+    pub front: Vec<Inline>,
+    pub back: Vec<Block>,
+}
+
+impl Flashcard {
+    /// Returns the plain text of the front of the card.
+    pub fn front_text(&self) -> String {
+        inlines_to_string(&self.front).trim().to_string()
+    }
+
+    /// Returns the plain text of the back of the card, blocks are separated by newlines.
+    pub fn back_text(&self) -> String {
+        let mut text = String::new();
+        push_blocks_text(&self.back, &mut text);
+
+        let lines: Vec<_> = text
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty())
+            .collect();
+        lines.join("\n")
+    }
+}
+
+/// Extracts the flashcards in a converted document.
+pub(crate) fn extract_flashcards(document: &Pandoc, context: &DocumentContext) -> Vec<Flashcard> {
+    let mut cards = Vec::new();
+    collect_flashcards(&document.blocks, context, &mut cards);
+    cards
+}
+
+fn collect_flashcards(blocks: &[Block], context: &DocumentContext, cards: &mut Vec<Flashcard>) {
+    for block in blocks {
+        match block {
+            Block::DefinitionList(entries) => {
+                for (term, definitions) in entries {
+                    cards.push(Flashcard {
+                        front: term.clone(),
+                        back: definitions.concat(),
+                    });
+
+                    for definition in definitions {
+                        collect_flashcards(definition, context, cards);
+                    }
+                }
+            }
+            Block::BulletList(items) | Block::OrderedList(_, items) => {
+                for item in items {
+                    if let Some(card) = question_item(item, context) {
+                        cards.push(card);
+                    }
+
+                    collect_flashcards(item, context, cards);
+                }
+            }
+            Block::BlockQuote(blocks) | Block::Div(_, blocks) => {
+                collect_flashcards(blocks, context, cards)
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Returns the flashcard defined by a list item if it's marked as uncertain and has nested
+/// content.
+fn question_item(item: &[Block], context: &DocumentContext) -> Option<Flashcard> {
+    let symbol = &context.config.todo_symbols.uncertain;
+    let (Block::Para(inlines) | Block::Plain(inlines)) = item.first()? else {
+        return None;
+    };
+
+    match inlines.first() {
+        Some(Inline::Str(str)) if !symbol.is_empty() && str == symbol => {}
+        Some(Inline::Span(attr, _)) if attr.classes.iter().any(|c| c == "todo-uncertain") => {}
+        _ => return None,
+    }
+
+    if item.len() < 2 {
+        return None;
+    }
+
+    let front = match inlines.get(1) {
+        Some(Inline::Space) => &inlines[2..],
+        _ => &inlines[1..],
+    };
+
+    Some(Flashcard {
+        front: front.to_vec(),
+        back: item[1..].to_vec(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::extract_flashcards;
+    use crate::document::DocumentContext;
+    use crate::Config;
+    use pandoc_types::definition::{Block, Inline, Pandoc};
+
+    fn plain(text: &str) -> Block {
+        Block::Plain(vec![Inline::Str(text.to_string())])
+    }
+
+    #[test]
+    fn flashcards() {
+        let config = Config::default();
+        let context = DocumentContext::new(&config);
+        let uncertain = Inline::Str(config.todo_symbols.uncertain.clone());
+
+        let document = Pandoc {
+            meta: Default::default(),
+            blocks: vec![
+                Block::DefinitionList(vec![(
+                    vec![Inline::Str("Term".to_string())],
+                    vec![vec![plain("Definition")]],
+                )]),
+                Block::BulletList(vec![
+                    vec![
+                        Block::Para(vec![
+                            uncertain.clone(),
+                            Inline::Space,
+                            Inline::Str("Question".to_string()),
+                        ]),
+                        Block::BulletList(vec![vec![plain("Answer")], vec![plain("More")]]),
+                    ],
+                    // Not a question since it has no answer
+                    vec![Block::Para(vec![uncertain, Inline::Space])],
+                ]),
+            ],
+        };
+
+        let cards = extract_flashcards(&document, &context);
+
+        assert_eq!(cards.len(), 2);
+        assert_eq!(cards[0].front_text(), "Term");
+        assert_eq!(cards[0].back_text(), "Definition");
+        assert_eq!(cards[1].front_text(), "Question");
+        assert_eq!(cards[1].back_text(), "Answer\nMore");
+    }
+}
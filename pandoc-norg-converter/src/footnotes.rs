@@ -0,0 +1,130 @@
+//! Footnotes, both the single paragraph (`^ Title`) and the ranged (`^^ Title ... ^^`) ones
+//!
+//! The content of each footnote is collected in the [`DocumentContext`] while walking the tree and
+//! is placed in a pandoc `Note` wherever a footnote link (`{^ Title}`) references it.
+
+use std::collections::HashMap;
+
+use pandoc_types::definition::Block as PandocBlock;
+
+use crate::document::{DefinitionKind, DocumentContext};
+use crate::ir::{convert_blocks_to_pandoc, Block};
+use crate::Builder;
+
+/// The footnotes of a document, by their normalized title (see [`normalize_footnote_name`]).
+#[derive(Default)]
+pub struct Footnotes<'source> {
+    /// The content of the footnotes collected while walking the tree.
+    pending: HashMap<String, Vec<Block<'source>>>,
+    /// The content of the footnotes converted to pandoc, see [`DocumentContext::build_footnotes`].
+    built: HashMap<String, Vec<PandocBlock>>,
+}
+
+impl<'source> Footnotes<'source> {
+    /// Adds the footnotes of `other` (which was built after `self`) to `self`.
+    pub fn merge(&mut self, other: Footnotes<'source>) {
+        self.pending.extend(other.pending);
+    }
+
+    /// Applies the transform to the content of the footnotes that weren't built yet.
+    #[cfg(feature = "ir")]
+    pub fn transform(&mut self, transform: &mut dyn crate::Transform) {
+        for blocks in self.pending.values_mut() {
+            crate::transform::walk_blocks(transform, blocks);
+        }
+    }
+}
+
+/// Returns the key used to match footnote links with the footnotes.
+pub(crate) fn normalize_footnote_name(title: &str) -> String {
+    title
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .to_lowercase()
+}
+
+impl<'builder, 'source> Builder<'builder, 'source>
+where
+    'source: 'builder,
+{
+    pub fn handle_footnote_list(&mut self) {
+        log::debug!("Parsing footnote list");
+
+        self.visit_children(Self::handle_footnote);
+    }
+
+    fn handle_footnote(&mut self) {
+        log::debug!("Parsing footnote");
+
+        let mut name = None;
+
+        self.document.push_scope();
+
+        self.visit_children(|this| {
+            if this.cursor.field_id() == this.field_ids.content {
+                this.handle_node();
+            } else if this.cursor.field_id() == this.field_ids.title {
+                let node = this.cursor.node();
+                name = Some(normalize_footnote_name(&this.source[node.byte_range()]));
+            } else if this.cursor.field_id() == this.field_ids.state
+                || this.cursor.node().kind() == "detached_modifier_extension"
+            {
+                // The rendered detached modifier extensions are placed before the content
+                this.handle_detached_ext(false);
+            }
+        });
+
+        // The extensions of footnotes without content weren't taken by a paragraph
+        let status = self.document.take_inlines_collector();
+        let mut blocks = self.document.pop_scope();
+        if !status.is_empty() {
+            blocks.insert(0, Block::Plain(status));
+        }
+
+        let Some(name) = name else {
+            return self.error("Footnote without a title");
+        };
+
+        let range = self.cursor.node().byte_range();
+        self.context
+            .define(DefinitionKind::Footnote, name.clone(), range);
+        self.context.footnotes.pending.insert(name, blocks);
+    }
+}
+
+impl DocumentContext<'_> {
+    /// Converts the collected footnotes to pandoc, this must be called before building the
+    /// document so that the footnote links can be resolved.
+    pub fn build_footnotes(&mut self) {
+        // Sorted so that the diagnostics of the conversion are always reported in the same order
+        let mut pending: Vec<_> = std::mem::take(&mut self.footnotes.pending)
+            .into_iter()
+            .collect();
+        pending.sort_by(|(a, _), (b, _)| a.cmp(b));
+
+        let built = pending
+            .into_iter()
+            .map(|(name, blocks)| (name, convert_blocks_to_pandoc(blocks, self)))
+            .collect();
+        self.footnotes.built = built;
+    }
+
+    /// Returns the content of the footnote with the given title (if it exists).
+    pub fn get_footnote(&self, title: &str) -> Option<&Vec<PandocBlock>> {
+        self.footnotes.built.get(&normalize_footnote_name(title))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::normalize_footnote_name;
+
+    #[test]
+    fn footnote_names() {
+        assert_eq!(
+            normalize_footnote_name("  A  Note\n  Title "),
+            "a note title"
+        );
+    }
+}
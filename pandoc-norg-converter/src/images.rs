@@ -0,0 +1,194 @@
+//! Images, both embedded with `@embed image` and with the `@image` tag
+//!
+//! The content of the tags is the path or URL of the image and the parameters are the image's
+//! attributes (`key=value`) and classes, the `caption`, `alt` and `title` attributes are used as
+//! the caption (which makes the image a figure), alternative text and title of the image.
+
+use std::borrow::Cow;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+#[cfg(feature = "serde")]
+use serde::Deserialize;
+
+use crate::ir::{Block, Image, Inline};
+use crate::tags::parameters_attr;
+use crate::Builder;
+
+/// Configuration of the inlining of local images as `data:` URIs, which makes the output
+/// self-contained (for example for sharing a single html file).
+///
+/// Only images with a path (not a URL) and a known image extension (`png`, `jpg`, `jpeg`,
+/// `gif`, `webp` or `svg`) are inlined.
+#[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
+pub struct InlineImages {
+    /// Images bigger than this size (in bytes) are kept as links (default: 64 KiB).
+    pub max_size: u64,
+    /// The directory that relative paths are resolved against (default: the working directory).
+    pub base_dir: Option<PathBuf>,
+}
+
+impl Default for InlineImages {
+    fn default() -> Self {
+        Self {
+            max_size: 64 * 1024,
+            base_dir: None,
+        }
+    }
+}
+
+impl InlineImages {
+    /// Returns the `data:` URI of the image at `url`, or `None` if it shouldn't be inlined.
+    fn data_uri(&self, url: &str) -> Result<Option<String>, String> {
+        if url.contains("://") || url.starts_with("data:") {
+            return Ok(None);
+        }
+
+        let path = Path::new(url);
+        let Some(mime) = path
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .and_then(mime_type)
+        else {
+            return Ok(None);
+        };
+
+        let path = match &self.base_dir {
+            Some(base_dir) => base_dir.join(path),
+            None => path.to_path_buf(),
+        };
+        let error = |err| format!("Failed to inline image {}: {err}", path.display());
+
+        let size = fs::metadata(&path).map_err(error)?.len();
+        if size > self.max_size {
+            log::debug!("Not inlining image {} ({size} bytes)", path.display());
+            return Ok(None);
+        }
+
+        let data = fs::read(&path).map_err(error)?;
+        Ok(Some(format!("data:{mime};base64,{}", base64(&data))))
+    }
+}
+
+/// Returns the MIME type of images with the given extension.
+fn mime_type(extension: &str) -> Option<&'static str> {
+    let mime = match extension.to_lowercase().as_str() {
+        "png" => "image/png",
+        "jpg" | "jpeg" => "image/jpeg",
+        "gif" => "image/gif",
+        "webp" => "image/webp",
+        "svg" => "image/svg+xml",
+        _ => return None,
+    };
+    Some(mime)
+}
+
+/// Encodes the data with the standard base64 alphabet (with padding).
+fn base64(data: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+    let mut out = String::with_capacity(data.len().div_ceil(3) * 4);
+
+    for chunk in data.chunks(3) {
+        let bytes = [
+            chunk[0],
+            *chunk.get(1).unwrap_or(&0),
+            *chunk.get(2).unwrap_or(&0),
+        ];
+        let group = u32::from_be_bytes([0, bytes[0], bytes[1], bytes[2]]);
+
+        for idx in 0..4 {
+            if idx <= chunk.len() {
+                let sextet = (group >> (18 - idx * 6)) & 0x3f;
+                out.push(ALPHABET[sextet as usize] as char);
+            } else {
+                out.push('=');
+            }
+        }
+    }
+
+    out
+}
+
+impl<'builder, 'source> Builder<'builder, 'source>
+where
+    'source: 'builder,
+{
+    pub(crate) fn handle_image_block(&mut self, parameters: &[Cow<'source, str>]) {
+        log::debug!("Parsing image block");
+
+        let text = self
+            .cursor
+            .node()
+            .utf8_text(self.source.as_bytes())
+            .expect("Invalid text");
+
+        self.add_image(Cow::Borrowed(text.trim()), parameters);
+    }
+
+    /// Adds an image (or a figure if it has a caption) with the given parameters, merged with
+    /// the attributes of the carryover tags.
+    ///
+    /// The caption is either the `caption=` parameter or a `#caption` carryover tag, and is also
+    /// used as the alternative text of the image unless an `alt=` parameter is passed.
+    pub(crate) fn add_image(&mut self, url: Cow<'source, str>, parameters: &[Cow<'source, str>]) {
+        let carryover_caption = self.take_carryover_caption();
+        let mut attr = parameters_attr(parameters);
+        let mut carryover = self.take_carryover_attr();
+        carryover.attributes.append(&mut attr.attributes);
+        carryover.classes.append(&mut attr.classes);
+        if !attr.identifier.is_empty() {
+            carryover.identifier = attr.identifier;
+        }
+
+        let url = match self.config.inline_images.as_ref().map(|c| c.data_uri(&url)) {
+            Some(Ok(Some(data_uri))) => Cow::Owned(data_uri),
+            Some(Err(err)) => {
+                self.warn(err);
+                url
+            }
+            Some(Ok(None)) | None => url,
+        };
+
+        let mut image = Image {
+            url,
+            attr: carryover,
+            alt: String::new(),
+            title: String::new(),
+        };
+        let caption = image
+            .take_attribute("caption")
+            .or(carryover_caption)
+            .filter(|caption| !caption.trim().is_empty());
+        image.alt = image
+            .take_attribute("alt")
+            .or_else(|| caption.clone())
+            .unwrap_or_default();
+        image.title = image.take_attribute("title").unwrap_or_default();
+
+        match caption {
+            Some(caption) => self.document.add_block(Block::Figure(caption, image)),
+            None => {
+                let segment = vec![Inline::Image(image)];
+                self.document.add_block(Block::Plain(segment));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::base64;
+
+    #[test]
+    fn encoding() {
+        assert_eq!(base64(b""), "");
+        assert_eq!(base64(b"f"), "Zg==");
+        assert_eq!(base64(b"fo"), "Zm8=");
+        assert_eq!(base64(b"foo"), "Zm9v");
+        assert_eq!(base64(b"foobar"), "Zm9vYmFy");
+        assert_eq!(base64(&[0xff, 0xfe, 0x00, 0x10]), "//4AEA==");
+    }
+}
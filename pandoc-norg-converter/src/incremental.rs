@@ -0,0 +1,196 @@
+//! Incremental conversion of documents that are edited often (like in editor previews)
+
+use pandoc_types::definition::Pandoc;
+use tree_sitter::{InputEdit, Point, Tree};
+
+use crate::Frontend;
+
+/// The parsed syntax tree of a document, kept between conversions by
+/// [`Frontend::convert_incremental`] so that only the edited parts of the document are parsed
+/// again.
+///
+/// A new (empty) `NorgTree` is created with [`NorgTree::new`], the first conversion using it
+/// parses the whole document. The edits made to the document after a conversion should be passed
+/// to [`NorgTree::edit`] (like editors already do for tree-sitter), otherwise they are found by
+/// comparing the source with a copy of the previous one.
+#[derive(Default)]
+pub struct NorgTree {
+    tree: Option<Tree>,
+    /// The source the tree was parsed from, only kept to find the edits when they aren't passed
+    /// to [`NorgTree::edit`].
+    source: Option<String>,
+    /// Whether the edits are passed to [`NorgTree::edit`], in which case the source isn't kept.
+    tracks_edits: bool,
+}
+
+impl NorgTree {
+    /// Creates an empty `NorgTree`.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Updates the tree with an edit made to the document since the last conversion.
+    ///
+    /// Once this is called, all the following edits must be passed to it (in the order they were
+    /// made), the sources are no longer compared.
+    pub fn edit(&mut self, edit: &InputEdit) {
+        if let Some(tree) = self.tree.as_mut() {
+            tree.edit(edit);
+        }
+
+        self.tracks_edits = true;
+        self.source = None;
+    }
+
+    /// Returns the tree for `source` (parsed with the `frontend`'s parser), parsing only the
+    /// parts of it that changed since the last time it was parsed.
+    fn reparse(&mut self, frontend: &mut Frontend, source: &str) -> &Tree {
+        let tree = match (self.tree.take(), self.source.as_deref()) {
+            (Some(tree), Some(old_source)) if old_source == source => tree,
+            (Some(mut tree), Some(old_source)) => {
+                tree.edit(&compute_edit(old_source, source));
+                frontend.parse(source, Some(&tree))
+            }
+            // The edits were already applied by `NorgTree::edit`
+            (Some(tree), None) => frontend.parse(source, Some(&tree)),
+            (None, _) => frontend.parse(source, None),
+        };
+
+        if !self.tracks_edits {
+            // The buffer of the previous source is reused
+            let old_source = self.source.get_or_insert_with(String::new);
+            old_source.clear();
+            old_source.push_str(source);
+        }
+
+        self.tree.insert(tree)
+    }
+}
+
+impl Frontend {
+    /// Converts the passed neorg source code to it's pandoc representation, reusing the syntax
+    /// tree of the previous conversion stored in `tree` (if passed).
+    ///
+    /// The tree is updated with the edits passed to [`NorgTree::edit`] since the previous
+    /// conversion (or with the edit found by comparing the sources if none were passed) and only
+    /// the changed parts are parsed again, which is much faster than parsing the whole document
+    /// for small edits (like typing in an editor). The tree of `source` is stored in `tree` for
+    /// the next conversion.
+    ///
+    /// Only the parsing is incremental, the whole document is still converted.
+    pub fn convert_incremental(&mut self, source: &str, tree: Option<&mut NorgTree>) -> Pandoc {
+        let Some(tree) = tree else {
+            return self.convert(source);
+        };
+
+        #[cfg(feature = "tracing")]
+        let _span = tracing::info_span!("convert", bytes = source.len()).entered();
+
+        let tree = tree.reparse(self, source);
+        self.convert_tree(source, tree, |document, _, _| document)
+    }
+}
+
+/// Returns the edit that turns `old` into `new`, the changed range is the range between the
+/// common prefix and suffix of both texts.
+fn compute_edit(old: &str, new: &str) -> InputEdit {
+    let mut prefix = old
+        .bytes()
+        .zip(new.bytes())
+        .take_while(|(a, b)| a == b)
+        .count();
+    while !old.is_char_boundary(prefix) || !new.is_char_boundary(prefix) {
+        prefix -= 1;
+    }
+
+    // The suffix can't overlap the prefix
+    let max_suffix = old.len().min(new.len()) - prefix;
+    let mut suffix = old
+        .bytes()
+        .rev()
+        .zip(new.bytes().rev())
+        .take(max_suffix)
+        .take_while(|(a, b)| a == b)
+        .count();
+    while !old.is_char_boundary(old.len() - suffix) || !new.is_char_boundary(new.len() - suffix) {
+        suffix -= 1;
+    }
+
+    let old_end_byte = old.len() - suffix;
+    let new_end_byte = new.len() - suffix;
+
+    InputEdit {
+        start_byte: prefix,
+        old_end_byte,
+        new_end_byte,
+        start_position: position(old, prefix),
+        old_end_position: position(old, old_end_byte),
+        new_end_position: position(new, new_end_byte),
+    }
+}
+
+/// Returns the row and (byte) column of the byte offset in the text.
+fn position(text: &str, offset: usize) -> Point {
+    let before = &text[..offset];
+    let row = before.matches('\n').count();
+    let column = match before.rfind('\n') {
+        Some(newline) => offset - newline - 1,
+        None => offset,
+    };
+
+    Point { row, column }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{compute_edit, NorgTree};
+    use crate::Frontend;
+    use tree_sitter::Point;
+
+    #[test]
+    fn edits() {
+        let edit = compute_edit("* Title\nSome text\n", "* Title\nSome more text\n");
+        assert_eq!(edit.start_byte, 13);
+        assert_eq!(edit.old_end_byte, 13);
+        assert_eq!(edit.new_end_byte, 18);
+        assert_eq!(edit.start_position, Point { row: 1, column: 5 });
+        assert_eq!(edit.new_end_position, Point { row: 1, column: 10 });
+
+        // Repeated characters are only counted once
+        let edit = compute_edit("aaa", "aa");
+        assert_eq!(
+            (edit.start_byte, edit.old_end_byte, edit.new_end_byte),
+            (2, 3, 2)
+        );
+
+        // Edits never split a character
+        let edit = compute_edit("é", "è");
+        assert_eq!(
+            (edit.start_byte, edit.old_end_byte, edit.new_end_byte),
+            (0, 2, 2)
+        );
+
+        let edit = compute_edit("line\n", "line\nnew");
+        assert_eq!(edit.old_end_position, Point { row: 1, column: 0 });
+        assert_eq!(edit.new_end_position, Point { row: 1, column: 3 });
+    }
+
+    #[test]
+    fn passed_edits() {
+        let old = "* Title\nSome text\n";
+        let new = "* Title\nSome more text\n";
+
+        let mut frontend = Frontend::default();
+        let mut tree = NorgTree::new();
+        frontend.convert_incremental(old, Some(&mut tree));
+        assert!(tree.source.is_some());
+
+        tree.edit(&compute_edit(old, new));
+        assert!(tree.source.is_none());
+
+        frontend.reset();
+        let document = frontend.convert_incremental(new, Some(&mut tree));
+        assert_eq!(document, Frontend::default().convert(new));
+        assert!(tree.source.is_none());
+    }
+}
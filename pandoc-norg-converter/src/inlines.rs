@@ -1,6 +1,12 @@
-use crate::document::DocumentLinkType;
-use crate::ir::{Inline, LinkType};
-use crate::Builder;
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use crate::document::{DefinitionKind, DocumentLinkType};
+use crate::ir::{FileTarget, Inline, LinkType};
+use crate::{trim_spaces, Builder};
+#[cfg(feature = "serde")]
+use serde::Deserialize;
 
 impl<'builder, 'source> Builder<'builder, 'source>
 where
@@ -34,7 +40,7 @@ where
 
                 match text {
                     "~" => {}
-                    modifier => log::error!("Unknown trailing modifier {}", modifier),
+                    modifier => self.error(format!("Unknown trailing modifier {}", modifier)),
                 }
             }
             "_line_break" => {}
@@ -57,74 +63,127 @@ where
             "anchor_declaration" => inlines.push(self.handle_link(true)),
             "anchor_definition" => inlines.push(self.handle_link(true)),
             // Attached modifiers
-            "bold" => inlines.push(Inline::Strong(self.handle_attached_modifier_content())),
-            "underline" => inlines.push(Inline::Underline(self.handle_attached_modifier_content())),
-            "italic" => inlines.push(Inline::Emph(self.handle_attached_modifier_content())),
-            "strikethrough" => {
-                inlines.push(Inline::Strikeout(self.handle_attached_modifier_content()))
-            }
-            "superscript" => {
-                inlines.push(Inline::Superscript(self.handle_attached_modifier_content()))
+            "bold" | "underline" | "italic" | "strikethrough" | "superscript" | "subscript"
+            | "spoiler" => {
+                let map = &self.config.modifier_map;
+                let style = match node.kind() {
+                    "bold" => &map.bold,
+                    "underline" => &map.underline,
+                    "italic" => &map.italic,
+                    "strikethrough" => &map.strikethrough,
+                    "superscript" => &map.superscript,
+                    "subscript" => &map.subscript,
+                    _ => &map.spoiler,
+                };
+
+                let content = self.handle_attached_modifier_content();
+                style.apply(content, inlines);
             }
-            "subscript" => inlines.push(Inline::Subscript(self.handle_attached_modifier_content())),
             "verbatim" => {
                 let text = self.get_delimited_modifier_text();
-                inlines.push(Inline::Code(text))
+                inlines.push(Inline::Code(normalize_verbatim(text)))
             }
             "inline_math" => {
                 let text = self.get_delimited_modifier_text();
                 inlines.push(Inline::Math(text))
             }
+            "variable" => {
+                let name = self.get_delimited_modifier_text();
+                inlines.push(Inline::Variable(name.trim()))
+            }
             // Null modifier
-            "inline_comment" => {}
+            "inline_comment" => {
+                if self.config.inline_comments != CommentStyle::Drop {
+                    let text = self.get_delimited_modifier_text();
+                    inlines.push(Inline::Comment(text.trim()))
+                }
+            }
             kind => {
-                log::error!("Unknown segment: {:?}", kind);
+                self.error(format!("Unknown segment: {:?}", kind));
             }
         }
     }
 
+    /// Returns the content of a markup attached modifier (bold, italic, etc.).
+    ///
+    /// In the free-form variant (`*| ... |*`) the whitespace next to the `|` delimiters is
+    /// allowed and isn't part of the content, so it's trimmed. If the grammar didn't parse the
+    /// content of a free-form modifier it's split into words.
     fn handle_attached_modifier_content(&mut self) -> Vec<Inline<'source>> {
         let mut inlines = Vec::new();
+        let mut free_form_start = None;
+        let mut free_form_end = None;
 
         self.visit_children(|this| {
             let node = this.cursor.node();
 
             match node.kind() {
-                "_open" | "_close" | "free_form_open" | "free_form_close" => {}
+                "_open" | "_close" => {}
+                "free_form_open" => free_form_start = Some(node.end_byte()),
+                "free_form_close" => free_form_end = Some(node.start_byte()),
                 _ => this.handle_segment(&mut inlines),
             }
         });
 
+        if let (Some(start), Some(end)) = (free_form_start, free_form_end) {
+            if inlines.is_empty() {
+                return split_words(&self.source[start..end.max(start)]);
+            }
+
+            trim_spaces(&mut inlines);
+        }
+
         inlines
     }
 
+    /// Returns the raw text inside a verbatim-like attached modifier (verbatim and inline math).
+    ///
+    /// Both the regular (`$...$`) and the free-form (`$| ... |$`) delimiters are supported, in
+    /// the free-form variant the content is everything between the `|` delimiters (including
+    /// any whitespace), this allows the content to contain the closing character.
     fn get_delimited_modifier_text(&mut self) -> &'source str {
         let node = self.cursor.node();
         let mut start = node.start_byte();
         let mut end = node.end_byte();
+        let mut free_form_start = None;
+        let mut free_form_end = None;
 
         self.visit_children(|this| {
             let node = this.cursor.node();
 
             match node.kind() {
-                "_open" => start = start.max(this.cursor.node().end_byte()),
-                "_close" => end = end.min(this.cursor.node().start_byte()),
-                "free_form_open" => start = start.max(this.cursor.node().end_byte()),
-                "free_form_close" => end = end.min(this.cursor.node().start_byte()),
+                "_open" => start = start.max(node.end_byte()),
+                "_close" => end = end.min(node.start_byte()),
+                "free_form_open" => free_form_start = Some(node.end_byte()),
+                "free_form_close" => free_form_end = Some(node.start_byte()),
                 _ => log::trace!("Node '{}' inside verbatim", node.kind()),
             }
         });
 
-        &self.source[start..end]
+        if let (Some(free_start), Some(free_end)) = (free_form_start, free_form_end) {
+            return &self.source[free_start..free_end.max(free_start)];
+        }
+
+        let text = &self.source[start..end.max(start)];
+
+        // The free-form delimiters might be included in the content if the grammar didn't
+        // produce nodes for them
+        match text
+            .strip_prefix('|')
+            .and_then(|text| text.strip_suffix('|'))
+        {
+            Some(inner) => inner,
+            None => text,
+        }
     }
 
     fn handle_link(&mut self, is_anchor: bool) -> Inline<'source> {
         let mut has_description = false;
         let mut text_inlines = Vec::new();
 
-        let mut anchor_name = "";
         let mut anchor_url = "";
         let mut anchor_link = LinkType::None;
+        let mut timestamp = None;
 
         self.visit_children(|this| {
             let node = this.cursor.node();
@@ -133,9 +192,6 @@ where
                 "link_description" => {
                     has_description = true;
                     this.handle_link_description(&mut text_inlines);
-                    anchor_name = node
-                        .utf8_text(this.source.as_bytes())
-                        .expect("Invalid text");
                 }
                 "link_location" => {
                     if let Some(text_node) = node.child_by_field_name("text") {
@@ -144,9 +200,31 @@ where
                             .expect("Invalid text");
                     }
 
-                    anchor_link = match node.child_by_field_name("type").map(|node| node.kind()) {
+                    let mut line_target = None;
+                    let file = node.child_by_field_name("file").map(|file| {
+                        file.utf8_text(this.source.as_bytes())
+                            .expect("Invalid text")
+                    });
+
+                    let link = match node.child_by_field_name("type").map(|node| node.kind()) {
                         Some("link_target_url") => LinkType::Href(anchor_url),
-                        Some("link_target_external_file") => LinkType::File(anchor_url),
+                        Some("link_target_external_file") => {
+                            let (path, line) = split_line_number(anchor_url);
+                            LinkType::File(path, line)
+                        }
+                        Some("link_target_line_number") if file.is_some() => {
+                            match anchor_url.trim().parse() {
+                                Ok(line) => line_target = Some(line),
+                                Err(_) => {
+                                    this.error(format!("Invalid line number: {}", anchor_url))
+                                }
+                            }
+                            LinkType::None
+                        }
+                        Some("link_target_line_number") => {
+                            this.warn("Line number links are only supported to other files");
+                            LinkType::None
+                        }
                         Some("link_target_heading1") => {
                             LinkType::DocumentLink(DocumentLinkType::Heading(1), anchor_url)
                         }
@@ -165,28 +243,94 @@ where
                         Some("link_target_heading6") => {
                             LinkType::DocumentLink(DocumentLinkType::Heading(6), anchor_url)
                         }
+                        Some("link_target_wiki") => LinkType::Wiki(Cow::Borrowed(anchor_url)),
+                        Some("link_target_footnote") => LinkType::Footnote(anchor_url),
+                        Some("link_target_definition") => {
+                            LinkType::DocumentLink(DocumentLinkType::Definition, anchor_url)
+                        }
+                        Some("link_target_generic") => LinkType::Generic(anchor_url),
+                        Some("link_target_timestamp") => {
+                            timestamp = Some(anchor_url);
+                            LinkType::None
+                        }
                         Some(ty) => {
-                            log::error!("Unknown link type: {}", ty);
+                            this.error(format!("Unknown link type: {}", ty));
                             LinkType::None
                         }
+                        None if file.is_some() => LinkType::None,
                         None => {
-                            log::error!("Link with no type");
+                            this.error("Link with no type");
                             LinkType::None
                         }
                     };
+
+                    // Links to other norg files, either to the file itself or to an element in
+                    // it (scoped links)
+                    if let Some(file) = file {
+                        if anchor_url.is_empty() {
+                            anchor_url = file;
+                        }
+
+                        let target = match link {
+                            LinkType::None => {
+                                Some(line_target.map_or(FileTarget::File, FileTarget::Line))
+                            }
+                            LinkType::DocumentLink(DocumentLinkType::Heading(_), heading)
+                            | LinkType::Generic(heading) => Some(FileTarget::Heading(heading)),
+                            LinkType::DocumentLink(DocumentLinkType::Definition, term) => {
+                                Some(FileTarget::Definition(term))
+                            }
+                            _ => None,
+                        };
+
+                        anchor_link = match target {
+                            Some(target) => LinkType::NorgFile(file, target),
+                            None => {
+                                this.warn(
+                                    "Only headings, definitions and line numbers are supported \
+                                     as targets in other files",
+                                );
+                                link
+                            }
+                        };
+                    } else {
+                        anchor_link = link;
+                    }
                 }
-                link_child => log::error!("Unknown link child: {}", link_child),
+                link_child => this.error(format!("Unknown link child: {}", link_child)),
             }
         });
 
+        if let (false, Some(text)) = (is_anchor, timestamp) {
+            return Inline::Timestamp(text_inlines, text);
+        }
+
         if !has_description {
             text_inlines.push(Inline::Str(anchor_url));
         }
 
+        if let (false, LinkType::Href(target)) = (is_anchor, &anchor_link) {
+            let keys = self.config.citations.as_ref().and_then(|c| c.keys(target));
+            if let Some(keys) = keys {
+                let suffix = match has_description {
+                    true => text_inlines,
+                    false => Vec::new(),
+                };
+                return Inline::Cite(keys, suffix);
+            }
+        }
+
         match is_anchor {
             true => {
+                let anchor_name = normalize_anchor_name(&text_inlines);
+
                 if LinkType::None != anchor_link {
-                    self.context.anchors.insert(anchor_name, anchor_link);
+                    let range = self.cursor.node().byte_range();
+                    self.context
+                        .define(DefinitionKind::Anchor, anchor_name.clone(), range);
+                    self.context
+                        .anchors
+                        .insert(anchor_name.clone(), anchor_link);
                 }
 
                 Inline::Anchor(text_inlines, anchor_name)
@@ -207,3 +351,552 @@ where
         });
     }
 }
+
+/// The pandoc inline used to render an attached modifier, see [`ModifierMap`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum ModifierStyle {
+    Emph,
+    Strong,
+    Underline,
+    Strikeout,
+    Superscript,
+    Subscript,
+    SmallCaps,
+    /// A `Span` with the class.
+    Span(String),
+    /// The content without any markup.
+    Plain,
+}
+
+impl ModifierStyle {
+    fn apply<'source>(
+        &'source self,
+        content: Vec<Inline<'source>>,
+        out: &mut Vec<Inline<'source>>,
+    ) {
+        let inline = match self {
+            ModifierStyle::Emph => Inline::Emph(content),
+            ModifierStyle::Strong => Inline::Strong(content),
+            ModifierStyle::Underline => Inline::Underline(content),
+            ModifierStyle::Strikeout => Inline::Strikeout(content),
+            ModifierStyle::Superscript => Inline::Superscript(content),
+            ModifierStyle::Subscript => Inline::Subscript(content),
+            ModifierStyle::SmallCaps => Inline::SmallCaps(content),
+            ModifierStyle::Span(class) => Inline::Span(content, class),
+            ModifierStyle::Plain => return out.extend(content),
+        };
+
+        out.push(inline)
+    }
+}
+
+/// A callback that returns the value of an inline variable (`&name&`), see [`Config::variables`].
+///
+/// [`Config::variables`]: crate::Config::variables
+pub type VariableResolver = Arc<dyn Fn(&str) -> Option<String> + Send + Sync>;
+
+/// How inline verbatim (`` `text` ``) is rendered.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum VerbatimStyle {
+    /// A `Code` inline (default).
+    #[default]
+    Code,
+    /// A `Span` with the `verbatim` class, useful when the output styles code heavily and
+    /// verbatim is used for terms that aren't code.
+    Span,
+    /// The text without any markup.
+    Raw,
+}
+
+/// How comments are rendered, see [`Config::inline_comments`] (for `%comment%`) and
+/// [`Config::block_comments`].
+///
+/// [`Config::inline_comments`]: crate::Config::inline_comments
+/// [`Config::block_comments`]: crate::Config::block_comments
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum CommentStyle {
+    /// The comments are removed (default).
+    #[default]
+    Drop,
+    /// A `Span` (or a `Div` for blocks) with the `comment` class, this allows keeping the
+    /// comments for reviewers (for example as comments in docx with a filter).
+    Span,
+    /// A raw HTML comment (`<!-- comment -->`).
+    RawHtml,
+}
+
+/// How the lines of a paragraph are separated, see [`Config::line_break_mode`].
+///
+/// [`Config::line_break_mode`]: crate::Config::line_break_mode
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum LineBreakMode {
+    /// The lines are joined with a space (default).
+    #[default]
+    Soft,
+    /// Each line ends with a hard line break, useful for poetry or addresses.
+    Hard,
+    /// The lines are separated with a soft line break, which keeps the lines of the source in
+    /// the output when using pandoc's `--wrap=preserve`.
+    Preserve,
+}
+
+/// How the lines of the paragraphs directly inside some elements are separated, overriding the
+/// [`Config::line_break_mode`] (`None` uses it), see [`Config::line_break_overrides`].
+///
+/// [`Config::line_break_mode`]: crate::Config::line_break_mode
+/// [`Config::line_break_overrides`]: crate::Config::line_break_overrides
+#[derive(Debug, Default)]
+#[cfg_attr(feature = "serde", derive(Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
+pub struct LineBreakOverrides {
+    /// The paragraphs of quotes.
+    pub quotes: Option<LineBreakMode>,
+    /// The paragraphs of list items.
+    pub lists: Option<LineBreakMode>,
+    /// The paragraphs of table cells.
+    pub tables: Option<LineBreakMode>,
+}
+
+/// Defines the pandoc inline used for each of neorg's attached modifiers.
+///
+/// This allows replacing the modifiers that the output format doesn't support, for example
+/// rendering underlines as emphasis or strikethroughs as a `Span` that can be styled. By default
+/// each modifier is rendered as the equivalent pandoc inline.
+#[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
+pub struct ModifierMap {
+    /// `*bold*` (default: [`ModifierStyle::Strong`])
+    pub bold: ModifierStyle,
+    /// `/italic/` (default: [`ModifierStyle::Emph`])
+    pub italic: ModifierStyle,
+    /// `_underline_` (default: [`ModifierStyle::Underline`])
+    pub underline: ModifierStyle,
+    /// `-strikethrough-` (default: [`ModifierStyle::Strikeout`])
+    pub strikethrough: ModifierStyle,
+    /// `^superscript^` (default: [`ModifierStyle::Superscript`])
+    pub superscript: ModifierStyle,
+    /// `,subscript,` (default: [`ModifierStyle::Subscript`])
+    pub subscript: ModifierStyle,
+    /// `!spoiler!` (default: [`ModifierStyle::Span`] with the `spoiler` class)
+    pub spoiler: ModifierStyle,
+}
+
+impl Default for ModifierMap {
+    fn default() -> Self {
+        Self {
+            bold: ModifierStyle::Strong,
+            italic: ModifierStyle::Emph,
+            underline: ModifierStyle::Underline,
+            strikethrough: ModifierStyle::Strikeout,
+            superscript: ModifierStyle::Superscript,
+            subscript: ModifierStyle::Subscript,
+            spoiler: ModifierStyle::Span(String::from("spoiler")),
+        }
+    }
+}
+
+/// Configuration of the links to other norg files (`{:path/to/file:}` and
+/// `{:path/to/file:** Heading}`).
+///
+/// The links to headings point to the identifier the heading would get when converting the
+/// other file (ignoring duplicates), so the files must be converted with the same configuration.
+#[derive(Debug, Default)]
+#[cfg_attr(feature = "serde", derive(Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
+pub struct WorkspaceLinks {
+    /// Template used to build the url of the files not in `paths`, `{path}` is replaced with the
+    /// file's path (without the `.norg` extension and the workspace root prefix `$/`)
+    /// (default: `{path}.html`).
+    pub path_template: Option<String>,
+    /// Maps the paths of files (as written in the links) to their url.
+    pub paths: HashMap<String, String>,
+}
+
+impl WorkspaceLinks {
+    /// Returns the url of the norg file at `path`.
+    pub fn file_url(&self, path: &str) -> String {
+        if let Some(url) = self.paths.get(path) {
+            return url.clone();
+        }
+
+        let path = path.strip_prefix("$/").unwrap_or(path);
+        let path = path.strip_suffix(".norg").unwrap_or(path);
+        let template = self.path_template.as_deref().unwrap_or("{path}.html");
+        template.replace("{path}", path)
+    }
+}
+
+/// Templates used to build the urls of links to elements inside of files.
+#[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
+pub struct LinkTemplates {
+    /// Url of a line in a non-norg file (`{/ path.txt:40}`), `{path}` is replaced with the path
+    /// of the file and `{line}` with the line number (default: `{path}#L{line}`).
+    pub file_line: String,
+    /// Url of a line in another norg file (`{:file:40}`), `{url}` is replaced with the url of the
+    /// file and `{line}` with the line number (default: `{url}`, since the lines of the source
+    /// don't exist in the output).
+    pub norg_file_line: String,
+    /// Url of a heading or definition in another norg file (`{:file:* Heading}`), `{url}` is
+    /// replaced with the url of the file and `{id}` with the identifier of the element
+    /// (default: `{url}#{id}`).
+    pub norg_file_element: String,
+}
+
+impl Default for LinkTemplates {
+    fn default() -> Self {
+        Self {
+            file_line: String::from("{path}#L{line}"),
+            norg_file_line: String::from("{url}"),
+            norg_file_element: String::from("{url}#{id}"),
+        }
+    }
+}
+
+/// Splits the line number from the path of a file link (`path.txt:40`).
+fn split_line_number(target: &str) -> (&str, Option<usize>) {
+    match target.rsplit_once(':') {
+        Some((path, line)) if !path.is_empty() => match line.trim().parse() {
+            Ok(line) => (path.trim_end(), Some(line)),
+            Err(_) => (target, None),
+        },
+        _ => (target, None),
+    }
+}
+
+/// Configuration of the `[[Page Name]]` style links.
+///
+/// The links are first resolved to a heading in the current document with the same text (of any
+/// level), if no such heading exists the link points to the path produced by `path_template`.
+///
+/// Neorg's native wiki links (`{? Page Name}`) are always resolved this way.
+#[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
+pub struct WikiLinks {
+    /// Template used to build the link url of pages not found in the document, `{page}` is
+    /// replaced with the page name (default: `{page}.html`).
+    pub path_template: String,
+}
+
+impl Default for WikiLinks {
+    fn default() -> Self {
+        Self {
+            path_template: String::from("{page}.html"),
+        }
+    }
+}
+
+/// Replaces the `[[Page Name]]` style links in a paragraph segment with link inlines.
+///
+/// Links can't contain other markup, if they do they are left as is.
+pub(crate) fn convert_wiki_links(inlines: Vec<Inline>) -> Vec<Inline> {
+    let mut output = Vec::with_capacity(inlines.len());
+    // The description of the link currently being parsed (if any)
+    let mut description: Option<Vec<Inline>> = None;
+
+    for inline in inlines {
+        let Inline::Str(mut text) = inline else {
+            match description.as_mut() {
+                Some(description) if matches!(inline, Inline::Space) => description.push(inline),
+                Some(_) => {
+                    output.push(Inline::Str("[["));
+                    output.append(&mut description.take().unwrap_or_default());
+                    output.push(inline);
+                }
+                None => output.push(inline),
+            }
+
+            continue;
+        };
+
+        loop {
+            match description.as_mut() {
+                None => match text.find("[[") {
+                    Some(idx) => {
+                        if idx > 0 {
+                            output.push(Inline::Str(&text[..idx]));
+                        }
+
+                        description = Some(Vec::new());
+                        text = &text[idx + 2..];
+                    }
+                    None => {
+                        if !text.is_empty() {
+                            output.push(Inline::Str(text));
+                        }
+
+                        break;
+                    }
+                },
+                Some(inlines) => match text.find("]]") {
+                    Some(idx) => {
+                        if idx > 0 {
+                            inlines.push(Inline::Str(&text[..idx]));
+                        }
+
+                        let inlines = description.take().unwrap_or_default();
+                        let mut page = String::new();
+                        for inline in &inlines {
+                            inline.push_text(&mut page);
+                        }
+                        let page = page.split_whitespace().collect::<Vec<_>>().join(" ");
+
+                        if page.is_empty() {
+                            output.push(Inline::Str("[[]]"));
+                        } else {
+                            output.push(Inline::Link(inlines, LinkType::Wiki(Cow::Owned(page))));
+                        }
+
+                        text = &text[idx + 2..];
+                    }
+                    None => {
+                        if !text.is_empty() {
+                            inlines.push(Inline::Str(text));
+                        }
+
+                        break;
+                    }
+                },
+            }
+        }
+    }
+
+    // Unterminated link
+    if let Some(mut inlines) = description {
+        output.push(Inline::Str("[["));
+        output.append(&mut inlines);
+    }
+
+    output
+}
+
+/// Returns the key used to match anchor declarations with their definitions.
+///
+/// The key is the plain text of the anchor description (without any markup) with all whitespace
+/// collapsed into single spaces and converted to lowercase, so that the same anchor can be
+/// referenced using different markup, spacing or capitalization.
+pub(crate) fn normalize_anchor_name(inlines: &[Inline]) -> String {
+    let mut text = String::new();
+
+    for inline in inlines {
+        inline.push_text(&mut text);
+    }
+
+    let mut name = String::with_capacity(text.len());
+
+    for word in text.split_whitespace() {
+        if !name.is_empty() {
+            name.push(' ');
+        }
+
+        name.extend(word.chars().flat_map(char::to_lowercase));
+    }
+
+    name
+}
+
+/// Splits raw text into words separated by spaces.
+pub(crate) fn split_words(text: &str) -> Vec<Inline<'_>> {
+    let mut inlines = Vec::new();
+
+    for word in text.split_whitespace() {
+        if !inlines.is_empty() {
+            inlines.push(Inline::Space);
+        }
+        inlines.push(Inline::Str(word));
+    }
+
+    inlines
+}
+
+/// Returns a raw HTML comment with the given text, see [`CommentStyle::RawHtml`].
+///
+/// Every `--` in the text is replaced with `- -`, since HTML comments can't contain it and a
+/// `-->` would end the comment early and leave the rest of the text as live HTML.
+pub(crate) fn html_comment(text: &str) -> String {
+    let mut text = text.to_string();
+    // Replacing a `---` leaves a `--` behind, so it's repeated until none is left
+    while text.contains("--") {
+        text = text.replace("--", "- -");
+    }
+
+    format!("<!-- {} -->", text)
+}
+
+/// Collapses the line breaks (and the indentation following them) of inline verbatim text that
+/// spans multiple lines into single spaces.
+fn normalize_verbatim(text: &str) -> Cow<'_, str> {
+    if !text.contains('\n') {
+        return Cow::Borrowed(text);
+    }
+
+    let mut normalized = String::with_capacity(text.len());
+    let mut lines = text.split('\n').peekable();
+    let mut first = true;
+
+    while let Some(mut line) = lines.next() {
+        if !first {
+            normalized.push(' ');
+            line = line.trim_start();
+        }
+
+        // The whitespace at the end of the last line is part of the verbatim content
+        if lines.peek().is_some() {
+            line = line.trim_end();
+        }
+
+        normalized.push_str(line);
+        first = false;
+    }
+
+    Cow::Owned(normalized)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        convert_wiki_links, html_comment, normalize_anchor_name, normalize_verbatim,
+        split_line_number, split_words, ModifierStyle, WorkspaceLinks,
+    };
+    use crate::ir::{Inline, LinkType};
+    use crate::trim_spaces;
+
+    #[test]
+    fn wiki_links() {
+        let segment = vec![
+            Inline::Str("See"),
+            Inline::Space,
+            Inline::Str("[[Page"),
+            Inline::Space,
+            Inline::Str("Name]],"),
+            Inline::Space,
+            Inline::Str("[[unterminated"),
+        ];
+
+        let mut output = String::new();
+        for inline in convert_wiki_links(segment) {
+            match inline {
+                Inline::Link(inlines, LinkType::Wiki(page)) => {
+                    assert_eq!(inlines.len(), 3);
+                    output.push_str(&format!("<{page}>"));
+                }
+                inline => inline.push_text(&mut output),
+            }
+        }
+
+        assert_eq!(output, "See <Page Name>, [[unterminated");
+    }
+
+    #[test]
+    fn modifier_styles() {
+        let span = ModifierStyle::Span(String::from("strike"));
+        let mut output = Vec::new();
+        span.apply(vec![Inline::Str("a")], &mut output);
+        ModifierStyle::Plain.apply(vec![Inline::Str("b"), Inline::Space], &mut output);
+
+        assert!(matches!(
+            output.as_slice(),
+            [Inline::Span(content, "strike"), Inline::Str("b"), Inline::Space]
+                if matches!(content.as_slice(), [Inline::Str("a")])
+        ));
+    }
+
+    #[test]
+    fn workspace_links() {
+        let mut links = WorkspaceLinks::default();
+        assert_eq!(links.file_url("notes/other"), "notes/other.html");
+        assert_eq!(links.file_url("$/notes/other.norg"), "notes/other.html");
+
+        links.path_template = Some(String::from("/wiki/{path}/"));
+        links.paths.insert(String::from("index"), String::from("/"));
+        assert_eq!(links.file_url("other"), "/wiki/other/");
+        assert_eq!(links.file_url("index"), "/");
+    }
+
+    #[test]
+    fn anchor_names() {
+        let plain = [
+            Inline::Str("Bold"),
+            Inline::Space,
+            Inline::Space,
+            Inline::Str("name"),
+        ];
+        let markup = [
+            Inline::Strong(vec![Inline::Str("bold")]),
+            Inline::Space,
+            Inline::Str("Name"),
+        ];
+
+        assert_eq!(normalize_anchor_name(&plain), "bold name");
+        assert_eq!(normalize_anchor_name(&markup), "bold name");
+    }
+
+    #[test]
+    fn single_line_verbatim() {
+        assert_eq!(normalize_verbatim("let x = 1;"), "let x = 1;");
+        assert_eq!(normalize_verbatim(" padded "), " padded ");
+    }
+
+    #[test]
+    fn multi_line_verbatim() {
+        assert_eq!(
+            normalize_verbatim("fn main() {\n        println!()\n    }"),
+            "fn main() { println!() }"
+        );
+        assert_eq!(normalize_verbatim("a  \r\n\tb "), "a b ");
+    }
+
+    #[test]
+    fn free_form_content() {
+        let text = |inlines: &[Inline]| {
+            let mut output = String::new();
+            inlines
+                .iter()
+                .for_each(|inline| inline.push_text(&mut output));
+            output
+        };
+
+        let words = split_words(" a  b\n c ");
+        assert_eq!(words.len(), 5);
+        assert_eq!(text(&words), "a b c");
+
+        let mut inlines = vec![
+            Inline::Space,
+            Inline::Str("a"),
+            Inline::Space,
+            Inline::Space,
+        ];
+        trim_spaces(&mut inlines);
+        assert_eq!(text(&inlines), "a");
+        assert_eq!(inlines.len(), 1);
+    }
+
+    #[test]
+    fn file_line_numbers() {
+        assert_eq!(split_line_number("path.txt:40"), ("path.txt", Some(40)));
+        assert_eq!(split_line_number("path.txt"), ("path.txt", None));
+        assert_eq!(split_line_number("C:\\notes.txt"), ("C:\\notes.txt", None));
+        assert_eq!(split_line_number(":40"), (":40", None));
+    }
+
+    #[test]
+    fn html_comments() {
+        assert_eq!(html_comment("A note"), "<!-- A note -->");
+        assert_eq!(
+            html_comment("Ends --> <b>here</b>"),
+            "<!-- Ends - -> <b>here</b> -->"
+        );
+        assert_eq!(html_comment("a---b"), "<!-- a- - -b -->");
+    }
+}
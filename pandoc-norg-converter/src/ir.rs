@@ -1,16 +1,81 @@
+//! The intermediate representation of documents (public with the `ir` feature)
+//!
+//! The tree is converted to these types while it's walked, they are converted to pandoc once the
+//! whole document was walked (when the links and references can be resolved).
+
+use std::borrow::Cow;
+use std::collections::HashMap;
+
 use pandoc_types::definition::{
-    Attr, Block as PandocBlock, Cell as PandocCell, ColSpec, Inline as PandocInline, MathType,
-    Row as PandocRow, Table, TableBody, TableHead, Target,
+    Alignment, Attr, Block as PandocBlock, Caption, Cell as PandocCell, ColSpec, ColWidth, Format,
+    Inline as PandocInline, ListAttributes, MathType, Row as PandocRow, Table, TableBody,
+    TableHead, Target,
 };
 
-use crate::document::{DocumentContext, DocumentLinkType};
+use crate::document::DocumentContext;
+use crate::extensions::{TodoOutputMode, TodoStatus};
+use crate::inlines::html_comment;
+use crate::stringify::text_to_inlines;
+use crate::{CommentStyle, LineBreakMode, VerbatimStyle, WikiLinks};
+#[cfg(feature = "serde")]
+use serde::Deserialize;
+
+pub use crate::document::DocumentLinkType;
+pub use crate::tasks::TaskQuery;
+pub use crate::toc::TableOfContents;
+
+/// Configuration of the LaTeX cross-reference mode.
+///
+/// When enabled through [`Config::latex_references`] a raw `\label{id}` is emitted after each
+/// heading, figure caption and equation with an identifier, and links to targets inside the document are converted to raw references
+/// (`\autoref{id}` by default) instead of hyperlinks, so that LaTeX numbers them.
+///
+/// [`Config::latex_references`]: crate::Config::latex_references
+#[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
+pub struct LatexReferences {
+    /// The command used for references, for example `ref` or `autoref` (default: `autoref`).
+    pub command: String,
+}
+
+impl Default for LatexReferences {
+    fn default() -> Self {
+        Self {
+            command: String::from("autoref"),
+        }
+    }
+}
 
 #[derive(Debug, PartialEq, Eq)]
 pub enum LinkType<'source> {
     None,
     Href(&'source str),
-    File(&'source str),
+    /// A link to a file that isn't a norg file and optionally to a line in it.
+    File(&'source str, Option<usize>),
     DocumentLink(DocumentLinkType, &'source str),
+    /// A link to a page by it's name, see [`WikiLinks`](crate::WikiLinks).
+    Wiki(Cow<'source, str>),
+    /// A reference to the footnote with the given title.
+    Footnote(&'source str),
+    /// A link to any element of the document with the given text (the `#` link target).
+    Generic(&'source str),
+    /// A link to another norg file and optionally to an element in it, see
+    /// [`WorkspaceLinks`](crate::WorkspaceLinks).
+    NorgFile(&'source str, FileTarget<'source>),
+}
+
+/// The target of a link inside another norg file.
+#[derive(Debug, PartialEq, Eq)]
+pub enum FileTarget<'source> {
+    /// The file itself.
+    File,
+    /// A heading (of any level) by it's title.
+    Heading(&'source str),
+    /// A definition by it's term.
+    Definition(&'source str),
+    /// A line number.
+    Line(usize),
 }
 
 #[derive(Debug)]
@@ -25,18 +90,100 @@ pub enum Inline<'source> {
 
     Subscript(Vec<Inline<'source>>),
     Superscript(Vec<Inline<'source>>),
+    SmallCaps(Vec<Inline<'source>>),
+    /// A span with a class, used by the [`ModifierMap`](crate::ModifierMap).
+    Span(Vec<Inline<'source>>, &'source str),
 
-    Code(&'source str),
+    Code(Cow<'source, str>),
     Math(&'source str),
+    /// An inline variable, resolved when the document is built.
+    Variable(&'source str),
+    /// An inline comment, only added if it's rendered, see [`CommentStyle`].
+    Comment(&'source str),
+    /// The TODO status of an item, see [`TodoOutputMode`].
+    TodoStatus(TodoStatus),
 
     Link(Vec<Inline<'source>>, LinkType<'source>),
-    Anchor(Vec<Inline<'source>>, &'source str),
+    Anchor(Vec<Inline<'source>>, String),
+    /// A citation of the keys with a suffix, see [`Citations`](crate::Citations).
+    Cite(Vec<&'source str>, Vec<Inline<'source>>),
+    /// Inlines with an identifier that links can point to, like the term of a definition.
+    Identified(Vec<Inline<'source>>, String),
+    /// A timestamp (`{@ 2023-04-01}`) with it's description (if any), see
+    /// [`TimestampStyle`](crate::TimestampStyle).
+    Timestamp(Vec<Inline<'source>>, &'source str),
+
+    Image(Image<'source>),
+}
 
-    Image(&'source str),
+/// An embedded image.
+#[derive(Debug)]
+pub struct Image<'source> {
+    /// The URL of the image, which might be a `data:` URI, see [`InlineImages`].
+    ///
+    /// [`InlineImages`]: crate::InlineImages
+    pub url: Cow<'source, str>,
+    /// The attributes of the image (like it's width and height).
+    pub attr: Attr,
+    /// The alternative text of the image.
+    pub alt: String,
+    pub title: String,
+}
+
+impl Image<'_> {
+    /// Removes the attribute with the given key and returns it's value (if it exists).
+    pub(crate) fn take_attribute(&mut self, key: &str) -> Option<String> {
+        let idx = self.attr.attributes.iter().position(|(k, _)| k == key)?;
+        Some(self.attr.attributes.remove(idx).1)
+    }
+
+    pub(crate) fn into_pandoc(self) -> PandocInline {
+        PandocInline::Image(
+            self.attr,
+            text_to_inlines(&self.alt),
+            Target {
+                url: self.url.into_owned(),
+                title: self.title,
+            },
+        )
+    }
 }
 
 impl<'source> Inline<'source> {
-    pub fn into_pandoc(self, context: &DocumentContext) -> PandocInline {
+    /// Appends the plain text of the inline (without any markup) to `out`.
+    pub fn push_text(&self, out: &mut String) {
+        match self {
+            Inline::Space => out.push(' '),
+            Inline::Str(str) | Inline::Math(str) | Inline::Variable(str) => out.push_str(str),
+            Inline::Comment(_) | Inline::TodoStatus(_) => {}
+            Inline::Image(image) => out.push_str(&image.alt),
+            Inline::Code(str) => out.push_str(str),
+            Inline::Emph(inlines)
+            | Inline::Strong(inlines)
+            | Inline::Underline(inlines)
+            | Inline::Strikeout(inlines)
+            | Inline::Subscript(inlines)
+            | Inline::Superscript(inlines)
+            | Inline::SmallCaps(inlines)
+            | Inline::Span(inlines, _)
+            | Inline::Link(inlines, _)
+            | Inline::Anchor(inlines, _)
+            | Inline::Cite(_, inlines)
+            | Inline::Identified(inlines, _) => {
+                for inline in inlines {
+                    inline.push_text(out);
+                }
+            }
+            Inline::Timestamp(inlines, text) if inlines.is_empty() => out.push_str(text.trim()),
+            Inline::Timestamp(inlines, _) => {
+                for inline in inlines {
+                    inline.push_text(out);
+                }
+            }
+        }
+    }
+
+    pub(crate) fn into_pandoc(self, context: &DocumentContext) -> PandocInline {
         match self {
             Inline::Space => PandocInline::Space,
             Inline::Str(str) => PandocInline::Str(str.to_string()),
@@ -58,11 +205,104 @@ impl<'source> Inline<'source> {
             Inline::Superscript(inlines) => {
                 PandocInline::Superscript(convert_inlines_to_pandoc(inlines, context))
             }
-            Inline::Code(str) => PandocInline::Code(Attr::default(), str.to_string()),
+            Inline::SmallCaps(inlines) => {
+                PandocInline::SmallCaps(convert_inlines_to_pandoc(inlines, context))
+            }
+            Inline::Span(inlines, class) => {
+                let attr = Attr {
+                    classes: vec![class.to_string()],
+                    ..Default::default()
+                };
+                PandocInline::Span(attr, convert_inlines_to_pandoc(inlines, context))
+            }
+            Inline::Code(str) => match context.config.inline_verbatim {
+                VerbatimStyle::Code => PandocInline::Code(Attr::default(), str.into_owned()),
+                VerbatimStyle::Span => {
+                    let attr = Attr {
+                        classes: vec![String::from("verbatim")],
+                        ..Default::default()
+                    };
+                    PandocInline::Span(attr, text_to_inlines(&str))
+                }
+                // The words are spliced into the parent by `push_inlines_to_pandoc`, a `Span`
+                // without attributes is only used when the inline is converted on it's own
+                VerbatimStyle::Raw => PandocInline::Span(Attr::default(), text_to_inlines(&str)),
+            },
             Inline::Math(str) => PandocInline::Math(MathType::InlineMath, str.to_string()),
+            Inline::TodoStatus(status) => {
+                let symbol = context.config.todo_symbols.symbol(status).to_string();
+
+                match context.config.todo_output {
+                    TodoOutputMode::Symbols => PandocInline::Str(symbol),
+                    TodoOutputMode::Spans => {
+                        let attr = Attr {
+                            classes: vec![format!("todo-{}", status.name())],
+                            attributes: vec![(
+                                String::from("data-todo"),
+                                status.name().to_string(),
+                            )],
+                            ..Default::default()
+                        };
+                        PandocInline::Span(attr, vec![PandocInline::Str(symbol)])
+                    }
+                    TodoOutputMode::Checkbox => match status {
+                        TodoStatus::Done => PandocInline::Str(String::from("☒")),
+                        _ => PandocInline::Str(String::from("☐")),
+                    },
+                }
+            }
+            Inline::Comment(text) => match context.config.inline_comments {
+                CommentStyle::RawHtml => {
+                    PandocInline::RawInline(Format(String::from("html")), html_comment(text))
+                }
+                _ => {
+                    let attr = Attr {
+                        classes: vec![String::from("comment")],
+                        ..Default::default()
+                    };
+                    PandocInline::Span(attr, text_to_inlines(text))
+                }
+            },
+            Inline::Variable(name) => {
+                let value = context
+                    .variables
+                    .get(name)
+                    .or_else(|| context.config.variable_values.get(name))
+                    .cloned()
+                    .or_else(|| {
+                        let resolve = context.config.variables.as_ref()?;
+                        resolve(name)
+                    });
+
+                let Some(value) = value else {
+                    context.warn(format!("Undefined variable {}", name));
+                    let attr = Attr {
+                        classes: vec![String::from("variable")],
+                        ..Default::default()
+                    };
+                    return PandocInline::Span(attr, vec![PandocInline::Str(name.to_string())]);
+                };
+
+                let mut inlines = text_to_inlines(&value);
+                match inlines.len() {
+                    1 => inlines.remove(0),
+                    _ => PandocInline::Span(Attr::default(), inlines),
+                }
+            }
+            Inline::Link(inlines, LinkType::Footnote(title)) => match context.get_footnote(title) {
+                Some(blocks) => PandocInline::Note(blocks.clone()),
+                None => {
+                    context.warn(format!("Missing footnote {}", title));
+                    PandocInline::Span(Attr::default(), convert_inlines_to_pandoc(inlines, context))
+                }
+            },
             Inline::Link(inlines, ty) => {
                 let url = get_link_url(&ty, context);
 
+                if let Some(reference) = latex_reference(&url, context) {
+                    return reference;
+                }
+
                 PandocInline::Link(
                     Attr::default(),
                     convert_inlines_to_pandoc(inlines, context),
@@ -73,11 +313,11 @@ impl<'source> Inline<'source> {
                 )
             }
             Inline::Anchor(inlines, id) => {
-                let url = context
-                    .anchors
-                    .get(id)
-                    .map(|ty| get_link_url(ty, context))
-                    .unwrap_or_default();
+                let url = context.get_anchor_url(&id);
+
+                if let Some(reference) = latex_reference(&url, context) {
+                    return reference;
+                }
 
                 PandocInline::Link(
                     Attr::default(),
@@ -88,35 +328,111 @@ impl<'source> Inline<'source> {
                     },
                 )
             }
-            Inline::Image(url) => {
-                let attr = Attr::default();
-                PandocInline::Image(
-                    attr,
-                    Vec::new(),
-                    Target {
-                        url: url.to_string(),
-                        title: String::new(),
-                    },
-                )
+            Inline::Cite(keys, suffix) => crate::citations::cite_to_pandoc(keys, suffix, context),
+            Inline::Identified(inlines, identifier) => {
+                let attr = Attr {
+                    identifier,
+                    ..Default::default()
+                };
+                PandocInline::Span(attr, convert_inlines_to_pandoc(inlines, context))
+            }
+            Inline::Timestamp(inlines, text) => {
+                crate::timestamps::timestamp_to_pandoc(inlines, text, context)
             }
+            Inline::Image(image) => image.into_pandoc(),
         }
     }
 }
 
-fn get_link_url(ty: &LinkType, context: &DocumentContext) -> String {
+/// Returns the raw LaTeX reference to `url` if the LaTeX reference mode is enabled and `url`
+/// points to an element of the document.
+fn latex_reference(url: &str, context: &DocumentContext) -> Option<PandocInline> {
+    let references = context.config.latex_references.as_ref()?;
+    let id = url.strip_prefix('#')?;
+
+    Some(PandocInline::RawInline(
+        Format(String::from("latex")),
+        format!("\\{}{{{}}}", references.command, id),
+    ))
+}
+
+/// Returns a raw LaTeX inline defining the label `id`.
+fn latex_label(id: &str) -> PandocInline {
+    PandocInline::RawInline(Format(String::from("latex")), format!("\\label{{{}}}", id))
+}
+
+pub(crate) fn get_link_url(ty: &LinkType, context: &DocumentContext) -> String {
     match *ty {
-        LinkType::None => String::new(),
+        LinkType::None | LinkType::Footnote(_) => String::new(),
         LinkType::Href(url) => url.to_string(),
-        LinkType::File(url) => url.to_string(),
+        LinkType::File(path, None) => path.to_string(),
+        LinkType::File(path, Some(line)) => context
+            .config
+            .link_templates
+            .file_line
+            .replace("{path}", path)
+            .replace("{line}", &line.to_string()),
         LinkType::DocumentLink(ref ty, text) => {
             let res = context.get_document_link(text, ty).cloned();
 
+            match (&res, ty) {
+                (None, _) => context.warn(format!("Missing document link for {}", text)),
+                (Some(_), DocumentLinkType::Heading(level)) => {
+                    context.warn_ambiguous_heading(text, *level)
+                }
+                _ => {}
+            }
+
+            res.unwrap_or_default()
+        }
+        LinkType::NorgFile(path, ref target) => {
+            let project_url = match *target {
+                FileTarget::Heading(title) => {
+                    context.project.and_then(|p| p.heading_url(path, title))
+                }
+                _ => None,
+            };
+            if let Some(url) = project_url {
+                return url.clone();
+            }
+
+            let url = context.config.workspace_links.file_url(path);
+            let templates = &context.config.link_templates;
+
+            match *target {
+                FileTarget::File => url,
+                FileTarget::Heading(text) | FileTarget::Definition(text) => {
+                    let text = text.split_whitespace().collect::<Vec<_>>().join(" ");
+                    let config = context.config;
+                    let id = crate::base_id(&text, config.id_style, config.id_characters);
+                    templates
+                        .norg_file_element
+                        .replace("{url}", &url)
+                        .replace("{id}", &id)
+                }
+                FileTarget::Line(line) => templates
+                    .norg_file_line
+                    .replace("{url}", &url)
+                    .replace("{line}", &line.to_string()),
+            }
+        }
+        LinkType::Generic(text) => {
+            let res = context.find_generic_link(text).cloned();
+
             if res.is_none() {
-                log::warn!("Missing document link for {}", text);
+                context.warn(format!("Missing document link for {}", text));
             }
 
             res.unwrap_or_default()
         }
+        LinkType::Wiki(ref page) => match context.find_heading_link(page) {
+            Some(url) => url.clone(),
+            None => {
+                let default = WikiLinks::default();
+                let wiki_links = context.config.wiki_links.as_ref().unwrap_or(&default);
+                wiki_links.path_template.replace("{page}", page)
+            }
+        },
     }
 }
 
@@ -126,6 +442,21 @@ type ParagraphSegment<'source> = Vec<Inline<'source>>;
 #[derive(Debug)]
 pub struct Cell<'source> {
     pub blocks: Vec<Block<'source>>,
+    /// The number of rows the cell spans.
+    pub row_span: i32,
+    /// The number of columns the cell spans.
+    pub col_span: i32,
+}
+
+impl<'source> Cell<'source> {
+    /// Creates a cell that doesn't span other rows or columns.
+    pub fn new(blocks: Vec<Block<'source>>) -> Self {
+        Self {
+            blocks,
+            row_span: 1,
+            col_span: 1,
+        }
+    }
 }
 
 #[derive(Debug)]
@@ -141,19 +472,114 @@ pub enum Block<'source> {
     Paragraph(Vec<ParagraphSegment<'source>>),
     Header(i32, Attr, ParagraphSegment<'source>),
     BlockQuote(Vec<Block<'source>>),
-
-    MathBlock(String),
-    CodeBlock(Option<&'source str>, String),
-
-    Table(usize, Row<'source>, Vec<Row<'source>>),
+    Div(Attr, Vec<Block<'source>>),
+
+    /// Display math, the attributes (if any) are set on a `Span` around the math so that it can
+    /// be referenced (as done by pandoc-crossref).
+    MathBlock(Attr, String),
+    HorizontalRule,
+    /// An image with a caption.
+    Figure(String, Image<'source>),
+    /// A code block, the language (if any) is the first class.
+    CodeBlock(Attr, String),
+    /// Raw content in the given format (for example `html`), passed unchanged to the output.
+    ///
+    /// Produced by `@pandoc.raw` tags, embeds, custom verbatim handlers (see
+    /// [`VerbatimHandler`](crate::VerbatimHandler)) and comments rendered as HTML (see
+    /// [`Config::block_comments`](crate::Config::block_comments)).
+    Raw(Format, String),
+
+    /// A table with an optional caption, the alignment of each column, an optional header row
+    /// and the body rows.
+    Table(
+        Option<String>,
+        Vec<Alignment>,
+        Option<Row<'source>>,
+        Vec<Row<'source>>,
+    ),
 
     BulletList(Vec<ListEntry<'source>>),
-    OrderedList(Vec<ListEntry<'source>>),
+    OrderedList(ListAttributes, Vec<ListEntry<'source>>),
     DefinitionList(Vec<(ParagraphSegment<'source>, Vec<Block<'source>>)>),
+
+    /// A list of tasks generated when the document is built, see [`TaskQuery`].
+    TaskQuery(TaskQuery),
+    /// A table of contents generated when the document is built, see [`TableOfContents`].
+    TableOfContents(TableOfContents),
+    /// The links to the categories of the document, see [`CategoryLinks`].
+    ///
+    /// [`CategoryLinks`]: crate::CategoryLinks
+    CategoryLinks(Vec<String>),
 }
 
 impl<'source> Block<'source> {
-    pub fn into_pandoc(self, context: &DocumentContext) -> PandocBlock {
+    /// Adds the attributes to the block, or returns them if the block can't have attributes.
+    ///
+    /// The identifier in `attr` (if any) replaces the block's identifier.
+    pub(crate) fn add_attr(&mut self, attr: Attr) -> Result<(), Attr> {
+        let (Block::Header(_, target, _) | Block::Div(target, _) | Block::CodeBlock(target, _)) =
+            self
+        else {
+            return Err(attr);
+        };
+
+        if !attr.identifier.is_empty() {
+            target.identifier = attr.identifier;
+        }
+        target.classes.extend(attr.classes);
+        target.attributes.extend(attr.attributes);
+
+        Ok(())
+    }
+
+    /// Replaces the identifiers of the headings inside the block that are in `ids`.
+    pub(crate) fn rename_ids(&mut self, ids: &HashMap<String, String>) {
+        match self {
+            Block::Header(_, attr, _) => {
+                if let Some(id) = ids.get(&attr.identifier) {
+                    attr.identifier = id.clone();
+                }
+            }
+            Block::BlockQuote(blocks) | Block::Div(_, blocks) => {
+                blocks.iter_mut().for_each(|block| block.rename_ids(ids))
+            }
+            Block::BulletList(entries) | Block::OrderedList(_, entries) => entries
+                .iter_mut()
+                .flat_map(|entry| &mut entry.blocks)
+                .for_each(|block| block.rename_ids(ids)),
+            Block::DefinitionList(entries) => {
+                for (term, blocks) in entries {
+                    for inline in term.iter_mut() {
+                        if let Inline::Identified(_, identifier) = inline {
+                            if let Some(id) = ids.get(identifier) {
+                                *identifier = id.clone();
+                            }
+                        }
+                    }
+                    blocks.iter_mut().for_each(|block| block.rename_ids(ids));
+                }
+            }
+            Block::Table(_, _, head, body) => head
+                .iter_mut()
+                .flatten()
+                .chain(body.iter_mut().flatten())
+                .flat_map(|cell| &mut cell.blocks)
+                .for_each(|block| block.rename_ids(ids)),
+            Block::Null
+            | Block::Plain(_)
+            | Block::Paragraph(_)
+            | Block::MathBlock(_, _)
+            | Block::HorizontalRule
+            | Block::CodeBlock(_, _)
+            | Block::Raw(_, _)
+            | Block::Figure(_, _)
+            | Block::TaskQuery(_)
+            | Block::TableOfContents(_)
+            | Block::CategoryLinks(_) => {}
+        }
+    }
+
+    pub(crate) fn into_pandoc(self, context: &DocumentContext) -> PandocBlock {
         match self {
             Block::Null => PandocBlock::Null,
             Block::Plain(segment) => {
@@ -162,45 +588,76 @@ impl<'source> Block<'source> {
                 PandocBlock::Plain(inlines)
             }
             Block::Paragraph(segments) => {
-                let mut inlines = Vec::new();
-                let mut segments = segments.into_iter();
+                paragraph_to_pandoc(segments, context.config.line_break_mode, context)
+            }
+            Block::Header(level, attr, segment) => {
+                let mut inlines = convert_inlines_to_pandoc(segment, context);
 
-                if let Some(segment) = segments.next() {
-                    inlines.extend(convert_inlines_to_pandoc(segment, context));
+                if context.config.latex_references.is_some() && !attr.identifier.is_empty() {
+                    inlines.push(latex_label(&attr.identifier));
                 }
 
-                for segment in segments {
-                    inlines.push(PandocInline::Space);
-                    inlines.extend(convert_inlines_to_pandoc(segment, context));
+                let level = (level + context.config.heading_offset).max(1);
+                match context.config.max_heading_level {
+                    // Headings past the maximum level are demoted to bold paragraphs, the
+                    // attributes are kept in a span so that links to them still work
+                    Some(max) if level > max => PandocBlock::Para(vec![PandocInline::Span(
+                        attr,
+                        vec![PandocInline::Strong(inlines)],
+                    )]),
+                    _ => PandocBlock::Header(level, attr, inlines),
                 }
-
-                PandocBlock::Para(inlines)
-            }
-            Block::Header(level, attr, segment) => {
-                let inlines = convert_inlines_to_pandoc(segment, context);
-
-                PandocBlock::Header(level, attr, inlines)
             }
             Block::BlockQuote(blocks) => {
-                let blocks = convert_blocks_to_pandoc(blocks, context);
+                let mode = context.config.line_break_overrides.quotes;
+                let blocks = convert_nested_blocks(blocks, mode, context);
                 PandocBlock::BlockQuote(blocks)
             }
-            Block::CodeBlock(language, code) => {
+            Block::Div(attr, blocks) => {
+                let blocks = convert_blocks_to_pandoc(blocks, context);
+                PandocBlock::Div(attr, blocks)
+            }
+            Block::CodeBlock(attr, code) => PandocBlock::CodeBlock(attr, code),
+            Block::Raw(format, content) => PandocBlock::RawBlock(format, content),
+            Block::Figure(caption, mut image) => {
+                // The identifier belongs to the figure so that references point to it
                 let attr = Attr {
-                    classes: language.into_iter().map(ToString::to_string).collect(),
+                    identifier: std::mem::take(&mut image.attr.identifier),
                     ..Default::default()
                 };
-                PandocBlock::CodeBlock(attr, code)
+                let mut caption_inlines = text_to_inlines(&caption);
+                if context.config.latex_references.is_some() && !attr.identifier.is_empty() {
+                    caption_inlines.push(latex_label(&attr.identifier));
+                }
+
+                let caption = Caption {
+                    short: None,
+                    long: vec![PandocBlock::Plain(caption_inlines)],
+                };
+                let content = vec![PandocBlock::Plain(vec![image.into_pandoc()])];
+                PandocBlock::Figure(attr, caption, content)
             }
-            Block::MathBlock(code) => {
-                PandocBlock::Para(vec![PandocInline::Math(MathType::DisplayMath, code)])
+            Block::HorizontalRule => PandocBlock::HorizontalRule,
+            Block::MathBlock(attr, code) => {
+                let mut inlines = vec![PandocInline::Math(MathType::DisplayMath, code)];
+                if context.config.latex_references.is_some() && !attr.identifier.is_empty() {
+                    inlines.push(latex_label(&attr.identifier));
+                }
+
+                match attr == Attr::default() {
+                    true => PandocBlock::Para(inlines),
+                    false => PandocBlock::Para(vec![PandocInline::Span(attr, inlines)]),
+                }
             }
-            Block::Table(num_cols, head, body) => {
+            Block::Table(caption, alignments, head, body) => {
+                let mode = context.config.line_break_overrides.tables;
                 let convert_row = |row: Row| {
                     let cells = row
                         .into_iter()
                         .map(|cell| PandocCell {
-                            content: convert_blocks_to_pandoc(cell.blocks, context),
+                            row_span: cell.row_span,
+                            col_span: cell.col_span,
+                            content: convert_nested_blocks(cell.blocks, mode, context),
                             ..Default::default()
                         })
                         .collect();
@@ -210,13 +667,25 @@ impl<'source> Block<'source> {
                         cells,
                     }
                 };
-                let head = convert_row(head);
+                let head = head.into_iter().map(convert_row).collect();
                 let body = body.into_iter().map(convert_row).collect();
+                let colspecs = alignments
+                    .into_iter()
+                    .map(|alignment| ColSpec(alignment, ColWidth::ColWidthDefault))
+                    .collect();
+
+                let caption = Caption {
+                    short: None,
+                    long: caption
+                        .map(|caption| vec![PandocBlock::Plain(text_to_inlines(&caption))])
+                        .unwrap_or_default(),
+                };
 
                 PandocBlock::Table(Table {
-                    colspecs: vec![ColSpec::default(); num_cols],
+                    caption,
+                    colspecs,
                     head: TableHead {
-                        rows: vec![head],
+                        rows: head,
                         ..Default::default()
                     },
                     bodies: vec![TableBody {
@@ -227,20 +696,22 @@ impl<'source> Block<'source> {
                 })
             }
             Block::BulletList(entries) => {
+                let mode = context.config.line_break_overrides.lists;
                 let entries = entries
                     .into_iter()
-                    .map(|entry| convert_blocks_to_pandoc(entry.blocks, context))
+                    .map(|entry| convert_nested_blocks(entry.blocks, mode, context))
                     .collect();
 
                 PandocBlock::BulletList(entries)
             }
-            Block::OrderedList(entries) => {
+            Block::OrderedList(attributes, entries) => {
+                let mode = context.config.line_break_overrides.lists;
                 let entries = entries
                     .into_iter()
-                    .map(|entry| convert_blocks_to_pandoc(entry.blocks, context))
+                    .map(|entry| convert_nested_blocks(entry.blocks, mode, context))
                     .collect();
 
-                PandocBlock::OrderedList(Default::default(), entries)
+                PandocBlock::OrderedList(attributes, entries)
             }
             Block::DefinitionList(entries) => {
                 let entries = entries
@@ -256,17 +727,107 @@ impl<'source> Block<'source> {
 
                 PandocBlock::DefinitionList(entries)
             }
+            Block::TaskQuery(query) => query.into_pandoc(context),
+            Block::TableOfContents(toc) => toc.into_pandoc(context),
+            Block::CategoryLinks(categories) => match context.config.category_links {
+                Some(ref links) => links.links_div(categories),
+                None => PandocBlock::Null,
+            },
         }
     }
 }
 
+/// Converts the inlines to pandoc, adjacent `Str`s are merged into one and consecutive `Space`s
+/// into a single one (like pandoc's own readers do).
 pub(crate) fn convert_inlines_to_pandoc(
     inlines: Vec<Inline>,
     context: &DocumentContext,
 ) -> Vec<PandocInline> {
-    inlines
+    let mut output = Vec::with_capacity(inlines.len());
+    push_inlines_to_pandoc(&mut output, inlines, context);
+    output
+}
+
+/// Converts the inlines to pandoc like [`convert_inlines_to_pandoc`] but appends them to
+/// `output`, the first inline is merged with the last one of `output` if possible.
+fn push_inlines_to_pandoc(
+    output: &mut Vec<PandocInline>,
+    inlines: Vec<Inline>,
+    context: &DocumentContext,
+) {
+    for inline in inlines {
+        // Fast path for the words and spaces of plain text, which don't need to be converted and
+        // are appended to the previous `Str` instead of allocating a string for each one
+        match (inline, output.last_mut()) {
+            (Inline::Str(text), Some(PandocInline::Str(previous))) => previous.push_str(text),
+            (Inline::Space, Some(PandocInline::Space)) => {}
+            // Raw verbatim is plain text, so it's words are part of the surrounding text
+            (Inline::Code(text), _) if context.config.inline_verbatim == VerbatimStyle::Raw => {
+                for inline in text_to_inlines(&text) {
+                    push_pandoc_inline(output, inline);
+                }
+            }
+            (inline, _) => push_pandoc_inline(output, inline.into_pandoc(context)),
+        }
+    }
+}
+
+/// Appends a converted inline to `output`, merging it with the last one if both are words or
+/// spaces.
+fn push_pandoc_inline(output: &mut Vec<PandocInline>, inline: PandocInline) {
+    match (inline, output.last_mut()) {
+        (PandocInline::Str(text), Some(PandocInline::Str(previous))) => previous.push_str(&text),
+        (PandocInline::Space, Some(PandocInline::Space)) => {}
+        (inline, _) => output.push(inline),
+    }
+}
+
+/// Returns the `Para` of a paragraph with it's lines separated according to `mode`.
+fn paragraph_to_pandoc(
+    segments: Vec<ParagraphSegment>,
+    mode: LineBreakMode,
+    context: &DocumentContext,
+) -> PandocBlock {
+    // The lines are converted directly into the paragraph, which is sized for all of them and the
+    // separators between them
+    let len = segments.iter().map(Vec::len).sum::<usize>() + segments.len().saturating_sub(1);
+    let mut inlines = Vec::with_capacity(len);
+    let mut segments = segments.into_iter();
+
+    if let Some(segment) = segments.next() {
+        push_inlines_to_pandoc(&mut inlines, segment, context);
+    }
+
+    for segment in segments {
+        inlines.push(match mode {
+            LineBreakMode::Soft => PandocInline::Space,
+            LineBreakMode::Hard => PandocInline::LineBreak,
+            LineBreakMode::Preserve => PandocInline::SoftBreak,
+        });
+        push_inlines_to_pandoc(&mut inlines, segment, context);
+    }
+
+    PandocBlock::Para(inlines)
+}
+
+/// Converts the blocks of a quote, list item or table cell, the lines of the paragraphs directly
+/// inside it are separated according to `mode` (if set) instead of the
+/// [`Config::line_break_mode`](crate::Config::line_break_mode).
+fn convert_nested_blocks(
+    blocks: Vec<Block>,
+    mode: Option<LineBreakMode>,
+    context: &DocumentContext,
+) -> Vec<PandocBlock> {
+    let Some(mode) = mode else {
+        return convert_blocks_to_pandoc(blocks, context);
+    };
+
+    blocks
         .into_iter()
-        .map(|inline| inline.into_pandoc(context))
+        .map(|block| match block {
+            Block::Paragraph(segments) => paragraph_to_pandoc(segments, mode, context),
+            block => block.into_pandoc(context),
+        })
         .collect()
 }
 
@@ -0,0 +1,117 @@
+//! Streaming of converted documents as pandoc json (requires the `json` feature)
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::io::{self, Write};
+
+use pandoc_types::definition::{MetaValue, Pandoc};
+use serde::ser::{SerializeSeq, SerializeStruct};
+use serde::{Serialize, Serializer};
+
+use crate::document::{DocumentBuilder, DocumentContext};
+use crate::ir::Block;
+use crate::source_map::{add_source_position, SourceMap};
+use crate::Frontend;
+
+impl Frontend {
+    /// Converts the passed neorg source code and writes it's pandoc json representation to
+    /// `writer`.
+    ///
+    /// The output is the same as serializing the result of [`Frontend::convert`], but each block
+    /// is written as soon as it's converted to pandoc instead of building the whole document
+    /// first, which reduces the peak memory usage for big documents.
+    ///
+    /// The writer isn't buffered, so wrapping it in a [`BufWriter`](std::io::BufWriter) is
+    /// recommended when writing to files.
+    pub fn convert_to_writer(&mut self, source: &str, writer: impl Write) -> io::Result<()> {
+        #[cfg(feature = "tracing")]
+        let _span = tracing::info_span!("convert", bytes = source.len()).entered();
+
+        let tree = self.parse(source, None);
+        self.walk_tree(source, &tree, |document, context| {
+            write_document(document, context, writer)
+        })
+    }
+}
+
+fn write_document(
+    document: DocumentBuilder,
+    context: &DocumentContext,
+    writer: impl Write,
+) -> io::Result<()> {
+    #[cfg(feature = "tracing")]
+    let _span = tracing::info_span!("pandoc").entered();
+
+    let (blocks, meta, mut source_map) = document.into_root_blocks(context);
+    if !context.config.emit_source_positions {
+        source_map.clear();
+    }
+
+    // The version isn't exposed by `pandoc_types`, so it's taken from an empty document
+    let version = serde_json::to_value(Pandoc::default())?["pandoc-api-version"].take();
+
+    let document = StreamedDocument {
+        version,
+        meta,
+        blocks: StreamedBlocks {
+            blocks: RefCell::new(blocks),
+            source_map,
+            context,
+        },
+    };
+    serde_json::to_writer(writer, &document)?;
+
+    Ok(())
+}
+
+/// A document serialized in the same way as [`Pandoc`], with it's blocks converted while they
+/// are serialized.
+struct StreamedDocument<'a, 'source> {
+    version: serde_json::Value,
+    meta: HashMap<String, MetaValue>,
+    blocks: StreamedBlocks<'a, 'source>,
+}
+
+impl Serialize for StreamedDocument<'_, '_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut value = serializer.serialize_struct("Pandoc", 3)?;
+        value.serialize_field("pandoc-api-version", &self.version)?;
+        value.serialize_field("meta", &self.meta)?;
+        value.serialize_field("blocks", &self.blocks)?;
+        value.end()
+    }
+}
+
+/// The blocks of a document, each one is converted to pandoc right before being serialized and
+/// dropped afterwards.
+///
+/// The blocks are taken out by the serialization, so it can only be serialized once.
+struct StreamedBlocks<'a, 'source> {
+    blocks: RefCell<Vec<Block<'source>>>,
+    /// The source ranges added to the blocks (empty if the source positions aren't emitted).
+    source_map: SourceMap,
+    context: &'a DocumentContext<'a>,
+}
+
+impl Serialize for StreamedBlocks<'_, '_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let blocks = self.blocks.take();
+        let mut seq = serializer.serialize_seq(Some(blocks.len()))?;
+
+        // The source map is ordered by path, so the ranges of each block are contiguous
+        let mut sources = self.source_map.iter().peekable();
+        for (idx, block) in blocks.into_iter().enumerate() {
+            let mut block = block.into_pandoc(self.context);
+
+            while let Some((path, source)) =
+                sources.next_if(|(path, _)| path.0.first() == Some(&idx))
+            {
+                add_source_position(&mut block, &path.0[1..], source);
+            }
+
+            seq.serialize_element(&block)?;
+        }
+
+        seq.end()
+    }
+}
@@ -17,67 +17,364 @@
 //! let document = frontend.convert(norg_source);
 //! ```
 //!
+//! # Features
+//!
+//! - `async`: Adds [`Frontend::convert_async`] to convert documents in tokio's blocking thread
+//!   pool, with support for cancellation.
+//! - `ir`: Makes the intermediate representation of documents ([`ir`]) public and adds
+//!   [`Frontend::add_transform`] to apply [`Transform`]s to it before it's converted to pandoc,
+//!   and [`Config::register_verbatim_handler`] to support custom verbatim ranged tags.
+//! - `json`: Adds [`Frontend::convert_to_writer`] to write the pandoc json of documents while
+//!   they are converted.
+//! - `markdown`: Adds [`Frontend::convert_to_markdown`], a built-in writer of pandoc markdown
+//!   (compatible with CommonMark for the common elements) that doesn't need pandoc.
+//! - `serde`: Allows the [`Config`] (and the types used by it) to be deserialized, the callbacks
+//!   ([`Config::on_diagnostic`] and [`Config::variables`]) are skipped.
+//! - `tracing`: Emits [`tracing`] spans around the parsing, the tree walking and the conversion to
+//!   pandoc of each document (with the number of nodes visited and blocks produced), which can be
+//!   used to find out where time goes when converting big documents.
+//!
+//! # WebAssembly
+//!
+//! The library can be built for `wasm32-unknown-unknown`, which requires a C compiler that
+//! targets WebAssembly (like clang) to build the tree-sitter runtime and the neorg grammar.
+//! [`Config::parallel`] is ignored and the `async` feature isn't supported on it.
+//!
 //! [neorg]: https://github.com/nvim-neorg/neorg
 //! [pandoc]: https://pandoc.org/
+//! [`tracing`]: https://docs.rs/tracing
 
+use std::borrow::Cow;
 use std::collections::HashMap;
+use std::sync::Arc;
+#[cfg(feature = "ir")]
+use std::sync::{Mutex, PoisonError};
 
-use document::{DocumentBuilder, DocumentContext};
+use document::{DefinitionKind, DocumentBuilder, DocumentContext};
 use field_ids::FieldIds;
-use pandoc_types::definition::{Attr, Pandoc};
-use tree_sitter::TreeCursor;
+use pandoc_types::definition::{Attr, MetaValue, Pandoc};
+#[cfg(feature = "serde")]
+use serde::Deserialize;
+use tree_sitter::{Parser, Tree, TreeCursor};
 
 use ir::Block;
+use tags::CarryoverTag;
+
+#[cfg(all(feature = "async", target_arch = "wasm32"))]
+compile_error!("The `async` feature isn't supported on WebAssembly");
 
+#[cfg(feature = "async")]
+mod asynchronous;
+mod citations;
 mod definitions;
+mod diagnostics;
 mod document;
+mod embed;
+mod epub;
 mod extensions;
 mod field_ids;
+mod flashcards;
+mod footnotes;
+mod images;
+mod incremental;
 mod inlines;
+#[cfg(feature = "ir")]
+pub mod ir;
+#[cfg(not(feature = "ir"))]
 mod ir;
+#[cfg(feature = "json")]
+mod json;
 mod lists;
+#[cfg(feature = "markdown")]
+mod markdown;
 mod meta;
+mod parallel;
+mod project;
 mod quote;
+mod sections;
+mod slides;
+mod source_map;
+mod stringify;
 mod table;
 mod tags;
+mod tangle;
+mod tasks;
+mod timestamps;
+mod title;
+mod toc;
+#[cfg(feature = "ir")]
+mod transform;
 
-pub use extensions::TodoSymbols;
+#[cfg(feature = "async")]
+pub use asynchronous::{CancellationToken, Cancelled};
+pub use citations::Citations;
+pub use diagnostics::{Diagnostic, DiagnosticHandler, Severity};
+pub use document::Heading;
+pub use epub::EpubSemantics;
+pub use extensions::{
+    ExtensionKind, ExtensionOrder, TodoKeywords, TodoOutputMode, TodoStatus, TodoSymbols,
+};
+pub use flashcards::Flashcard;
+pub use images::InlineImages;
+pub use incremental::NorgTree;
+pub use inlines::{
+    CommentStyle, LineBreakMode, LineBreakOverrides, LinkTemplates, ModifierMap, ModifierStyle,
+    VariableResolver, VerbatimStyle, WikiLinks, WorkspaceLinks,
+};
+pub use ir::LatexReferences;
+pub use lists::{ListNumbering, NumberingDelimiter, NumberingStyle};
+#[cfg(feature = "markdown")]
+pub use markdown::write_markdown;
+pub use meta::CategoryLinks;
+pub use project::Project;
+pub use sections::{SectionInfo, TodoSummary};
+pub use slides::{SlideSplit, Slides};
+pub use source_map::{BlockPath, SourceMap, SourceRange};
+#[cfg(feature = "ir")]
+pub use tags::{InfirmHandler, VerbatimHandler};
+pub use tangle::TangledFile;
+pub use tasks::DatedTask;
+pub use timestamps::TimestampStyle;
+#[cfg(feature = "ir")]
+pub use transform::{walk_block, walk_inline, Transform};
+/// The types of tree-sitter used to describe the edits passed to [`NorgTree::edit`].
+pub use tree_sitter::{InputEdit, Point};
 
 use crate::document::DocumentLinkType;
 
 #[derive(Default)]
 struct FrontendState {
     identifiers: HashMap<String, u32>,
+    /// If set, the text passed to [`FrontendState::generate_id`] and the returned identifier are
+    /// recorded, this is used to regenerate the identifiers of sections converted in parallel.
+    generated: Option<Vec<(String, String)>>,
+    /// If set and cancelled the tree walk stops early, see [`Frontend::convert_async`].
+    #[cfg(feature = "async")]
+    cancellation: Option<CancellationToken>,
 }
 
 impl FrontendState {
     /// Generates an unique (for a given `Frontend` instance) string that's a
     /// valid HTML5 `id` attribute value from the passed text.
-    fn generate_id(&mut self, text: &str) -> String {
-        // https://html.spec.whatwg.org/multipage/dom.html#the-id-attribute
-        //
-        // > When specified on HTML elements, the id attribute value must be unique
-        // > amongst all the IDs in the element's tree and must contain at least one
-        // > character. The value must not contain any ASCII whitespace.
-        //
-        // Also replace tildes (`~`) so that they can be used for appending the counter,
-        // and other whitespace-like characthers (like tabs and newlines) because while this
-        // isn't necessary for HTML5 other formats don't handle them well
-        let mut base = text.replace([' ', '~', '\t', '\n'], "-");
-
-        // If `base` was already used as an identifier a counter will be appended
-        // to it so that a new unique id can be generated
-        match self.identifiers.get_mut(&base) {
-            Some(counter) => {
-                base.push_str(&format!("~{}", *counter));
+    ///
+    /// The identifiers only depend on the texts passed before (and the configuration), so the
+    /// same documents always get the same identifiers.
+    fn generate_id(&mut self, text: &str, config: &Config) -> String {
+        let duplicates = &config.duplicate_ids;
+        let base = base_id(text, config.id_style, config.id_characters);
+
+        let id = match self.identifiers.get(&base) {
+            None => base,
+            // If `base` was already used as an identifier a counter will be appended to it, the
+            // counter is increased until an unused identifier is found since the result might
+            // collide with the identifier of another text (for example a heading named `A-1`)
+            Some(_) => loop {
+                let counter = self.identifiers.get_mut(&base).expect("Base was removed");
+                let candidate = duplicates.format(&base, duplicates.start + *counter);
                 *counter += 1;
-            }
-            None => {
-                self.identifiers.insert(base.clone(), 0);
-            }
+
+                if !self.identifiers.contains_key(&candidate) {
+                    break candidate;
+                }
+            },
+        };
+
+        // All the generated identifiers are stored so that later duplicates of them (either
+        // directly or through a counter) can be detected
+        self.identifiers.insert(id.clone(), 0);
+
+        if let Some(generated) = self.generated.as_mut() {
+            generated.push((text.to_string(), id.clone()));
         }
 
-        base
+        id
+    }
+}
+
+/// Returns the identifier generated from `text` before it's made unique.
+fn base_id(text: &str, style: IdStyle, characters: IdCharacters) -> String {
+    let text = match characters {
+        IdCharacters::Unicode => Cow::Borrowed(text),
+        IdCharacters::Ascii => Cow::Owned(transliterate(text)),
+    };
+
+    let id = match style {
+        IdStyle::Raw => {
+            // https://html.spec.whatwg.org/multipage/dom.html#the-id-attribute
+            //
+            // > When specified on HTML elements, the id attribute value must be unique
+            // > amongst all the IDs in the element's tree and must contain at least one
+            // > character. The value must not contain any ASCII whitespace.
+            //
+            // Also replace tildes (`~`) and other whitespace-like characthers (like tabs and
+            // non-breaking spaces) because while this isn't necessary for HTML5 other formats
+            // don't handle them well, and remove the characters that end or break the fragment
+            // of an URL (like `#` and `/`) so that links to the identifier work
+            text.chars()
+                .filter_map(|c| match c {
+                    '~' => Some('-'),
+                    c if c.is_whitespace() => Some('-'),
+                    c if c.is_control() || is_fragment_breaking(c) => None,
+                    c => Some(c),
+                })
+                .collect()
+        }
+        IdStyle::Pandoc => {
+            let text: String = text
+                .chars()
+                .filter(|c| {
+                    c.is_alphanumeric() || c.is_whitespace() || matches!(c, '_' | '-' | '.')
+                })
+                .flat_map(char::to_lowercase)
+                .collect();
+            let id = text.split_whitespace().collect::<Vec<_>>().join("-");
+            id.trim_start_matches(|c: char| !c.is_alphabetic())
+                .to_string()
+        }
+        IdStyle::GitHub => text
+            .trim()
+            .chars()
+            .filter(|c| c.is_alphanumeric() || matches!(c, ' ' | '_' | '-'))
+            .flat_map(char::to_lowercase)
+            .map(|c| if c == ' ' { '-' } else { c })
+            .collect(),
+    };
+
+    match id.is_empty() {
+        true => String::from("section"),
+        false => id,
+    }
+}
+
+/// Returns whether the character can't be used unescaped in the fragment of an URL.
+fn is_fragment_breaking(c: char) -> bool {
+    matches!(
+        c,
+        '#' | '/' | '?' | '%' | '"' | '<' | '>' | '\\' | '`' | '^' | '{' | '}' | '|' | '[' | ']'
+    )
+}
+
+/// Replaces the accented latin letters with their ASCII counterparts and removes all the other
+/// non ASCII characters, except whitespace which is kept as a space.
+fn transliterate(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+
+    for c in text.chars() {
+        if c.is_ascii() {
+            out.push(c);
+            continue;
+        }
+
+        let replacement = match c {
+            'À'..='Å' | 'Ā' | 'Ă' | 'Ą' => "A",
+            'à'..='å' | 'ā' | 'ă' | 'ą' => "a",
+            'Æ' => "AE",
+            'æ' => "ae",
+            'Ç' | 'Ć' | 'Č' => "C",
+            'ç' | 'ć' | 'č' => "c",
+            'Ð' | 'Ď' | 'Đ' => "D",
+            'ð' | 'ď' | 'đ' => "d",
+            'È'..='Ë' | 'Ē' | 'Ė' | 'Ę' | 'Ě' => "E",
+            'è'..='ë' | 'ē' | 'ė' | 'ę' | 'ě' => "e",
+            'Ğ' => "G",
+            'ğ' => "g",
+            'Ì'..='Ï' | 'Ī' | 'Į' | 'İ' => "I",
+            'ì'..='ï' | 'ī' | 'į' | 'ı' => "i",
+            'Ł' => "L",
+            'ł' => "l",
+            'Ñ' | 'Ń' | 'Ň' => "N",
+            'ñ' | 'ń' | 'ň' => "n",
+            'Ò'..='Ö' | 'Ø' | 'Ō' | 'Ő' => "O",
+            'ò'..='ö' | 'ø' | 'ō' | 'ő' => "o",
+            'Œ' => "OE",
+            'œ' => "oe",
+            'Ř' => "R",
+            'ř' => "r",
+            'Ś' | 'Ş' | 'Š' => "S",
+            'ś' | 'ş' | 'š' => "s",
+            'ß' => "ss",
+            'Ť' => "T",
+            'ť' => "t",
+            'Þ' => "Th",
+            'þ' => "th",
+            'Ù'..='Ü' | 'Ū' | 'Ů' | 'Ű' | 'Ų' => "U",
+            'ù'..='ü' | 'ū' | 'ů' | 'ű' | 'ų' => "u",
+            'Ý' | 'Ÿ' => "Y",
+            'ý' | 'ÿ' => "y",
+            'Ź' | 'Ż' | 'Ž' => "Z",
+            'ź' | 'ż' | 'ž' => "z",
+            '‐'..='—' => "-",
+            c if c.is_whitespace() => " ",
+            _ => "",
+        };
+        out.push_str(replacement);
+    }
+
+    out
+}
+
+/// The algorithm used to generate identifiers from text (like the titles of headings).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum IdStyle {
+    /// The text with whitespace and tildes replaced with `-` (default), for example
+    /// `My Heading!` becomes `My-Heading!`.
+    #[default]
+    Raw,
+    /// The same identifiers as pandoc's `auto_identifiers` extension, for example `My Heading!`
+    /// becomes `my-heading`.
+    Pandoc,
+    /// The same identifiers as GitHub (and pandoc's `gfm_auto_identifiers` extension), for
+    /// example `1. My  Heading!` becomes `1-my--heading`.
+    GitHub,
+}
+
+/// The characters kept in the identifiers generated from text, see [`Config::id_characters`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum IdCharacters {
+    /// Any unicode letter or number is kept (default), for example `Über` stays `Über`.
+    #[default]
+    Unicode,
+    /// Only ASCII characters are kept, accented latin letters are replaced with their ASCII
+    /// counterparts and the other characters are removed, for example `Über` becomes `Uber`.
+    ///
+    /// This is useful for formats that don't handle unicode identifiers well (like LaTeX
+    /// labels).
+    Ascii,
+}
+
+/// Defines how the identifiers of elements with the same text (like headings with the same title)
+/// are made unique.
+///
+/// The first element uses the identifier generated from the text and the following ones use
+/// the identifier formatted with a counter.
+#[cfg_attr(feature = "serde", derive(Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
+pub struct DuplicateIds {
+    /// The format of the identifiers of duplicates, `{id}` is replaced with the identifier and
+    /// `{n}` with the counter (default: `{id}-{n}`).
+    pub format: String,
+    /// The counter of the first duplicate (default: `1`).
+    pub start: u32,
+}
+
+impl Default for DuplicateIds {
+    fn default() -> Self {
+        Self {
+            format: String::from("{id}-{n}"),
+            start: 1,
+        }
+    }
+}
+
+impl DuplicateIds {
+    fn format(&self, id: &str, counter: u32) -> String {
+        self.format
+            .replace("{id}", id)
+            .replace("{n}", &counter.to_string())
     }
 }
 
@@ -93,51 +390,297 @@ impl FrontendState {
 /// same pandoc document, for example if generating an html document by including the result of
 /// many neorg documents and stitching them together, this is because the `Frontend` keeps track of
 /// some information in order to ensure for example unique identifiers between the processed files.
+/// It also reuses it's parser between conversions, which makes converting many small documents
+/// faster, use [`Frontend::reset`] to forget the information of the previous documents.
 ///
 /// [`&str`]: str
 /// [`convert`]: Frontend::convert
 #[derive(Default)]
 pub struct Frontend {
-    config: Config,
+    /// The configuration, shared with the conversions running in other threads (see
+    /// [`Frontend::convert_async`]).
+    config: Arc<Config>,
     state: FrontendState,
+    /// The transforms, shared like the configuration.
+    #[cfg(feature = "ir")]
+    transforms: Arc<Mutex<Vec<Box<dyn Transform>>>>,
+    /// The link targets of the other documents while converting a [`Project`].
+    project: Option<Arc<project::ProjectIndex>>,
+    /// The parser used by all the conversions, created by the first one.
+    parser: Option<Parser>,
 }
 
 impl Frontend {
     /// Creates a new `Frontend` with the provided configuration.
     pub fn new(config: Config) -> Self {
         Frontend {
-            config,
+            config: Arc::new(config),
             ..Default::default()
         }
     }
 
+    /// Forgets the information kept from the previous conversions (like the identifiers already
+    /// used), so the next document is converted as if it was the first one.
+    ///
+    /// The configuration and the transforms are kept.
+    pub fn reset(&mut self) {
+        self.state = Default::default();
+
+        if let Some(parser) = self.parser.as_mut() {
+            parser.reset();
+        }
+    }
+
+    /// Parses the neorg source code with the `Frontend`'s parser, reusing the unchanged parts of
+    /// `old_tree` (which must already have been edited to match `source`) if passed.
+    fn parse(&mut self, source: &str, old_tree: Option<&Tree>) -> Tree {
+        #[cfg(feature = "tracing")]
+        let _span = tracing::info_span!("parse", incremental = old_tree.is_some()).entered();
+
+        let parser = self.parser.get_or_insert_with(|| {
+            let mut parser = Parser::new();
+            parser
+                .set_language(tree_sitter_norg::language())
+                .expect("Failed to load tree sitter grammar");
+            parser
+        });
+
+        parser
+            .parse(source, old_tree)
+            .expect("Failed to parse file")
+    }
+
     /// Converts the passed neorg source code to it's pandoc representation.
     pub fn convert(&mut self, source: &str) -> Pandoc {
-        let mut parser = tree_sitter::Parser::new();
-        parser
-            .set_language(tree_sitter_norg::language())
-            .expect("Failed to load tree sitter grammar");
+        self.convert_with(source, |document, _, _| document)
+    }
 
-        let tree = parser.parse(source, None).expect("Failed to parse file");
-        let field_ids = FieldIds::new(&tree);
-        let mut cursor = tree.walk();
+    /// Converts the passed neorg source code to it's pandoc representation and also returns
+    /// metadata about each of the document's top level sections.
+    ///
+    /// See [`SectionInfo`] for the extracted information.
+    pub fn convert_with_sections(&mut self, source: &str) -> (Pandoc, Vec<SectionInfo>) {
+        self.convert_with(source, |document, context, _| {
+            let sections = sections::extract_sections(&document, context);
+            (document, sections)
+        })
+    }
 
-        let mut builder = Builder {
-            source,
-            cursor: &mut cursor,
+    /// Converts the passed neorg source code to it's pandoc representation and also returns
+    /// the flashcards defined in the document.
+    ///
+    /// See [`Flashcard`] for how the flashcards are defined.
+    pub fn convert_with_flashcards(&mut self, source: &str) -> (Pandoc, Vec<Flashcard>) {
+        self.convert_with(source, |document, context, _| {
+            let cards = flashcards::extract_flashcards(&document, context);
+            (document, cards)
+        })
+    }
 
-            config: &self.config,
-            frontend: &mut self.state,
-            field_ids,
+    /// Converts the passed neorg source code to it's pandoc representation and also returns
+    /// the outline of the document, the headings in the order they appear.
+    ///
+    /// This is meant to be used to build the navigation of a site, see [`Heading`].
+    pub fn convert_with_outline(&mut self, source: &str) -> (Pandoc, Vec<Heading>) {
+        self.convert_with(source, |document, context, _| {
+            (document, context.headings.clone())
+        })
+    }
 
-            document: DocumentBuilder::default(),
-            context: DocumentContext::default(),
+    /// Converts the passed neorg source code to it's pandoc representation and also returns
+    /// the source range of the document's blocks.
+    ///
+    /// This is meant to be used by previewers to synchronize the cursor position or to fold
+    /// blocks, see [`SourceMap`].
+    pub fn convert_with_source_map(&mut self, source: &str) -> (Pandoc, SourceMap) {
+        self.convert_with(source, |document, _, source_map| (document, source_map))
+    }
+
+    /// Converts the passed neorg source code to markdown with the built-in writer, without
+    /// running pandoc.
+    ///
+    /// The output is pandoc markdown, which is also valid CommonMark for headings, paragraphs,
+    /// lists, code blocks, quotes, links and images. The metadata and the attributes of most
+    /// elements are dropped, use pandoc to convert the json output for a complete conversion.
+    #[cfg(feature = "markdown")]
+    pub fn convert_to_markdown(&mut self, source: &str) -> String {
+        let document = self.convert(source);
+        markdown::write_markdown(&document)
+    }
+
+    /// Extracts the code blocks of the passed neorg source code into files (tangles the document),
+    /// see [`TangledFile`] for how the files of the code blocks are chosen.
+    ///
+    /// The files are returned in the order they first appear in the document and aren't written.
+    pub fn tangle(&mut self, source: &str) -> Vec<TangledFile> {
+        let tree = self.parse(source, None);
+        self.walk_tree(source, &tree, |document, _| {
+            let (blocks, metadata) = document.finish();
+            let blocks = blocks.iter().map(|(block, _)| block);
+            tangle::tangle_blocks(blocks, metadata.get("tangle"))
+        })
+    }
+
+    /// Returns the tasks of the passed neorg source code with a start (`(> 2024-06-01)`) or due
+    /// (`(< 2024-06-01)`) date, in the order they appear in the document.
+    ///
+    /// This is meant to export the tasks to calendar applications, see [`DatedTask`].
+    pub fn dated_tasks(&mut self, source: &str) -> Vec<DatedTask> {
+        let tree = self.parse(source, None);
+        self.walk_tree(source, &tree, |_, context| tasks::dated_tasks(context))
+    }
+
+    /// Returns the debug representation of the intermediate representation of the passed neorg
+    /// source code (the blocks before being converted to pandoc), useful to report bugs.
+    #[cfg(feature = "ir")]
+    pub fn debug_ir(&mut self, source: &str) -> String {
+        let tree = self.parse(source, None);
+        self.walk_tree(source, &tree, |document, _| {
+            let (blocks, _) = document.finish();
+            let blocks: Vec<_> = blocks.into_iter().map(|(block, _)| block).collect();
+            format!("{:#?}", blocks)
+        })
+    }
+
+    /// Converts the passed neorg source code and passes the result, the context collected while
+    /// building it and the source map of the result, to `f`.
+    fn convert_with<R>(
+        &mut self,
+        source: &str,
+        f: impl FnOnce(Pandoc, &DocumentContext, SourceMap) -> R,
+    ) -> R {
+        #[cfg(feature = "tracing")]
+        let _span = tracing::info_span!("convert", bytes = source.len()).entered();
+
+        let tree = self.parse(source, None);
+        self.convert_tree(source, &tree, f)
+    }
+
+    /// Converts the already parsed `tree` of the passed neorg source code and passes the result,
+    /// the context collected while building it and the source map of the result, to `f`.
+    fn convert_tree<R>(
+        &mut self,
+        source: &str,
+        tree: &Tree,
+        f: impl FnOnce(Pandoc, &DocumentContext, SourceMap) -> R,
+    ) -> R {
+        self.walk_tree(source, tree, |document, context| {
+            let (document, source_map) = {
+                #[cfg(feature = "tracing")]
+                let span = tracing::info_span!("pandoc", blocks = tracing::field::Empty).entered();
+
+                let built = document.build(context);
+
+                #[cfg(feature = "tracing")]
+                span.record("blocks", built.0.blocks.len());
+
+                built
+            };
+
+            f(document, context, source_map)
+        })
+    }
+
+    /// Walks the already parsed `tree` of the passed neorg source code and passes the document
+    /// (before being converted to pandoc) and the context collected while building it to `f`.
+    fn walk_tree<R>(
+        &mut self,
+        source: &str,
+        tree: &Tree,
+        f: impl FnOnce(DocumentBuilder, &DocumentContext) -> R,
+    ) -> R {
+        let (mut document, mut context) = {
+            #[cfg(feature = "tracing")]
+            let _span = tracing::info_span!("walk", nodes = tracing::field::Empty).entered();
+
+            // Threads aren't available in WebAssembly
+            let parallel = self.config.parallel && cfg!(not(target_arch = "wasm32"));
+            if parallel && self.config.section_filter.is_none() {
+                parallel::walk_sections(source, tree, &self.config, &mut self.state)
+            } else {
+                let field_ids = FieldIds::new(tree);
+                let mut cursor = tree.walk();
+                let mut builder = Builder::new(
+                    source,
+                    &mut cursor,
+                    &self.config,
+                    &mut self.state,
+                    field_ids,
+                );
+                builder.handle_node();
+                builder.finish()
+            }
         };
 
-        builder.handle_node();
+        document.add_default_meta(&self.config.base_metadata);
+
+        #[cfg(feature = "ir")]
+        {
+            // A transform that panicked in another conversion doesn't prevent using the others
+            let mut transforms = self
+                .transforms
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner);
+            for transform in transforms.iter_mut() {
+                document.transform(transform.as_mut());
+                context.footnotes.transform(transform.as_mut());
+            }
+        }
+
+        context.project = self.project.as_deref();
+        context.resolve_anchors();
+        context.build_footnotes();
+        f(document, &context)
+    }
+}
+
+/// Returns the tree-sitter syntax tree of the neorg source code as an indented S-expression,
+/// with the field name and the (zero based) start and end positions of each node.
+///
+/// This is the tree walked by the converter, which is useful to report unhandled nodes.
+pub fn syntax_tree(source: &str) -> String {
+    let tree = Frontend::default().parse(source, None);
+    let mut cursor = tree.walk();
+    let mut out = String::new();
+    write_syntax_node(&mut cursor, 0, &mut out);
+    out
+}
+
+fn write_syntax_node(cursor: &mut TreeCursor, depth: usize, out: &mut String) {
+    let node = cursor.node();
+    let (start, end) = (node.start_position(), node.end_position());
+
+    if depth > 0 {
+        out.push('\n');
+    }
+    out.push_str(&"  ".repeat(depth));
+    if let Some(field) = cursor.field_name() {
+        out.push_str(field);
+        out.push_str(": ");
+    }
+    out.push_str(&format!(
+        "({} [{}, {}] - [{}, {}]",
+        node.kind(),
+        start.row,
+        start.column,
+        end.row,
+        end.column
+    ));
 
-        builder.document.build(&builder.context)
+    if cursor.goto_first_child() {
+        loop {
+            if cursor.node().is_named() {
+                write_syntax_node(cursor, depth + 1, out);
+            }
+
+            if !cursor.goto_next_sibling() {
+                break;
+            }
+        }
+        cursor.goto_parent();
     }
+
+    out.push(')');
 }
 
 /// Holds the configuration used by a [`Frontend`].
@@ -146,9 +689,164 @@ impl Frontend {
 ///
 /// [`default`]: Config::default
 #[derive(Default)]
+#[cfg_attr(feature = "serde", derive(Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
 pub struct Config {
     /// Defines the symbols to be used for neorg's TODO status extension.
     pub todo_symbols: TodoSymbols,
+    /// Defines how the TODO statuses are rendered, see [`TodoOutputMode`].
+    pub todo_output: TodoOutputMode,
+    /// Enables the recognition of `[[Page Name]]` style links (disabled by default).
+    ///
+    /// This is useful for documents migrated from other tools like Obsidian, see [`WikiLinks`]
+    /// for how the links are resolved.
+    pub wiki_links: Option<WikiLinks>,
+    /// Renders the TODO status of headings as org-mode style keywords instead of symbols
+    /// (disabled by default), see [`TodoKeywords`].
+    pub heading_todo_keywords: Option<TodoKeywords>,
+    /// Emits raw LaTeX labels and references for internal links (disabled by default), see
+    /// [`LatexReferences`].
+    pub latex_references: Option<LatexReferences>,
+    /// Wraps sections in `Div`s tagged with their EPUB structural semantics (disabled by default),
+    /// see [`EpubSemantics`].
+    pub epub_semantics: Option<EpubSemantics>,
+    /// Wraps each heading and it's content in a `Div` with the `section` class (disabled by
+    /// default), like pandoc's `--section-divs`.
+    ///
+    /// The identifier of the heading is moved to the `Div`, so links to the heading point to the
+    /// whole section. When the [`EpubSemantics`] are enabled the same `Div`s are used.
+    pub section_divs: bool,
+    /// Adds hints for pandoc's slide writers so that the slides are split at the headings of the
+    /// given level (disabled by default), see [`Slides`].
+    pub slides: Option<Slides>,
+    /// Receives the warnings and errors found while converting documents (by default they are
+    /// reported with the `log` crate).
+    ///
+    /// This allows applications to show them in their own interface.
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub on_diagnostic: Option<DiagnosticHandler>,
+    /// Converts the top level sections of each document in parallel (disabled by default).
+    ///
+    /// This is only worth it for very big documents, the output is the same as the one of the
+    /// sequential conversion. It's ignored in WebAssembly, where threads aren't available.
+    pub parallel: bool,
+    /// Defines how the identifiers of headings with the same title are made unique.
+    pub duplicate_ids: DuplicateIds,
+    /// Defines how identifiers are generated from the titles of headings, see [`IdStyle`].
+    pub id_style: IdStyle,
+    /// Defines which characters are kept in the identifiers generated from text, see
+    /// [`IdCharacters`].
+    pub id_characters: IdCharacters,
+    /// Gives the terms of definitions an identifier (disabled by default), which is required
+    /// for links to definitions (`{$ term}`) to work.
+    ///
+    /// The identifiers are generated from the term prefixed with `term` (for example `term-Object`
+    /// with the default [`IdStyle`]) so that they don't take the identifiers of headings.
+    pub definition_ids: bool,
+    /// Defines the pandoc inlines used for the attached modifiers, see [`ModifierMap`].
+    pub modifier_map: ModifierMap,
+    /// Defines how inline verbatim is rendered, see [`VerbatimStyle`].
+    pub inline_verbatim: VerbatimStyle,
+    /// Defines the order in which chained detached modifier extensions are rendered, see
+    /// [`ExtensionOrder`].
+    pub extension_order: ExtensionOrder,
+    /// Only converts the heading with the given path of titles and it's content (and the
+    /// document's metadata), for example `["Chapters", "Introduction"]` selects the
+    /// `Introduction` heading inside of the `Chapters` heading (disabled by default).
+    ///
+    /// The titles are matched ignoring their case and whitespace, if no heading matches the
+    /// document is empty. Sections are never converted in parallel when this is set.
+    pub section_filter: Option<Vec<String>>,
+    /// Defines how links to other norg files are resolved, see [`WorkspaceLinks`].
+    pub workspace_links: WorkspaceLinks,
+    /// Defines the urls of links to lines and elements inside of files, see [`LinkTemplates`].
+    pub link_templates: LinkTemplates,
+    /// The values of inline variables (`&name&`) supplied from outside the document, the
+    /// variables defined in the document with macro tags (`=name ... =end`) take precedence.
+    pub variable_values: HashMap<String, String>,
+    /// Returns the values of inline variables (`&name&`) that aren't defined in the document or
+    /// in [`Config::variable_values`], the undefined variables are rendered as a `Span` with the
+    /// `variable` class containing their name.
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub variables: Option<VariableResolver>,
+    /// Defines how inline comments are rendered (by default they are removed), see
+    /// [`CommentStyle`].
+    pub inline_comments: CommentStyle,
+    /// Adds a `data-pos` attribute with the position (`line:column`, one based) in the neorg
+    /// source to the top level blocks with attributes (headers, `Div`s, code blocks and tables),
+    /// useful to synchronize the scrolling of live previews (disabled by default).
+    ///
+    /// Only the blocks in the [`SourceMap`] get a position, which are the top level blocks of the
+    /// source (even when they are wrapped in section `Div`s). The section `Div`s themselves and
+    /// the blocks nested in other blocks (like list items and the content of quotes) don't get
+    /// one, and neither do top level lists and paragraphs since pandoc has no attributes for
+    /// them, so previews should scroll to the closest previous block with a position.
+    pub emit_source_positions: bool,
+    /// Includes the elements (and sections) marked with the `#private` or `#export.ignore`
+    /// carryover tags, which are skipped by default. The included elements get a class with the
+    /// tag's name.
+    pub include_private: bool,
+    /// Defines how comment blocks (`@comment` ranged tags and elements with a `#comment` carryover
+    /// tag) are rendered (by default they are removed), see [`CommentStyle`].
+    pub block_comments: CommentStyle,
+    /// Defines how the lines of paragraphs are separated (by default they are joined with
+    /// spaces), see [`LineBreakMode`].
+    pub line_break_mode: LineBreakMode,
+    /// Defines how the lines of the paragraphs in quotes, list items and table cells are
+    /// separated if it should be different from the [`Config::line_break_mode`], see
+    /// [`LineBreakOverrides`].
+    pub line_break_overrides: LineBreakOverrides,
+    /// Defines the default numbering of ordered lists, see [`ListNumbering`].
+    pub list_numbering: ListNumbering,
+    /// Disables the mapping of neorg's metadata fields to the standard pandoc fields (`authors` to
+    /// `author` and `updated`/`created` to `date`).
+    pub raw_metadata: bool,
+    /// Metadata added to all the documents, the values in the documents' metadata
+    /// (`@document.meta`) take precedence over these (for example `lang` or `toc-title`).
+    pub base_metadata: HashMap<String, MetaValue>,
+    /// Adds links to the categories of the document (disabled by default), see
+    /// [`CategoryLinks`].
+    pub category_links: Option<CategoryLinks>,
+    /// Converts the links with the citation prefix (like `{cite:key}`) to citations (disabled by
+    /// default), see [`Citations`].
+    pub citations: Option<Citations>,
+    /// Defines how timestamps (`{@ 2023-04-01}`) are rendered, see [`TimestampStyle`].
+    pub timestamps: TimestampStyle,
+    /// Shifts the level of all the headings by the given amount (like pandoc's
+    /// `--shift-heading-level-by`), for example `1` converts `*` headings to level 2 headings.
+    ///
+    /// Levels are never shifted below 1.
+    pub heading_offset: i32,
+    /// Promotes the heading to the `title` metadata field if the document has a single level 1
+    /// heading and no title (including in the [`Config::base_metadata`]), the other headings are
+    /// shifted up one level (disabled by default).
+    ///
+    /// This is the usual convention for standalone documents, where pandoc renders the title.
+    pub promote_title: bool,
+    /// The deepest heading level in the output (after applying the [`Config::heading_offset`]),
+    /// deeper headings are demoted to bold paragraphs (no limit by default).
+    pub max_heading_level: Option<i32>,
+    /// Inlines local images as `data:` URIs (disabled by default), see [`InlineImages`].
+    pub inline_images: Option<InlineImages>,
+    /// Disables raw HTML, `@embed html` and `@pandoc.raw html` tags are removed (with a warning)
+    /// and `@embed video` and `@embed audio` tags are converted to links to the file.
+    pub disable_raw_html: bool,
+    /// Adds the `numberLines` class to all the code blocks so that pandoc numbers their lines
+    /// (disabled by default).
+    ///
+    /// Single code blocks can also be numbered with a `#numberLines` carryover tag, and the first
+    /// line number can be set with `#startFrom 10`.
+    pub number_lines: bool,
+    /// The handlers of custom verbatim ranged tags by the tag's name, see
+    /// [`Config::register_verbatim_handler`].
+    #[cfg(feature = "ir")]
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub verbatim_handlers: HashMap<String, VerbatimHandler>,
+    /// The handlers of custom infirm tags by the tag's name, see
+    /// [`Config::register_infirm_handler`].
+    #[cfg(feature = "ir")]
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub infirm_handlers: HashMap<String, InfirmHandler>,
 }
 
 struct Builder<'builder, 'source>
@@ -159,22 +857,76 @@ where
     cursor: &'builder mut TreeCursor<'source>,
 
     config: &'source Config,
-    frontend: &'source mut FrontendState,
+    frontend: &'builder mut FrontendState,
     field_ids: FieldIds,
 
     document: DocumentBuilder<'source>,
     context: DocumentContext<'source>,
+    /// The index of the top level section currently being processed.
+    section: Option<usize>,
+    /// The carryover tags waiting to be applied to the next element.
+    carryover_tags: Vec<CarryoverTag<'source>>,
+    /// The index in [`DocumentContext::headings`] of the heading currently being processed.
+    heading: Option<usize>,
+    /// The number of nodes passed to [`Builder::handle_node`].
+    #[cfg(feature = "tracing")]
+    visited_nodes: usize,
 }
 
 impl<'builder, 'source> Builder<'builder, 'source>
 where
     'source: 'builder,
 {
+    fn new(
+        source: &'source str,
+        cursor: &'builder mut TreeCursor<'source>,
+        config: &'source Config,
+        frontend: &'builder mut FrontendState,
+        field_ids: FieldIds,
+    ) -> Self {
+        Builder {
+            source,
+            cursor,
+
+            config,
+            frontend,
+            field_ids,
+
+            document: DocumentBuilder::default(),
+            context: DocumentContext::new(config),
+            section: None,
+            carryover_tags: Vec::new(),
+            heading: None,
+            #[cfg(feature = "tracing")]
+            visited_nodes: 0,
+        }
+    }
+
+    /// Returns the document and the context that were built.
+    fn finish(self) -> (DocumentBuilder<'source>, DocumentContext<'source>) {
+        #[cfg(feature = "tracing")]
+        tracing::Span::current().record("nodes", self.visited_nodes);
+
+        (self.document, self.context)
+    }
+
     fn handle_node(&mut self) {
         let node = self.cursor.node();
 
         log::trace!("Found node '{}'", node.kind());
 
+        #[cfg(feature = "tracing")]
+        {
+            self.visited_nodes += 1;
+        }
+
+        #[cfg(feature = "async")]
+        if let Some(ref token) = self.frontend.cancellation {
+            if token.is_cancelled() {
+                return;
+            }
+        }
+
         match node.kind() {
             "document" => self.handle_document(),
             "heading1" => self.handle_heading(1),
@@ -190,12 +942,17 @@ where
             "ranged_tag" => self.handle_ranged_tag(),
             "ranged_verbatim_tag" => self.handle_verbatim(),
             "generic_list" => self.handle_lists(),
+            "strong_carryover_set" | "weak_carryover_set" => self.handle_carryover_set(),
+            "infirm_tag" => self.handle_infirm_tag(),
 
             "definition_list" => self.handle_definition_list(),
+            "footnote_list" => self.handle_footnote_list(),
             "table" => self.handle_table(),
-            kind => {
-                log::error!("Unknown node: {:?}", kind)
-            }
+            "horizontal_line" => self.document.add_block(Block::HorizontalRule),
+            // The delimiters only end the heading (or all the headings) before them, since the
+            // headings aren't nested in the output they only separate the paragraphs
+            "weak_paragraph_delimiter" | "strong_paragraph_delimiter" => {}
+            kind => self.error(format!("Unknown node: {:?}", kind)),
         }
     }
 
@@ -223,23 +980,125 @@ where
     fn handle_document(&mut self) {
         log::debug!("Parsing document");
 
-        self.visit_children(Self::handle_node);
+        let Some(ref path) = self.config.section_filter else {
+            self.visit_children(Self::handle_mapped_node);
+            return;
+        };
+
+        let mut found = false;
+        self.visit_children(|this| {
+            let node = this.cursor.node();
+
+            if node.kind() == "ranged_verbatim_tag" && this.is_document_meta(&node) {
+                this.handle_mapped_node();
+            } else if node.kind().starts_with("heading") {
+                found |= this.handle_filtered_heading(path);
+            }
+        });
+
+        if !found {
+            self.error(format!("No heading matches the section {}", path.join("/")));
+        }
+    }
+
+    /// Returns whether the verbatim tag is the document's metadata.
+    fn is_document_meta(&self, node: &tree_sitter::Node) -> bool {
+        let mut cursor = node.walk();
+        let name = node
+            .children(&mut cursor)
+            .find(|child| child.kind() == "tag_name");
+
+        name.is_some_and(|name| &self.source[name.byte_range()] == "document.meta")
+    }
+
+    /// Handles the heading under the cursor only if it matches `path` (see
+    /// [`Config::section_filter`]), or the headings inside of it if it matches the first
+    /// title of the path.
+    ///
+    /// Returns whether a heading was handled.
+    fn handle_filtered_heading(&mut self, path: &[String]) -> bool {
+        let node = self.cursor.node();
+        let (Some((title, rest)), Some(title_node)) =
+            (path.split_first(), node.child_by_field_name("title"))
+        else {
+            return false;
+        };
+
+        let text = self.source[title_node.byte_range()]
+            .split_whitespace()
+            .collect::<Vec<_>>()
+            .join(" ");
+        if text.to_lowercase() != title.to_lowercase() {
+            return false;
+        }
+
+        if rest.is_empty() {
+            self.handle_mapped_node();
+            return true;
+        }
+
+        let mut found = false;
+        self.visit_children(|this| {
+            if this.cursor.field_id() == this.field_ids.content
+                && this.cursor.node().kind().starts_with("heading")
+            {
+                found |= this.handle_filtered_heading(rest);
+            }
+        });
+        found
+    }
+
+    /// Handles a node recording the source range of the blocks it adds to the root scope (that
+    /// don't have a more specific range).
+    fn handle_mapped_node(&mut self) {
+        let start = self.document.root_len();
+
+        self.handle_node();
+
+        let source = SourceRange::from_node(&self.cursor.node());
+        self.document.set_root_sources(start, source);
     }
 
     fn handle_heading(&mut self, level: i32) {
         log::debug!("Parsing heading (level: {})", level);
 
+        if level == 1 {
+            self.section = Some(self.section.map_or(0, |idx| idx + 1));
+        }
+
+        let parent = self.heading;
+        // The tasks added before the title belong to the heading itself
+        let first_task = self.context.tasks.len();
+
         self.visit_children(|this| {
             if this.cursor.field_id() == this.field_ids.content {
-                this.handle_node();
+                this.handle_mapped_node();
             } else if this.cursor.field_id() == this.field_ids.title {
                 let node = this.cursor.node();
                 let mut inlines = this.document.take_inlines_collector();
+                let mut title = Vec::new();
+
+                this.handle_segment(&mut title);
+                trim_spaces(&mut title);
+
+                // The identifier is generated from the cleaned title instead of the source text
+                // so that it doesn't include modifiers like `~` and the indentation of
+                // continuation lines.
+                let mut title_text = String::new();
+                for inline in &title {
+                    inline.push_text(&mut title_text);
+                }
+                let title_text = title_text.split_whitespace().collect::<Vec<_>>().join(" ");
 
-                this.handle_segment(&mut inlines);
+                inlines.append(&mut title);
 
                 let text = &this.source[node.start_byte()..node.end_byte()];
-                let identifier = this.frontend.generate_id(text);
+                this.context.define(
+                    DefinitionKind::Heading(level),
+                    text.to_string(),
+                    node.byte_range(),
+                );
+                let identifier = this.frontend.generate_id(&title_text, this.config);
                 let url = format!("#{}", identifier);
                 let attr = Attr {
                     identifier,
@@ -249,11 +1108,27 @@ where
                 this.context
                     .add_document_link(text, DocumentLinkType::Heading(level), url);
 
+                let heading = this.context.headings.len();
+                this.context.headings.push(Heading {
+                    title: title_text,
+                    id: attr.identifier.clone(),
+                    level,
+                });
+                this.heading = Some(heading);
+                for task in &mut this.context.tasks[first_task..] {
+                    task.heading = Some(heading);
+                }
+
+                let start = this.document.root_len();
                 this.document.add_block(Block::Header(level, attr, inlines));
+                this.document
+                    .set_root_sources(start, SourceRange::from_node(&node));
             } else if this.cursor.field_id() == this.field_ids.state {
-                this.handle_detached_ext();
+                this.handle_detached_ext(true);
             }
         });
+
+        self.heading = parent;
     }
 
     fn handle_quote(&mut self) {
@@ -279,9 +1154,22 @@ where
         self.visit_children(|this| {
             this.handle_segment(&mut segment);
 
+            // Lines with only removed inline comments don't add an empty segment
+            if segment
+                .iter()
+                .all(|inline| matches!(inline, ir::Inline::Space))
+            {
+                segment.clear();
+            }
+
             if !segment.is_empty() {
                 let mut new_segment = Vec::new();
                 std::mem::swap(&mut segment, &mut new_segment);
+
+                if this.config.wiki_links.is_some() {
+                    new_segment = inlines::convert_wiki_links(new_segment);
+                }
+
                 segments.push(new_segment);
             }
         });
@@ -291,3 +1179,106 @@ where
         }
     }
 }
+
+/// Removes the leading and trailing spaces from a list of inlines.
+pub(crate) fn trim_spaces(inlines: &mut Vec<ir::Inline>) {
+    while let Some(ir::Inline::Space) = inlines.last() {
+        inlines.pop();
+    }
+
+    let leading = inlines
+        .iter()
+        .take_while(|inline| matches!(inline, ir::Inline::Space))
+        .count();
+    inlines.drain(..leading);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{base_id, Config, DuplicateIds, FrontendState, IdCharacters, IdStyle};
+
+    #[test]
+    fn duplicate_ids() {
+        let mut state = FrontendState::default();
+        let config = Config::default();
+
+        assert_eq!(state.generate_id("A heading", &config), "A-heading");
+        assert_eq!(state.generate_id("A heading", &config), "A-heading-1");
+        // Collides with the identifier of the next duplicate
+        assert_eq!(state.generate_id("A heading 2", &config), "A-heading-2");
+        assert_eq!(state.generate_id("A heading", &config), "A-heading-3");
+        assert_eq!(state.generate_id("A heading 1", &config), "A-heading-1-1");
+
+        let config = Config {
+            duplicate_ids: DuplicateIds {
+                format: String::from("{id}_{n}"),
+                start: 0,
+            },
+            ..Default::default()
+        };
+        assert_eq!(state.generate_id("Other", &config), "Other");
+        assert_eq!(state.generate_id("Other", &config), "Other_0");
+    }
+
+    #[test]
+    fn id_styles() {
+        assert_eq!(
+            base_id("My Heading!", IdStyle::Raw, IdCharacters::Unicode),
+            "My-Heading!"
+        );
+        assert_eq!(
+            base_id("My Heading!", IdStyle::Pandoc, IdCharacters::Unicode),
+            "my-heading"
+        );
+        assert_eq!(
+            base_id("My Heading!", IdStyle::GitHub, IdCharacters::Unicode),
+            "my-heading"
+        );
+
+        assert_eq!(
+            base_id(
+                "1. Über  the_top-v1.2",
+                IdStyle::Pandoc,
+                IdCharacters::Unicode
+            ),
+            "über-the_top-v1.2"
+        );
+        assert_eq!(
+            base_id(
+                "1. Über  the_top-v1.2",
+                IdStyle::GitHub,
+                IdCharacters::Unicode
+            ),
+            "1-über--the_top-v12"
+        );
+
+        assert_eq!(
+            base_id("123", IdStyle::Pandoc, IdCharacters::Unicode),
+            "section"
+        );
+        assert_eq!(
+            base_id("???", IdStyle::GitHub, IdCharacters::Unicode),
+            "section"
+        );
+    }
+
+    #[test]
+    fn id_characters() {
+        assert_eq!(
+            base_id("a/b #c?\td~e", IdStyle::Raw, IdCharacters::Unicode),
+            "ab-c-d-e"
+        );
+        assert_eq!(
+            base_id("Über straße", IdStyle::Raw, IdCharacters::Ascii),
+            "Uber-strasse"
+        );
+        assert_eq!(
+            base_id("Łódź — 東京", IdStyle::Pandoc, IdCharacters::Ascii),
+            "lodz--"
+        );
+        assert_eq!(
+            base_id("東京", IdStyle::GitHub, IdCharacters::Ascii),
+            "section"
+        );
+    }
+}
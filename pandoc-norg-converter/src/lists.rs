@@ -1,5 +1,116 @@
+use pandoc_types::definition::{Attr, ListAttributes, ListNumberDelim, ListNumberStyle};
+
 use crate::ir::{Block, ListEntry};
 use crate::Builder;
+#[cfg(feature = "serde")]
+use serde::Deserialize;
+
+/// The style of the numbers of ordered lists.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "kebab-case"))]
+pub enum NumberingStyle {
+    /// The default style of the output format (default).
+    #[default]
+    Default,
+    /// `1, 2, 3`
+    Decimal,
+    /// `a, b, c`
+    LowerAlpha,
+    /// `A, B, C`
+    UpperAlpha,
+    /// `i, ii, iii`
+    LowerRoman,
+    /// `I, II, III`
+    UpperRoman,
+}
+
+/// The delimiter after the numbers of ordered lists.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "kebab-case"))]
+pub enum NumberingDelimiter {
+    /// The default delimiter of the output format (default).
+    #[default]
+    Default,
+    /// `1.`
+    Period,
+    /// `1)`
+    Paren,
+    /// `(1)`
+    Parens,
+}
+
+/// Defines the default numbering of ordered lists.
+///
+/// The numbering of a single list can be changed with carryover tags before it's first item,
+/// `#start 3` sets the number of the first item, `#numbering lower-roman` sets the style (see
+/// [`NumberingStyle`] for the names) and `#delimiter paren` sets the delimiter (see
+/// [`NumberingDelimiter`] for the names).
+#[derive(Debug, Default)]
+#[cfg_attr(feature = "serde", derive(Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
+pub struct ListNumbering {
+    pub style: NumberingStyle,
+    pub delimiter: NumberingDelimiter,
+}
+
+impl NumberingStyle {
+    fn from_name(name: &str) -> Option<Self> {
+        Some(match name {
+            "default" => NumberingStyle::Default,
+            "decimal" => NumberingStyle::Decimal,
+            "lower-alpha" => NumberingStyle::LowerAlpha,
+            "upper-alpha" => NumberingStyle::UpperAlpha,
+            "lower-roman" => NumberingStyle::LowerRoman,
+            "upper-roman" => NumberingStyle::UpperRoman,
+            _ => return None,
+        })
+    }
+
+    fn into_pandoc(self) -> ListNumberStyle {
+        match self {
+            NumberingStyle::Default => ListNumberStyle::DefaultStyle,
+            NumberingStyle::Decimal => ListNumberStyle::Decimal,
+            NumberingStyle::LowerAlpha => ListNumberStyle::LowerAlpha,
+            NumberingStyle::UpperAlpha => ListNumberStyle::UpperAlpha,
+            NumberingStyle::LowerRoman => ListNumberStyle::LowerRoman,
+            NumberingStyle::UpperRoman => ListNumberStyle::UpperRoman,
+        }
+    }
+}
+
+impl NumberingDelimiter {
+    fn from_name(name: &str) -> Option<Self> {
+        Some(match name {
+            "default" => NumberingDelimiter::Default,
+            "period" => NumberingDelimiter::Period,
+            "paren" => NumberingDelimiter::Paren,
+            "parens" => NumberingDelimiter::Parens,
+            _ => return None,
+        })
+    }
+
+    fn into_pandoc(self) -> ListNumberDelim {
+        match self {
+            NumberingDelimiter::Default => ListNumberDelim::DefaultDelim,
+            NumberingDelimiter::Period => ListNumberDelim::Period,
+            NumberingDelimiter::Paren => ListNumberDelim::OneParen,
+            NumberingDelimiter::Parens => ListNumberDelim::TwoParens,
+        }
+    }
+}
+
+impl ListNumbering {
+    /// Returns the default list attributes.
+    fn attributes(&self) -> ListAttributes {
+        ListAttributes {
+            start_number: 1,
+            style: self.style.into_pandoc(),
+            delim: self.delimiter.into_pandoc(),
+        }
+    }
+}
 
 /// The list type currently being processed.
 #[derive(PartialEq, Clone, Copy)]
@@ -45,8 +156,18 @@ where
             return;
         }
 
+        // The pending carryover tags apply to the whole list (and the numbering ones to it's
+        // first level), the items only take the tags written inside of them
+        let (numbering, list_tags) = std::mem::take(&mut self.carryover_tags)
+            .into_iter()
+            .partition(|tag| is_numbering_tag(tag.name));
+        self.carryover_tags = numbering;
+
+        self.document.push_scope();
+        let mut set_depth = 0;
+
         loop {
-            let res = self.build_lists_level(0);
+            let res = self.build_lists_level(0, &mut set_depth);
             self.document.add_block(res.block);
 
             if let ExitCondition::EndOfNodes = res.exit {
@@ -55,17 +176,73 @@ where
         }
 
         self.cursor.goto_parent();
+
+        let blocks = self.document.pop_scope();
+        self.carryover_tags = list_tags;
+
+        // The attributes are attached to a `Div` since pandoc lists can't have attributes
+        let attr = self.take_carryover_attr();
+        if attr != Attr::default() {
+            self.document.add_block(Block::Div(attr, blocks));
+        } else {
+            for block in blocks {
+                self.document.add_block(block);
+            }
+        }
     }
 
-    fn build_lists_level(&mut self, level: usize) -> BuildListsResult<'source> {
+    /// Moves the cursor to the next list node, leaving any carryover set that was entered to
+    /// reach the current node.
+    ///
+    /// `set_depth` is the number of carryover sets the cursor is currently inside of.
+    fn goto_next_list_node(&mut self, set_depth: &mut usize) -> bool {
+        loop {
+            if self.cursor.goto_next_sibling() {
+                return true;
+            }
+
+            if *set_depth == 0 {
+                return false;
+            }
+
+            self.cursor.goto_parent();
+            *set_depth -= 1;
+        }
+    }
+
+    fn build_lists_level(
+        &mut self,
+        level: usize,
+        set_depth: &mut usize,
+    ) -> BuildListsResult<'source> {
         let mut entries = Vec::new();
         let mut exit = ExitCondition::EndOfNodes;
         let mut list_type = ListType::Unknown;
+        let mut attributes = self.config.list_numbering.attributes();
 
         loop {
             let node = self.cursor.node();
 
             let (new_level, new_type) = match node.kind() {
+                "strong_carryover" | "weak_carryover" => {
+                    self.handle_carryover_tag();
+                    if !self.goto_next_list_node(set_depth) {
+                        break;
+                    } else {
+                        continue;
+                    }
+                }
+                // The carryover tags are stored and the list item they apply to is processed as
+                // if it was a sibling of the other items
+                "strong_carryover_set" | "weak_carryover_set" => {
+                    if self.cursor.goto_first_child() {
+                        *set_depth += 1;
+                    } else if !self.goto_next_list_node(set_depth) {
+                        break;
+                    }
+                    continue;
+                }
+
                 "unordered_list1" => (0, ListType::Unordered),
                 "unordered_list2" => (1, ListType::Unordered),
                 "unordered_list3" => (2, ListType::Unordered),
@@ -81,8 +258,8 @@ where
                 "ordered_list6" => (5, ListType::Ordered),
 
                 kind => {
-                    log::error!("(lists) unknown node: {:?}", kind);
-                    if !self.cursor.goto_next_sibling() {
+                    self.error(format!("(lists) unknown node: {:?}", kind));
+                    if !self.goto_next_list_node(set_depth) {
                         break;
                     } else {
                         continue;
@@ -101,11 +278,13 @@ where
 
             match level.cmp(&new_level) {
                 std::cmp::Ordering::Less => {
-                    let res = self.build_lists_level(new_level);
+                    let res = self.build_lists_level(new_level, set_depth);
                     let mut list = res.block;
                     let diff = new_level - level;
                     for _ in 1..diff {
-                        list = list_from_type(res.list_type, vec![ListEntry { blocks: vec![list] }])
+                        let entries = vec![ListEntry { blocks: vec![list] }];
+                        let attributes = self.config.list_numbering.attributes();
+                        list = list_from_type(res.list_type, entries, attributes)
                     }
                     entries.push(ListEntry { blocks: vec![list] });
 
@@ -113,25 +292,64 @@ where
                         continue;
                     }
                 }
-                std::cmp::Ordering::Equal => entries.push(self.handle_list_content(level)),
+                std::cmp::Ordering::Equal => {
+                    if entries.is_empty() {
+                        self.take_list_numbering(&mut attributes);
+                    }
+                    entries.push(self.handle_list_content(level))
+                }
                 std::cmp::Ordering::Greater => {
                     exit = ExitCondition::LevelIsHigher;
                     break;
                 }
             };
 
-            if !self.cursor.goto_next_sibling() {
+            if !self.goto_next_list_node(set_depth) {
                 break;
             }
         }
 
         BuildListsResult {
-            block: list_from_type(list_type, entries),
+            block: list_from_type(list_type, entries, attributes),
             list_type,
             exit,
         }
     }
 
+    /// Applies the pending list numbering carryover tags (see [`ListNumbering`]) to `attributes`
+    /// and removes them.
+    fn take_list_numbering(&mut self, attributes: &mut ListAttributes) {
+        let mut tags = std::mem::take(&mut self.carryover_tags);
+
+        tags.retain(|tag| {
+            let parameter = tag.parameters.first().map(|p| p.as_ref());
+            let valid = match (tag.name, parameter) {
+                ("start", Some(start)) => start
+                    .parse()
+                    .map(|start| attributes.start_number = start)
+                    .is_ok(),
+                ("numbering", Some(style)) => NumberingStyle::from_name(style)
+                    .map(|style| attributes.style = style.into_pandoc())
+                    .is_some(),
+                ("delimiter", Some(delimiter)) => NumberingDelimiter::from_name(delimiter)
+                    .map(|delimiter| attributes.delim = delimiter.into_pandoc())
+                    .is_some(),
+                (name, _) if is_numbering_tag(name) => false,
+                _ => return true,
+            };
+
+            if !valid {
+                self.warn(format!(
+                    "Invalid list numbering tag: {} {:?}",
+                    tag.name, parameter
+                ));
+            }
+            false
+        });
+
+        self.carryover_tags = tags;
+    }
+
     fn handle_list_content(&mut self, level: usize) -> ListEntry<'source> {
         self.document.push_scope();
 
@@ -156,29 +374,42 @@ where
                 "unordered_list1" | "unordered_list2" | "unordered_list3" | "unordered_list4"
                 | "unordered_list5" | "unordered_list6" | "ordered_list1" | "ordered_list2"
                 | "ordered_list3" | "ordered_list4" | "ordered_list5" | "ordered_list6" => {
-                    let block = this.build_lists_level(level + 1).block;
+                    let block = this.build_lists_level(level + 1, &mut 0).block;
                     this.document.add_block(block);
                 }
 
                 "paragraph" => this.handle_paragraph(),
+                "ranged_tag" | "ranged_verbatim_tag" => this.handle_node(),
 
-                "detached_modifier_extension" => this.handle_detached_ext(),
+                "detached_modifier_extension" => this.handle_detached_ext(false),
+                "strong_carryover" | "weak_carryover" => this.handle_carryover_tag(),
 
-                kind => log::error!("(lists) unknown node: {:?}", kind),
+                kind => this.error(format!("(lists) unknown node: {:?}", kind)),
             }
         });
 
-        ListEntry {
-            blocks: self.document.pop_scope(),
+        let mut blocks = self.document.pop_scope();
+
+        // The attributes are attached to a `Div` since pandoc list items can't have attributes
+        let attr = self.take_carryover_attr();
+        if attr != Attr::default() {
+            blocks = vec![Block::Div(attr, blocks)];
         }
+
+        ListEntry { blocks }
     }
 }
 
+/// Returns whether the carryover tag sets the numbering of a list, see [`ListNumbering`].
+fn is_numbering_tag(name: &str) -> bool {
+    matches!(name, "start" | "numbering" | "delimiter")
+}
+
 /// Constructs a list block from a set of items and the list type.
-fn list_from_type(list_type: ListType, items: Vec<ListEntry>) -> Block {
+fn list_from_type(list_type: ListType, items: Vec<ListEntry>, attributes: ListAttributes) -> Block {
     match list_type {
         ListType::Unknown => Block::Null,
-        ListType::Ordered => Block::OrderedList(items),
+        ListType::Ordered => Block::OrderedList(attributes, items),
         ListType::Unordered => Block::BulletList(items),
     }
 }
@@ -0,0 +1,471 @@
+//! A built-in markdown writer, to preview documents without pandoc
+//!
+//! The converted document is written as pandoc markdown that is also valid CommonMark for the
+//! common elements (headings, paragraphs, lists, code blocks, quotes, links and images). Pandoc
+//! specific syntax is only used when CommonMark has no equivalent: heading identifiers
+//! (`{#id}`), pipe tables, footnotes, definition lists and math. The attributes of other elements
+//! (and `Div`s and `Span`s) are dropped.
+
+use pandoc_types::definition::{
+    Alignment, Block, Format, Inline, ListAttributes, ListNumberDelim, ListNumberStyle, MathType,
+    Pandoc, QuoteType, Row, Target,
+};
+
+/// Returns the markdown of a converted document (without the metadata), written with the
+/// built-in writer used by [`Frontend::convert_to_markdown`].
+///
+/// [`Frontend::convert_to_markdown`]: crate::Frontend::convert_to_markdown
+pub fn write_markdown(document: &Pandoc) -> String {
+    let mut writer = Writer::default();
+    let mut out = writer.blocks(&document.blocks);
+
+    // The notes can contain other notes, which are added while writing them
+    let mut idx = 0;
+    while idx < writer.notes.len() {
+        let blocks = std::mem::take(&mut writer.notes[idx]);
+        let content = writer.blocks(&blocks);
+        out.push_str(&format!(
+            "\n\n[^{}]: {}",
+            idx + 1,
+            indent(&content, "    ", false)
+        ));
+        idx += 1;
+    }
+
+    out.push('\n');
+    out
+}
+
+#[derive(Default)]
+struct Writer {
+    /// The content of the footnotes, in the order they are referenced.
+    notes: Vec<Vec<Block>>,
+}
+
+impl Writer {
+    /// Writes the blocks separated by empty lines.
+    fn blocks(&mut self, blocks: &[Block]) -> String {
+        self.blocks_with(blocks, "\n\n")
+    }
+
+    /// Writes the blocks separated by `separator`.
+    fn blocks_with(&mut self, blocks: &[Block], separator: &str) -> String {
+        let blocks: Vec<_> = blocks
+            .iter()
+            .map(|block| self.block(block))
+            .filter(|text| !text.is_empty())
+            .collect();
+        blocks.join(separator)
+    }
+
+    fn block(&mut self, block: &Block) -> String {
+        match block {
+            Block::Plain(inlines) | Block::Para(inlines) => self.inlines(inlines),
+            Block::LineBlock(lines) => lines
+                .iter()
+                .map(|line| format!("| {}", self.inlines(line)))
+                .collect::<Vec<_>>()
+                .join("\n"),
+            Block::CodeBlock(attr, code) => {
+                let fence = "`".repeat(longest_run(code, '`').max(2) + 1);
+                let language = attr.classes.first().map_or("", String::as_str);
+                format!(
+                    "{fence}{language}\n{}\n{fence}",
+                    code.trim_end_matches('\n')
+                )
+            }
+            Block::RawBlock(format, text) if is_markdown_format(format) => text.clone(),
+            Block::RawBlock(..) | Block::Null => String::new(),
+            Block::BlockQuote(blocks) => indent(&self.blocks(blocks), "> ", true),
+            Block::OrderedList(attributes, items) => {
+                let markers =
+                    (attributes.start_number..).map(|number| list_marker(number, attributes));
+                self.list(items, markers)
+            }
+            Block::BulletList(items) => self.list(items, std::iter::repeat(String::from("-"))),
+            Block::DefinitionList(entries) => entries
+                .iter()
+                .map(|(term, definitions)| {
+                    let mut text = self.inlines(term);
+                    for definition in definitions {
+                        let definition = self.blocks(definition);
+                        text.push_str(&format!("\n\n:   {}", indent(&definition, "    ", false)));
+                    }
+                    text
+                })
+                .collect::<Vec<_>>()
+                .join("\n\n"),
+            Block::Header(level, attr, inlines) => {
+                let mut text = format!("{} {}", "#".repeat(*level as usize), self.inlines(inlines));
+                if !attr.identifier.is_empty() {
+                    text.push_str(&format!(" {{#{}}}", attr.identifier));
+                }
+                text
+            }
+            Block::HorizontalRule => String::from("---"),
+            Block::Table(table) => {
+                let columns = table.colspecs.len();
+                let head = table.head.rows.first();
+                let body = table.bodies.iter().flat_map(|body| &body.body);
+
+                let mut lines = vec![self.row(head, columns)];
+                let alignments = table.colspecs.iter().map(|spec| match spec.0 {
+                    Alignment::AlignLeft => ":--",
+                    Alignment::AlignRight => "--:",
+                    Alignment::AlignCenter => ":-:",
+                    Alignment::AlignDefault => "---",
+                });
+                lines.push(format!("|{}|", alignments.collect::<Vec<_>>().join("|")));
+                lines.extend(body.map(|row| self.row(Some(row), columns)));
+
+                let caption = self.blocks(&table.caption.long);
+                if !caption.is_empty() {
+                    lines.push(format!("\nTable: {}", caption.replace('\n', " ")));
+                }
+                lines.join("\n")
+            }
+            Block::Figure(_, _, blocks) | Block::Div(_, blocks) => self.blocks(blocks),
+        }
+    }
+
+    /// Writes the items of a list, each one starts with the next marker and the following lines
+    /// are indented to the content of the first line.
+    fn list(&mut self, items: &[Vec<Block>], markers: impl Iterator<Item = String>) -> String {
+        // Lists are tight unless an item has multiple paragraphs, nested lists after the text
+        // of an item (a `Plain`) don't make it loose
+        let loose = items.iter().any(|item| {
+            let paragraphs = item.iter().filter(|block| matches!(block, Block::Para(_)));
+            paragraphs.count() > 1
+        });
+        let separator = match loose {
+            true => "\n\n",
+            false => "\n",
+        };
+
+        let items: Vec<_> = items
+            .iter()
+            .zip(markers)
+            .map(|(item, marker)| {
+                let content = self.blocks_with(item, separator);
+                let padding = " ".repeat(marker.len() + 1);
+                format!("{marker} {}", indent(&content, &padding, false))
+            })
+            .collect();
+
+        items.join(separator)
+    }
+
+    /// Writes a row of a pipe table, `row` is `None` for the empty header of tables without one.
+    fn row(&mut self, row: Option<&Row>, columns: usize) -> String {
+        let mut cells: Vec<_> = row
+            .into_iter()
+            .flat_map(|row| &row.cells)
+            .map(|cell| self.blocks(&cell.content).replace('\n', " "))
+            .collect();
+        cells.resize(columns.max(cells.len()), String::new());
+
+        format!("| {} |", cells.join(" | "))
+    }
+
+    fn inlines(&mut self, inlines: &[Inline]) -> String {
+        let mut out = String::new();
+
+        for inline in inlines {
+            match inline {
+                Inline::Str(text) => out.push_str(&escape(text)),
+                Inline::Emph(inlines) => out.push_str(&format!("*{}*", self.inlines(inlines))),
+                Inline::Strong(inlines) => out.push_str(&format!("**{}**", self.inlines(inlines))),
+                Inline::Strikeout(inlines) => {
+                    out.push_str(&format!("~~{}~~", self.inlines(inlines)))
+                }
+                Inline::Superscript(inlines) => {
+                    out.push_str(&format!("^{}^", self.inlines(inlines)))
+                }
+                Inline::Subscript(inlines) => out.push_str(&format!("~{}~", self.inlines(inlines))),
+                Inline::Quoted(QuoteType::SingleQuote, inlines) => {
+                    out.push_str(&format!("‘{}’", self.inlines(inlines)))
+                }
+                Inline::Quoted(QuoteType::DoubleQuote, inlines) => {
+                    out.push_str(&format!("“{}”", self.inlines(inlines)))
+                }
+                Inline::Underline(inlines)
+                | Inline::SmallCaps(inlines)
+                | Inline::Cite(_, inlines)
+                | Inline::Span(_, inlines) => out.push_str(&self.inlines(inlines)),
+                Inline::Code(_, code) => {
+                    let fence = "`".repeat(longest_run(code, '`') + 1);
+                    // Code starting or ending with a backtick needs a space after the fence
+                    match code.starts_with('`') || code.ends_with('`') {
+                        true => out.push_str(&format!("{fence} {code} {fence}")),
+                        false => out.push_str(&format!("{fence}{code}{fence}")),
+                    }
+                }
+                Inline::Space => out.push(' '),
+                Inline::SoftBreak => out.push('\n'),
+                Inline::LineBreak => out.push_str("\\\n"),
+                Inline::Math(MathType::InlineMath, math) => out.push_str(&format!("${math}$")),
+                Inline::Math(MathType::DisplayMath, math) => out.push_str(&format!("$${math}$$")),
+                Inline::RawInline(format, text) if is_markdown_format(format) => out.push_str(text),
+                Inline::RawInline(..) => {}
+                Inline::Link(_, inlines, target) => out.push_str(&format!(
+                    "[{}]{}",
+                    self.inlines(inlines),
+                    destination(target)
+                )),
+                Inline::Image(_, inlines, target) => out.push_str(&format!(
+                    "![{}]{}",
+                    self.inlines(inlines),
+                    destination(target)
+                )),
+                Inline::Note(blocks) => {
+                    self.notes.push(blocks.clone());
+                    out.push_str(&format!("[^{}]", self.notes.len()));
+                }
+            }
+        }
+
+        out
+    }
+}
+
+/// Returns the marker of the item of an ordered list with the given number.
+///
+/// Only decimal numbers followed by a period or a parenthesis are valid CommonMark, the other
+/// styles and delimiters use pandoc's `fancy_lists` syntax.
+fn list_marker(number: i32, attributes: &ListAttributes) -> String {
+    let number = match attributes.style {
+        ListNumberStyle::LowerAlpha => alpha_number(number),
+        ListNumberStyle::UpperAlpha => alpha_number(number).to_uppercase(),
+        ListNumberStyle::LowerRoman => roman_number(number),
+        ListNumberStyle::UpperRoman => roman_number(number).to_uppercase(),
+        _ => number.to_string(),
+    };
+
+    match attributes.delim {
+        ListNumberDelim::OneParen => format!("{number})"),
+        ListNumberDelim::TwoParens => format!("({number})"),
+        // Pandoc requires two spaces after a capital letter and a period (so that initials like
+        // `B. Russell` don't start a list)
+        _ if attributes.style == ListNumberStyle::UpperAlpha => format!("{number}. "),
+        _ => format!("{number}."),
+    }
+}
+
+/// Returns the number as letters (`a` to `z`, then `aa`, `ab`, ...), numbers below 1 are 1.
+fn alpha_number(number: i32) -> String {
+    let mut number = number.max(1) as u32;
+    let mut letters = Vec::new();
+
+    while number > 0 {
+        number -= 1;
+        letters.push(char::from(b'a' + (number % 26) as u8));
+        number /= 26;
+    }
+
+    letters.iter().rev().collect()
+}
+
+/// Returns the number as lowercase roman numerals, numbers below 1 are 1.
+fn roman_number(number: i32) -> String {
+    const NUMERALS: [(i32, &str); 13] = [
+        (1000, "m"),
+        (900, "cm"),
+        (500, "d"),
+        (400, "cd"),
+        (100, "c"),
+        (90, "xc"),
+        (50, "l"),
+        (40, "xl"),
+        (10, "x"),
+        (9, "ix"),
+        (5, "v"),
+        (4, "iv"),
+        (1, "i"),
+    ];
+
+    let mut number = number.max(1);
+    let mut roman = String::new();
+
+    for (value, numeral) in NUMERALS {
+        while number >= value {
+            roman.push_str(numeral);
+            number -= value;
+        }
+    }
+
+    roman
+}
+
+/// Returns whether raw content in the format can be written as is.
+fn is_markdown_format(format: &Format) -> bool {
+    matches!(
+        format.0.as_str(),
+        "markdown" | "commonmark" | "gfm" | "html"
+    )
+}
+
+/// Returns the destination (and title) of a link or image, between parentheses.
+fn destination(target: &Target) -> String {
+    let url = match target.url.contains([' ', '(', ')']) {
+        true => format!("<{}>", target.url),
+        false => target.url.clone(),
+    };
+
+    match target.title.is_empty() {
+        true => format!("({url})"),
+        false => format!("({url} \"{}\")", target.title.replace('"', "\\\"")),
+    }
+}
+
+/// Escapes the characters with a special meaning in markdown.
+fn escape(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+
+    for char in text.chars() {
+        if matches!(
+            char,
+            '\\' | '*' | '_' | '`' | '[' | ']' | '<' | '>' | '#' | '~' | '^' | '$' | '|'
+        ) {
+            out.push('\\');
+        }
+        out.push(char);
+    }
+
+    out
+}
+
+/// Returns the length of the longest run of `char` in `text`.
+fn longest_run(text: &str, char: char) -> usize {
+    text.split(|c| c != char)
+        .map(str::len)
+        .max()
+        .unwrap_or_default()
+}
+
+/// Adds `prefix` to the lines of the text (except the first one if `first` is false), empty lines
+/// only get the prefix without trailing whitespace.
+fn indent(text: &str, prefix: &str, first: bool) -> String {
+    text.lines()
+        .enumerate()
+        .map(|(idx, line)| match (idx, line.is_empty()) {
+            (0, _) if !first => line.to_string(),
+            (_, true) => prefix.trim_end().to_string(),
+            _ => format!("{prefix}{line}"),
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{escape, indent, list_marker, longest_run, write_markdown};
+    use pandoc_types::definition::{
+        Attr, Block, Inline, ListAttributes, ListNumberDelim, ListNumberStyle, Pandoc, Target,
+    };
+
+    fn text(text: &str) -> Vec<Inline> {
+        let mut inlines = Vec::new();
+        for word in text.split(' ') {
+            if !inlines.is_empty() {
+                inlines.push(Inline::Space);
+            }
+            inlines.push(Inline::Str(word.to_string()));
+        }
+        inlines
+    }
+
+    #[test]
+    fn helpers() {
+        assert_eq!(escape("a *b* [c]"), r"a \*b\* \[c\]");
+        assert_eq!(longest_run("a `` b ```", '`'), 3);
+        assert_eq!(indent("a\n\nb", "> ", true), "> a\n>\n> b");
+        assert_eq!(indent("a\nb", "  ", false), "a\n  b");
+    }
+
+    #[test]
+    fn list_markers() {
+        let attributes = |style, delim| ListAttributes {
+            start_number: 1,
+            style,
+            delim,
+        };
+
+        let decimal = attributes(ListNumberStyle::Decimal, ListNumberDelim::Period);
+        assert_eq!(list_marker(12, &decimal), "12.");
+        let alpha = attributes(ListNumberStyle::LowerAlpha, ListNumberDelim::OneParen);
+        assert_eq!(list_marker(28, &alpha), "ab)");
+        let alpha = attributes(ListNumberStyle::UpperAlpha, ListNumberDelim::Period);
+        assert_eq!(list_marker(3, &alpha), "C. ");
+        let roman = attributes(ListNumberStyle::UpperRoman, ListNumberDelim::TwoParens);
+        assert_eq!(list_marker(1994, &roman), "(MCMXCIV)");
+    }
+
+    #[test]
+    fn document() {
+        let heading = Attr {
+            identifier: String::from("title"),
+            ..Default::default()
+        };
+        let link = Inline::Link(
+            Attr::default(),
+            text("a link"),
+            Target {
+                url: String::from("https://example.com"),
+                title: String::new(),
+            },
+        );
+        let code = Attr {
+            classes: vec![String::from("rust")],
+            ..Default::default()
+        };
+
+        let document = Pandoc {
+            meta: Default::default(),
+            blocks: vec![
+                Block::Header(1, heading, text("Title")),
+                Block::Para(vec![
+                    Inline::Strong(text("Bold")),
+                    Inline::Space,
+                    link,
+                    Inline::Note(vec![Block::Para(text("A note"))]),
+                ]),
+                Block::OrderedList(
+                    ListAttributes {
+                        delim: ListNumberDelim::OneParen,
+                        ..Default::default()
+                    },
+                    vec![
+                        vec![Block::Plain(text("One"))],
+                        vec![
+                            Block::Plain(text("Two")),
+                            Block::BulletList(vec![vec![Block::Plain(text("Nested"))]]),
+                        ],
+                    ],
+                ),
+                Block::BlockQuote(vec![Block::Para(text("A")), Block::Para(text("quote"))]),
+                Block::CodeBlock(code, String::from("fn main() {}\n")),
+            ],
+        };
+
+        assert_eq!(
+            write_markdown(&document),
+            "# Title {#title}\n\
+             \n\
+             **Bold** [a link](https://example.com)[^1]\n\
+             \n\
+             1) One\n\
+             2) Two\n   \
+                - Nested\n\
+             \n\
+             > A\n\
+             >\n\
+             > quote\n\
+             \n\
+             ```rust\n\
+             fn main() {}\n\
+             ```\n\
+             \n\
+             [^1]: A note\n"
+        );
+    }
+}
@@ -1,15 +1,71 @@
+use crate::ir::Block;
+use crate::timestamps::Timestamp;
 use crate::Builder;
-use pandoc_types::definition::MetaValue;
+use pandoc_types::definition::{
+    Attr, Block as PandocBlock, Inline as PandocInline, MetaValue, Target,
+};
+#[cfg(feature = "serde")]
+use serde::Deserialize;
+use std::borrow::Cow;
 use std::collections::HashMap;
 
+/// Configuration of the links to the categories of a document (the `categories` metadata field).
+///
+/// When enabled through [`Config::category_links`] a `Div` with the `categories` class
+/// containing a link to each category is added where the document's metadata is defined.
+///
+/// [`Config::category_links`]: crate::Config::category_links
+#[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
+pub struct CategoryLinks {
+    /// Template used to build the url of each category, `{category}` is replaced with the
+    /// category's name (default: `categories/{category}.html`).
+    pub path_template: String,
+}
+
+impl Default for CategoryLinks {
+    fn default() -> Self {
+        Self {
+            path_template: String::from("categories/{category}.html"),
+        }
+    }
+}
+
+impl CategoryLinks {
+    /// Returns the `Div` with the links to the categories.
+    pub(crate) fn links_div(&self, categories: Vec<String>) -> PandocBlock {
+        let mut inlines = Vec::new();
+
+        for category in categories {
+            if !inlines.is_empty() {
+                inlines.push(PandocInline::Space);
+            }
+
+            let target = Target {
+                url: self.path_template.replace("{category}", &category),
+                title: String::new(),
+            };
+            let text = vec![PandocInline::Str(category)];
+            inlines.push(PandocInline::Link(Attr::default(), text, target));
+        }
+
+        let attr = Attr {
+            classes: vec![String::from("categories")],
+            ..Default::default()
+        };
+        PandocBlock::Div(attr, vec![PandocBlock::Plain(inlines)])
+    }
+}
+
 impl<'builder, 'source> Builder<'builder, 'source> {
-    pub(crate) fn handle_document_meta_block(&mut self, parameters: &[&str]) {
+    pub(crate) fn handle_document_meta_block(&mut self, parameters: &[Cow<'source, str>]) {
         if !parameters.is_empty() {
-            log::warn!(
+            self.warn(format!(
                 "Embed block expected 0 parameter received: {}",
                 parameters.len()
-            );
-            log::warn!("Extra parameters: {:?}", parameters);
+            ));
+            self.warn(format!("Extra parameters: {:?}", parameters));
         }
 
         let text = self
@@ -18,11 +74,95 @@ impl<'builder, 'source> Builder<'builder, 'source> {
             .utf8_text(self.source.as_bytes())
             .expect("Invalid text");
 
-        let (meta, _) = parse_object_inner(text);
+        let (mut meta, _) = parse_object_inner(text);
+        if !self.config.raw_metadata {
+            normalize_meta(&mut meta);
+        }
+
+        if self.config.category_links.is_some() {
+            let categories = categories(&meta);
+            if !categories.is_empty() {
+                self.document.add_block(Block::CategoryLinks(categories));
+            }
+        }
+
         self.document.extend_meta(meta);
     }
 }
 
+/// Returns the categories in the `categories` field of the metadata (either a list or a space
+/// separated string).
+fn categories(meta: &HashMap<String, MetaValue>) -> Vec<String> {
+    match meta.get("categories") {
+        Some(MetaValue::MetaList(list)) => list
+            .iter()
+            .filter_map(|value| match value {
+                MetaValue::MetaString(category) => Some(category.clone()),
+                _ => None,
+            })
+            .collect(),
+        Some(MetaValue::MetaString(text)) => text.split_whitespace().map(String::from).collect(),
+        _ => Vec::new(),
+    }
+}
+
+/// Maps neorg's metadata fields to the standard fields used by pandoc.
+///
+/// - `authors` is moved to `author` (as a list).
+/// - `created` and `updated` are converted to ISO 8601 if they are neorg timestamps
+///   (`Sat 1st Apr 2023`).
+/// - `updated` (or `created` if there's no `updated` field) is copied to `date`, without the time.
+///
+/// - `categories` is split into a list if it's a (space separated) string.
+///
+/// Fields already present in the metadata aren't replaced.
+fn normalize_meta(meta: &mut HashMap<String, MetaValue>) {
+    if let Some(MetaValue::MetaString(_)) = meta.get("categories") {
+        let categories = categories(meta).into_iter().map(MetaValue::MetaString);
+        meta.insert(
+            String::from("categories"),
+            MetaValue::MetaList(categories.collect()),
+        );
+    }
+
+    if !meta.contains_key("author") {
+        if let Some(authors) = meta.remove("authors") {
+            let authors = match authors {
+                MetaValue::MetaList(list) => list,
+                author => vec![author],
+            };
+            meta.insert(String::from("author"), MetaValue::MetaList(authors));
+        }
+    }
+
+    for key in ["created", "updated"] {
+        let Some(MetaValue::MetaString(text)) = meta.get(key) else {
+            continue;
+        };
+
+        // Neorg's timestamps are converted to ISO 8601, ISO dates are kept as they are
+        match Timestamp::parse(text) {
+            Some(timestamp) if !text.starts_with(&timestamp.date()) => {
+                meta.insert(key.to_string(), MetaValue::MetaString(timestamp.iso()));
+            }
+            _ => {}
+        }
+    }
+
+    if !meta.contains_key("date") {
+        let date = match (meta.get("updated"), meta.get("created")) {
+            (Some(MetaValue::MetaString(date)), _) | (None, Some(MetaValue::MetaString(date))) => {
+                Some(Timestamp::parse(date).map_or_else(|| date.clone(), |date| date.date()))
+            }
+            _ => None,
+        };
+
+        if let Some(date) = date {
+            meta.insert(String::from("date"), MetaValue::MetaString(date));
+        }
+    }
+}
+
 fn parse_object_inner(mut text: &str) -> (HashMap<String, MetaValue>, &str) {
     let mut map = HashMap::default();
 
@@ -57,8 +197,14 @@ fn parse_object_entry(text: &str) -> (String, MetaValue, &str) {
         }
     };
 
-    let rest = consume_whitespace(rest);
-    let (value, rest) = parse_value(rest);
+    let rest = rest.trim_start_matches(|c: char| c != '\n' && c.is_whitespace());
+    let next_line = consume_whitespace(rest);
+    // Values only continue on the next line if they are an object or an array, otherwise the
+    // value is empty
+    let (value, rest) = match next_line.chars().next() {
+        Some('[' | '{') => parse_value(next_line),
+        _ => parse_value(rest),
+    };
 
     (name.to_string(), value, rest)
 }
@@ -106,11 +252,25 @@ fn parse_value(text: &str) -> (MetaValue, &str) {
         }
         _ => {
             let (str, rest) = parse_string(text);
-            (MetaValue::MetaString(str.to_string()), rest)
+            (parse_scalar(str), rest)
         }
     }
 }
 
+/// Returns the typed value of a scalar, following the conventions of pandoc's yaml metadata.
+///
+/// - `true` and `false` are booleans.
+/// - Empty values, `null` and `~` are empty strings (which are falsy in templates).
+/// - Numbers are kept as strings (with their original text) since pandoc has no number values.
+fn parse_scalar(text: &str) -> MetaValue {
+    match text {
+        "true" => MetaValue::MetaBool(true),
+        "false" => MetaValue::MetaBool(false),
+        "" | "null" | "~" => MetaValue::MetaString(String::new()),
+        _ => MetaValue::MetaString(text.to_string()),
+    }
+}
+
 fn parse_string(text: &str) -> (&str, &str) {
     let (str, rest) = consume_any(text, |c| matches!(c, '[' | ']' | '{' | '}' | ':' | '\n'));
     (str.trim(), rest)
@@ -128,7 +288,7 @@ fn consume_any(text: &str, stop: impl Fn(char) -> bool) -> (&str, &str) {
 
 #[cfg(test)]
 mod test {
-    use super::parse_object_inner;
+    use super::{normalize_meta, parse_object_inner};
     use pandoc_types::definition::MetaValue;
     use std::collections::HashMap;
 
@@ -304,4 +464,106 @@ mod test {
 
         assert_eq!(meta, expected);
     }
+
+    #[test]
+    fn normalized() {
+        let input = r#"
+            authors: [
+              Jane
+              John
+            ]
+            created: 2023-05-01T10:20:30+0100
+            updated: 2024-01-02
+        "#;
+
+        let (mut meta, _) = parse_object_inner(input);
+        normalize_meta(&mut meta);
+
+        assert_eq!(
+            meta.get("author"),
+            Some(&MetaValue::MetaList(vec![
+                MetaValue::MetaString("Jane".to_string()),
+                MetaValue::MetaString("John".to_string()),
+            ]))
+        );
+        assert!(!meta.contains_key("authors"));
+        assert_eq!(
+            meta.get("date"),
+            Some(&MetaValue::MetaString("2024-01-02".to_string()))
+        );
+
+        meta.insert(
+            String::from("categories"),
+            MetaValue::MetaString(String::from("rust  neorg")),
+        );
+        normalize_meta(&mut meta);
+        assert_eq!(
+            meta.get("categories"),
+            Some(&MetaValue::MetaList(vec![
+                MetaValue::MetaString("rust".to_string()),
+                MetaValue::MetaString("neorg".to_string()),
+            ]))
+        );
+
+        meta.remove("date");
+        meta.remove("updated");
+        normalize_meta(&mut meta);
+        assert_eq!(
+            meta.get("date"),
+            Some(&MetaValue::MetaString("2023-05-01".to_string()))
+        );
+
+        meta.remove("date");
+        meta.insert(
+            String::from("updated"),
+            MetaValue::MetaString(String::from("Sat 1st Apr 2023 16:00")),
+        );
+        normalize_meta(&mut meta);
+        assert_eq!(
+            meta.get("updated"),
+            Some(&MetaValue::MetaString("2023-04-01T16:00".to_string()))
+        );
+        assert_eq!(
+            meta.get("date"),
+            Some(&MetaValue::MetaString("2023-04-01".to_string()))
+        );
+    }
+
+    #[test]
+    fn typed_values() {
+        let input = r#"
+            draft: true
+            published: false
+            version: 1.5
+            pages: 42
+            empty:
+            nothing: null
+            flags: [
+                true
+                no
+            ]
+        "#;
+
+        let (meta, _) = parse_object_inner(input);
+
+        let mut expected = HashMap::default();
+        expected.insert("draft".to_string(), MetaValue::MetaBool(true));
+        expected.insert("published".to_string(), MetaValue::MetaBool(false));
+        expected.insert(
+            "version".to_string(),
+            MetaValue::MetaString("1.5".to_string()),
+        );
+        expected.insert("pages".to_string(), MetaValue::MetaString("42".to_string()));
+        expected.insert("empty".to_string(), MetaValue::MetaString(String::new()));
+        expected.insert("nothing".to_string(), MetaValue::MetaString(String::new()));
+        expected.insert(
+            "flags".to_string(),
+            MetaValue::MetaList(vec![
+                MetaValue::MetaBool(true),
+                MetaValue::MetaString("no".to_string()),
+            ]),
+        );
+
+        assert_eq!(meta, expected);
+    }
 }
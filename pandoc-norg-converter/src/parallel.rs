@@ -0,0 +1,183 @@
+//! Parallel conversion of the top level sections of a document
+//!
+//! The children of the document node are split into groups of whole top level sections, each
+//! group is converted in it's own thread with a separate [`Builder`] and the results are merged in
+//! the document order.
+//!
+//! Since the heading identifiers depend on the identifiers generated before them, each group
+//! records the identifiers it generated and they are generated again, in order, with the
+//! `Frontend`'s state while merging. The indices of the top level sections are also counted from
+//! the start of each group and shifted while merging.
+
+use std::collections::HashMap;
+use std::ops::Range;
+use std::thread;
+
+use pandoc_types::definition::MetaValue;
+use tree_sitter::{Node, Tree};
+
+use crate::document::{DocumentBuilder, DocumentContext};
+use crate::field_ids::FieldIds;
+use crate::ir::Block;
+use crate::source_map::SourceRange;
+use crate::{Builder, Config, FrontendState};
+
+/// The result of converting a group of sections.
+struct Group<'source> {
+    blocks: Vec<(Block<'source>, Option<SourceRange>)>,
+    metadata: HashMap<String, MetaValue>,
+    context: DocumentContext<'source>,
+    /// The text used to generate each identifier and the identifier, in order.
+    generated: Vec<(String, String)>,
+    /// The number of top level sections in the group.
+    sections: usize,
+}
+
+/// Walks the document in parallel, returns the same result as walking it with a single
+/// [`Builder`].
+pub(crate) fn walk_sections<'source>(
+    source: &'source str,
+    tree: &'source Tree,
+    config: &'source Config,
+    frontend: &mut FrontendState,
+) -> (DocumentBuilder<'source>, DocumentContext<'source>) {
+    let workers = thread::available_parallelism().map_or(1, |workers| workers.get());
+    let groups = split_groups(tree, workers);
+
+    let groups: Vec<Group> = thread::scope(|scope| {
+        let handles: Vec<_> = groups
+            .into_iter()
+            .map(|nodes| {
+                let state = FrontendState {
+                    generated: Some(Vec::new()),
+                    #[cfg(feature = "async")]
+                    cancellation: frontend.cancellation.clone(),
+                    ..Default::default()
+                };
+
+                scope.spawn(move || convert_group(source, tree, config, state, nodes))
+            })
+            .collect();
+
+        handles
+            .into_iter()
+            .map(|handle| handle.join().expect("Section conversion panicked"))
+            .collect()
+    });
+
+    let mut document = DocumentBuilder::default();
+    let mut context = DocumentContext::new(config);
+    let mut sections = 0;
+
+    for mut group in groups {
+        let ids: HashMap<String, String> = group
+            .generated
+            .into_iter()
+            .map(|(text, id)| {
+                let new_id = frontend.generate_id(&text, config);
+                (id, new_id)
+            })
+            .collect();
+
+        for (mut block, source) in group.blocks {
+            block.rename_ids(&ids);
+            document.add_root_block(block, source);
+        }
+
+        // The tasks before the first section of the group belong to the last section of the
+        // previous groups
+        for task in &mut group.context.tasks {
+            task.section = match task.section {
+                Some(idx) => Some(idx + sections),
+                None => sections.checked_sub(1),
+            };
+        }
+        sections += group.sections;
+
+        document.extend_meta(group.metadata);
+        context.merge(group.context, &ids);
+    }
+
+    (document, context)
+}
+
+/// Splits the children of the document node into (at most) `workers` groups of whole top level
+/// sections with roughly the same size, returns the range of children in each group.
+fn split_groups(tree: &Tree, workers: usize) -> Vec<Range<usize>> {
+    let root = tree.root_node();
+    let target_size = root.byte_range().len() / workers.max(1) + 1;
+
+    let mut groups = Vec::new();
+    let mut start = 0;
+    let mut start_byte = 0;
+
+    let mut cursor = root.walk();
+    for (idx, node) in root.children(&mut cursor).enumerate() {
+        if !starts_section(node) {
+            continue;
+        }
+
+        if idx > start && node.start_byte() - start_byte >= target_size {
+            groups.push(start..idx);
+            start = idx;
+            start_byte = node.start_byte();
+        }
+    }
+
+    groups.push(start..root.child_count());
+    groups
+}
+
+/// Returns whether the node is a top level heading, looking through the carryover sets that
+/// apply to it (like [`Builder::handle_carryover_set`]).
+fn starts_section(node: Node) -> bool {
+    match node.kind() {
+        "heading1" => true,
+        "strong_carryover_set" | "weak_carryover_set" => {
+            let mut cursor = node.walk();
+            let mut children = node.children(&mut cursor);
+            children
+                .find(|child| !matches!(child.kind(), "strong_carryover" | "weak_carryover"))
+                .is_some_and(starts_section)
+        }
+        _ => false,
+    }
+}
+
+fn convert_group<'source>(
+    source: &'source str,
+    tree: &'source Tree,
+    config: &'source Config,
+    mut frontend: FrontendState,
+    nodes: Range<usize>,
+) -> Group<'source> {
+    let field_ids = FieldIds::new(tree);
+    let mut cursor = tree.walk();
+    let mut builder = Builder::new(source, &mut cursor, config, &mut frontend, field_ids);
+
+    if builder.cursor.goto_first_child() {
+        for _ in 0..nodes.start {
+            builder.cursor.goto_next_sibling();
+        }
+
+        for _ in nodes {
+            builder.handle_mapped_node();
+
+            if !builder.cursor.goto_next_sibling() {
+                break;
+            }
+        }
+    }
+
+    let sections = builder.section.map_or(0, |idx| idx + 1);
+    let (document, context) = builder.finish();
+    let (blocks, metadata) = document.finish();
+
+    Group {
+        blocks,
+        metadata,
+        context,
+        generated: frontend.generated.unwrap_or_default(),
+        sections,
+    }
+}
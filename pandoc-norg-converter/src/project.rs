@@ -0,0 +1,173 @@
+//! Conversion of multiple documents (a project) with links between them
+//!
+//! The documents are converted in two passes, the first one collects the identifiers of the
+//! headings and the targets of the anchors of every document and the second one converts the
+//! documents with them, so links to headings in other files (`{:file:** Heading}`) point to the
+//! identifier the heading really got and anchors can be defined in any of the files.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use pandoc_types::definition::Pandoc;
+use tree_sitter::Tree;
+
+use crate::{Config, Frontend};
+
+/// A set of norg documents converted together.
+///
+/// The documents are identified by their path relative to the workspace root (without the
+/// `.norg` extension), which is how they are referenced in links to other files. The urls of the
+/// documents are built with [`Config::workspace_links`] and the identifiers are unique across
+/// all the documents.
+///
+/// # Example
+///
+/// ```rust
+/// use pandoc_norg_converter::{Config, Project};
+///
+/// let mut project = Project::new(Config::default());
+/// project.add_document("index", "See {:notes/intro:* Introduction}");
+/// project.add_document("notes/intro", "* Introduction");
+///
+/// for (name, document) in project.convert() {
+///     println!("{name}: {} blocks", document.blocks.len());
+/// }
+/// ```
+pub struct Project {
+    frontend: Frontend,
+    /// The name and source of each document, in the order they were added.
+    documents: Vec<(String, String)>,
+}
+
+/// The link targets of all the documents in a [`Project`].
+#[derive(Default)]
+pub(crate) struct ProjectIndex {
+    /// The url of each heading by the name of it's document and it's title.
+    headings: HashMap<(String, String), String>,
+    /// The url of each anchor by it's normalized name.
+    anchors: HashMap<String, String>,
+}
+
+/// Returns the name of the document at `path`, as written in a link or passed to
+/// [`Project::add_document`].
+fn document_name(path: &str) -> &str {
+    let path = path.strip_prefix("$/").unwrap_or(path);
+    path.strip_suffix(".norg").unwrap_or(path)
+}
+
+impl ProjectIndex {
+    /// Returns the url of the heading with the given title in the document at `path`.
+    pub fn heading_url(&self, path: &str, title: &str) -> Option<&String> {
+        let title = title.split_whitespace().collect::<Vec<_>>().join(" ");
+        let key = (document_name(path).to_string(), title);
+        self.headings.get(&key)
+    }
+
+    /// Returns the url of the anchor with the given normalized name.
+    pub fn anchor_url(&self, name: &str) -> Option<&String> {
+        self.anchors.get(name)
+    }
+}
+
+impl Project {
+    /// Creates an empty project, all the documents are converted with `config`.
+    pub fn new(config: Config) -> Self {
+        Self {
+            frontend: Frontend::new(config),
+            documents: Vec::new(),
+        }
+    }
+
+    /// Adds a document with the given name (it's path relative to the workspace root).
+    pub fn add_document(&mut self, name: impl Into<String>, source: impl Into<String>) {
+        let name = name.into();
+        let name = document_name(&name).to_string();
+        self.documents.push((name, source.into()));
+    }
+
+    /// Converts all the documents, returns the name and the pandoc representation of each
+    /// document in the order they were added.
+    pub fn convert(&mut self) -> Vec<(String, Pandoc)> {
+        let trees: Vec<Tree> = self
+            .documents
+            .iter()
+            .map(|(_, source)| self.frontend.parse(source, None))
+            .collect();
+
+        let index = self.collect_index(&trees);
+
+        // The identifiers are generated again in the same order, so they match the index
+        self.frontend.state = Default::default();
+        self.frontend.project = Some(Arc::new(index));
+
+        let documents = self
+            .documents
+            .iter()
+            .zip(&trees)
+            .map(|((name, source), tree)| {
+                let document = self
+                    .frontend
+                    .convert_tree(source, tree, |document, _, _| document);
+                (name.clone(), document)
+            })
+            .collect();
+
+        self.frontend.project = None;
+        self.frontend.state = Default::default();
+
+        documents
+    }
+
+    /// Walks all the documents (without reporting diagnostics, they are reported when the
+    /// documents are converted) and collects their link targets.
+    fn collect_index(&mut self, trees: &[Tree]) -> ProjectIndex {
+        let on_diagnostic = self.config_mut().on_diagnostic.replace(Arc::new(|_| {}));
+        self.frontend.state = Default::default();
+
+        let mut index = ProjectIndex::default();
+
+        for ((name, source), tree) in self.documents.iter().zip(trees) {
+            self.frontend.walk_tree(source, tree, |_, context| {
+                let url = context.config.workspace_links.file_url(name);
+
+                for heading in &context.headings {
+                    let key = (name.clone(), heading.title.clone());
+                    let heading_url = format!("{}#{}", url, heading.id);
+                    index.headings.entry(key).or_insert(heading_url);
+                }
+
+                for (anchor, anchor_url) in &context.anchor_urls {
+                    if anchor_url.is_empty() {
+                        continue;
+                    }
+
+                    let anchor_url = match anchor_url.strip_prefix('#') {
+                        Some(id) => format!("{}#{}", url, id),
+                        None => anchor_url.clone(),
+                    };
+                    index.anchors.entry(anchor.clone()).or_insert(anchor_url);
+                }
+            });
+        }
+
+        self.config_mut().on_diagnostic = on_diagnostic;
+        index
+    }
+
+    /// Returns the configuration of the project's `Frontend`, which is never shared since the
+    /// `Frontend` isn't used to convert documents asynchronously.
+    fn config_mut(&mut self) -> &mut Config {
+        Arc::get_mut(&mut self.frontend.config).expect("The project's configuration is shared")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::document_name;
+
+    #[test]
+    fn document_names() {
+        assert_eq!(document_name("$/notes/intro.norg"), "notes/intro");
+        assert_eq!(document_name("notes/intro"), "notes/intro");
+    }
+}
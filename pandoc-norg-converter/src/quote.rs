@@ -4,7 +4,9 @@ use crate::Builder;
 pub(crate) struct QuoteBuilder<'a, 'builder, 'source> {
     builder: &'a mut Builder<'builder, 'source>,
     // TODO: Try to work directly with the document scopes
-    blocks: [Vec<Block<'source>>; 6],
+    /// The blocks of each nesting level, this always has at least the root level and grows as
+    /// deeper levels are found.
+    blocks: Vec<Vec<Block<'source>>>,
     last_level: usize,
 }
 
@@ -15,7 +17,7 @@ where
     pub fn new(builder: &'a mut Builder<'builder, 'source>) -> Self {
         Self {
             builder,
-            blocks: Default::default(),
+            blocks: vec![Vec::new()],
             last_level: 0,
         }
     }
@@ -24,14 +26,11 @@ where
         loop {
             let node = self.builder.cursor.node();
 
-            match node.kind() {
-                "quote1" => self.handle_quote_level(0),
-                "quote2" => self.handle_quote_level(1),
-                "quote3" => self.handle_quote_level(2),
-                "quote4" => self.handle_quote_level(3),
-                "quote5" => self.handle_quote_level(4),
-                "quote6" => self.handle_quote_level(5),
-                kind => log::error!("(quote) unknown node: {:?}", kind),
+            match quote_level(node.kind()) {
+                Some(level) => self.handle_quote_level(level, None),
+                None => self
+                    .builder
+                    .error(format!("(quote) unknown node: {:?}", node.kind())),
             }
 
             if !self.builder.cursor.goto_next_sibling() {
@@ -39,14 +38,17 @@ where
             }
         }
 
-        let Self {
-            blocks: [root, ..], ..
-        } = self;
+        // Merge any quotes that weren't closed back into the root
+        self.merge_quotes(0);
 
-        root
+        self.blocks.swap_remove(0)
     }
 
     fn merge_quotes(&mut self, level: usize) {
+        if self.blocks.len() <= level {
+            self.blocks.resize_with(level + 1, Vec::new);
+        }
+
         let mut i = self.last_level;
         while i > level {
             let mut temp = Vec::new();
@@ -58,7 +60,24 @@ where
         self.last_level = level;
     }
 
-    fn handle_quote_level(&mut self, level: usize) {
+    /// Handles the quote node of `level` under the cursor, `parent` is the level of the quote
+    /// containing it (if any).
+    fn handle_quote_level(&mut self, level: usize, parent: Option<usize>) {
+        // Nested quotes are always deeper than their parent, otherwise their content would be
+        // merged into the wrong level
+        let level = match parent {
+            Some(parent) if level <= parent => {
+                self.builder.warn(format!(
+                    "Quote of level {} is nested in a quote of level {}, treating it as level {}",
+                    level + 1,
+                    parent + 1,
+                    parent + 2
+                ));
+                parent + 1
+            }
+            _ => level,
+        };
+
         if !self.builder.cursor.goto_first_child() {
             return;
         }
@@ -66,29 +85,29 @@ where
         loop {
             let node = self.builder.cursor.node();
 
-            match node.kind() {
-                "quote1" => self.handle_quote_level(0),
-                "quote2" => self.handle_quote_level(1),
-                "quote3" => self.handle_quote_level(2),
-                "quote4" => self.handle_quote_level(3),
-                "quote5" => self.handle_quote_level(4),
-                "quote6" => self.handle_quote_level(5),
+            match (node.kind(), quote_level(node.kind())) {
+                (_, Some(child)) => self.handle_quote_level(child, Some(level)),
 
-                "quote1_prefix" | "quote2_prefix" | "quote3_prefix" | "quote4_prefix"
-                | "quote5_prefix" | "quote6_prefix" => {}
+                (
+                    "quote1_prefix" | "quote2_prefix" | "quote3_prefix" | "quote4_prefix"
+                    | "quote5_prefix" | "quote6_prefix",
+                    _,
+                ) => {}
 
-                "paragraph" => {
+                ("paragraph" | "ranged_tag" | "ranged_verbatim_tag", _) => {
                     self.merge_quotes(level);
 
                     self.builder.document.push_scope();
-                    self.builder.handle_paragraph();
+                    self.builder.handle_node();
                     let mut scope = self.builder.document.pop_scope();
                     self.blocks[level].append(&mut scope);
                 }
 
-                "detached_modifier_extension" => self.builder.handle_detached_ext(),
+                ("detached_modifier_extension", _) => self.builder.handle_detached_ext(false),
 
-                kind => log::error!("(quote) unknown node: {:?}", kind),
+                (kind, _) => self
+                    .builder
+                    .error(format!("(quote) unknown node: {:?}", kind)),
             }
 
             if !self.builder.cursor.goto_next_sibling() {
@@ -101,3 +120,10 @@ where
         self.builder.cursor.goto_parent();
     }
 }
+
+/// Returns the nesting level (starting at 0) of a quote node (`quote1` to `quote6`), or `None` if
+/// the node isn't a quote.
+fn quote_level(kind: &str) -> Option<usize> {
+    let level: usize = kind.strip_prefix("quote")?.parse().ok()?;
+    level.checked_sub(1)
+}
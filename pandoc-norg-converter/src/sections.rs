@@ -0,0 +1,276 @@
+//! Extraction of per section metadata from converted documents
+
+use pandoc_types::definition::{Block, Inline, Pandoc};
+
+use crate::document::DocumentContext;
+use crate::extensions::TodoStatus;
+use crate::stringify::{inlines_to_string, push_blocks_text};
+
+/// Metadata about a top level (`*`) section of a document.
+///
+/// This is returned by [`Frontend::convert_with_sections`] and is meant to be used by static
+/// site generators to render section cards or to split pages without having to process the
+/// converted document again.
+///
+/// [`Frontend::convert_with_sections`]: crate::Frontend::convert_with_sections
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SectionInfo {
+    /// The plain text of the section's heading.
+    pub title: String,
+    /// The identifier of the section's heading.
+    pub id: String,
+    /// The number of words in the section's content (including any subsections).
+    pub word_count: usize,
+    /// The tasks inside the section (including the heading itself).
+    pub todos: TodoSummary,
+    /// The content of the first paragraph in the section.
+    pub first_paragraph: Option<Vec<Inline>>,
+}
+
+/// The number of tasks with each TODO status.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TodoSummary {
+    pub cancelled: usize,
+    pub done: usize,
+    pub on_hold: usize,
+    pub pending: usize,
+    pub recurring: usize,
+    pub uncertain: usize,
+    pub undone: usize,
+    pub urgent: usize,
+}
+
+impl TodoSummary {
+    /// Returns the total number of tasks.
+    pub fn total(&self) -> usize {
+        self.cancelled
+            + self.done
+            + self.on_hold
+            + self.pending
+            + self.recurring
+            + self.uncertain
+            + self.undone
+            + self.urgent
+    }
+
+    fn add(&mut self, status: TodoStatus) {
+        let counter = match status {
+            TodoStatus::Cancelled => &mut self.cancelled,
+            TodoStatus::Done => &mut self.done,
+            TodoStatus::OnHold => &mut self.on_hold,
+            TodoStatus::Pending => &mut self.pending,
+            TodoStatus::Recurring => &mut self.recurring,
+            TodoStatus::Uncertain => &mut self.uncertain,
+            TodoStatus::Undone => &mut self.undone,
+            TodoStatus::Urgent => &mut self.urgent,
+        };
+
+        *counter += 1;
+    }
+}
+
+/// Extracts the metadata of all top level sections in a converted document.
+pub(crate) fn extract_sections(document: &Pandoc, context: &DocumentContext) -> Vec<SectionInfo> {
+    let mut sections: Vec<SectionInfo> = Vec::new();
+    let mut text = String::new();
+    // The level of the top level headings in the output, see `Config::heading_offset`
+    let top_level = (1 + context.config.heading_offset).max(1);
+
+    let mut blocks = Vec::new();
+    flatten_sections(&document.blocks, &mut blocks);
+
+    for (block, section_id) in blocks {
+        match block {
+            Block::Header(level, attr, inlines) if *level == top_level => {
+                if let Some(section) = sections.last_mut() {
+                    section.word_count = text.split_whitespace().count();
+                }
+
+                text.clear();
+                sections.push(SectionInfo {
+                    title: inlines_to_string(inlines),
+                    id: section_id.unwrap_or(&attr.identifier).to_string(),
+                    ..Default::default()
+                });
+
+                continue;
+            }
+            _ => {}
+        }
+
+        let Some(section) = sections.last_mut() else {
+            continue;
+        };
+
+        if let Block::Para(inlines) = block {
+            if section.first_paragraph.is_none() {
+                section.first_paragraph = Some(inlines.clone());
+            }
+        }
+
+        push_blocks_text(std::slice::from_ref(block), &mut text);
+    }
+
+    if let Some(section) = sections.last_mut() {
+        section.word_count = text.split_whitespace().count();
+    }
+
+    for task in &context.tasks {
+        if let Some(section) = task.section.and_then(|idx| sections.get_mut(idx)) {
+            section.todos.add(task.status);
+        }
+    }
+
+    sections
+}
+
+/// Appends the blocks to `out` with the section `Div`s (see [`Config::section_divs`]) replaced
+/// by their content, the headings that start a section are paired with the section's identifier.
+///
+/// [`Config::section_divs`]: crate::Config::section_divs
+fn flatten_sections<'a>(blocks: &'a [Block], out: &mut Vec<(&'a Block, Option<&'a str>)>) {
+    for block in blocks {
+        match block {
+            Block::Div(attr, content) if attr.classes.iter().any(|class| class == "section") => {
+                let start = out.len();
+                flatten_sections(content, out);
+
+                if let Some((Block::Header(..), id)) = out.get_mut(start) {
+                    if !attr.identifier.is_empty() {
+                        *id = Some(&attr.identifier);
+                    }
+                }
+            }
+            block => out.push((block, None)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{extract_sections, SectionInfo, TodoSummary};
+    use crate::document::{DocumentContext, Task};
+    use crate::extensions::TodoStatus;
+    use crate::Config;
+    use pandoc_types::definition::{Attr, Block, Inline, Pandoc};
+
+    fn header(level: i32, id: &str, title: &str) -> Block {
+        let attr = Attr {
+            identifier: id.to_string(),
+            ..Default::default()
+        };
+        Block::Header(level, attr, vec![Inline::Str(title.to_string())])
+    }
+
+    fn para(words: &[&str]) -> Block {
+        let mut inlines = Vec::new();
+        for word in words {
+            if !inlines.is_empty() {
+                inlines.push(Inline::Space);
+            }
+            inlines.push(Inline::Str(word.to_string()));
+        }
+        Block::Para(inlines)
+    }
+
+    #[test]
+    fn sections() {
+        let document = Pandoc {
+            meta: Default::default(),
+            blocks: vec![
+                para(&["Before", "any", "section"]),
+                header(1, "First", "First"),
+                para(&["One", "two"]),
+                header(2, "Nested", "Nested"),
+                para(&["three"]),
+                header(1, "Second", "Second"),
+            ],
+        };
+
+        let config = Config::default();
+        let mut context = DocumentContext::new(&config);
+        context.tasks.push(Task {
+            status: TodoStatus::Done,
+            text: String::new(),
+            section: Some(0),
+            heading: None,
+            start: None,
+            due: None,
+        });
+        context.tasks.push(Task {
+            status: TodoStatus::Undone,
+            text: String::new(),
+            section: Some(1),
+            heading: None,
+            start: None,
+            due: None,
+        });
+
+        let sections = extract_sections(&document, &context);
+
+        assert_eq!(
+            sections,
+            vec![
+                SectionInfo {
+                    title: "First".to_string(),
+                    id: "First".to_string(),
+                    word_count: 4,
+                    todos: TodoSummary {
+                        done: 1,
+                        ..Default::default()
+                    },
+                    first_paragraph: Some(vec![
+                        Inline::Str("One".to_string()),
+                        Inline::Space,
+                        Inline::Str("two".to_string()),
+                    ]),
+                },
+                SectionInfo {
+                    title: "Second".to_string(),
+                    id: "Second".to_string(),
+                    word_count: 0,
+                    todos: TodoSummary {
+                        undone: 1,
+                        ..Default::default()
+                    },
+                    first_paragraph: None,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn section_divs() {
+        let section = |id: &str, blocks| {
+            let attr = Attr {
+                identifier: id.to_string(),
+                classes: vec![String::from("section")],
+                ..Default::default()
+            };
+            Block::Div(attr, blocks)
+        };
+        let document = Pandoc {
+            meta: Default::default(),
+            blocks: vec![
+                section(
+                    "first",
+                    vec![
+                        header(1, "", "First"),
+                        section("nested", vec![header(2, "", "Nested"), para(&["one"])]),
+                    ],
+                ),
+                section("second", vec![header(1, "", "Second"), para(&["two"])]),
+            ],
+        };
+
+        let config = Config::default();
+        let context = DocumentContext::new(&config);
+        let sections = extract_sections(&document, &context);
+
+        let summary: Vec<_> = sections
+            .iter()
+            .map(|section| (section.id.as_str(), section.word_count))
+            .collect();
+        assert_eq!(summary, vec![("first", 2), ("second", 1)]);
+    }
+}
@@ -0,0 +1,117 @@
+//! Hints for pandoc's slide writers (reveal.js, beamer, etc.)
+//!
+//! Pandoc splits the slides at the headings of the slide level (guessed from the document unless
+//! `--slide-level` is passed), the hints make the split explicit so the slides match the
+//! structure of the norg document. Speaker notes are added with the `#notes` carryover tag, which
+//! wraps the following element in a `Div` with the `notes` class.
+
+#[cfg(feature = "serde")]
+use serde::Deserialize;
+
+use crate::ir::Block;
+use crate::source_map::SourceRange;
+
+/// Configuration of the slide splitting hints, see [`Config::slides`].
+///
+/// [`Config::slides`]: crate::Config::slides
+#[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
+pub struct Slides {
+    /// The level of the headings that start a new slide, which should be the level passed to
+    /// pandoc's `--slide-level` (default: `2`).
+    pub level: i32,
+    /// How the slides are split (default: [`SlideSplit::HorizontalRule`]).
+    pub split: SlideSplit,
+}
+
+impl Default for Slides {
+    fn default() -> Self {
+        Self {
+            level: 2,
+            split: SlideSplit::HorizontalRule,
+        }
+    }
+}
+
+/// How the slides are split, see [`Slides`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum SlideSplit {
+    /// A horizontal rule is added before each heading of the slide level, which always starts a
+    /// new slide (default).
+    #[default]
+    HorizontalRule,
+    /// Each heading and it's content are wrapped in a `Div` with the `section` class, which
+    /// pandoc uses as the slides (and the title slides of the higher levels).
+    Sections,
+}
+
+/// Adds a horizontal rule before each heading of the given `level`, unless it directly follows
+/// another heading or rule (which would create an empty slide) or is the first block.
+///
+/// The added rules have no source range.
+pub(crate) fn insert_slide_breaks<'source>(
+    blocks: Vec<Block<'source>>,
+    sources: Vec<Option<SourceRange>>,
+    level: i32,
+) -> (Vec<Block<'source>>, Vec<Option<SourceRange>>) {
+    let mut output = Vec::with_capacity(blocks.len());
+    let mut output_sources = Vec::with_capacity(blocks.len());
+    let mut sources = sources.into_iter();
+
+    for block in blocks {
+        let starts_slide = matches!(block, Block::Header(block_level, ..) if block_level == level);
+        let previous = output.last();
+        let follows_break = matches!(
+            previous,
+            None | Some(Block::Header(..) | Block::HorizontalRule)
+        );
+
+        if starts_slide && !follows_break {
+            output.push(Block::HorizontalRule);
+            output_sources.push(None);
+        }
+
+        output.push(block);
+        output_sources.push(sources.next().flatten());
+    }
+
+    (output, output_sources)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::insert_slide_breaks;
+    use crate::ir::{Block, Inline};
+    use pandoc_types::definition::Attr;
+
+    #[test]
+    fn slide_breaks() {
+        let header = |level| Block::Header(level, Attr::default(), Vec::new());
+        let paragraph = || Block::Plain(vec![Inline::Str("text")]);
+
+        let blocks = vec![
+            header(1),
+            header(2),
+            paragraph(),
+            header(2),
+            paragraph(),
+            header(3),
+            header(1),
+        ];
+        let (blocks, sources) = insert_slide_breaks(blocks, Vec::new(), 2);
+
+        let kinds: Vec<_> = blocks
+            .iter()
+            .map(|block| match block {
+                Block::Header(level, ..) => format!("h{level}"),
+                Block::HorizontalRule => String::from("hr"),
+                _ => String::from("p"),
+            })
+            .collect();
+        assert_eq!(kinds, ["h1", "h2", "p", "hr", "h2", "p", "h3", "h1"]);
+        assert_eq!(sources.len(), blocks.len());
+    }
+}
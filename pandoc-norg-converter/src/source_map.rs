@@ -0,0 +1,136 @@
+//! Mapping of the converted blocks to the source they were generated from
+
+use std::ops::Range;
+
+use pandoc_types::definition::Block as PandocBlock;
+use tree_sitter::Node;
+
+/// The location of a block in a converted document.
+///
+/// The first index is the index of the block in [`Pandoc::blocks`], the following indices (if
+/// any) are the index of the block inside of the blocks of the container `Div` (for example the
+/// section `Div`s created by [`EpubSemantics`] or [`Config::section_divs`]).
+///
+/// [`Pandoc::blocks`]: pandoc_types::definition::Pandoc::blocks
+/// [`EpubSemantics`]: crate::EpubSemantics
+/// [`Config::section_divs`]: crate::Config::section_divs
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct BlockPath(pub Vec<usize>);
+
+/// A range of the neorg source.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct SourceRange {
+    /// The byte range in the source.
+    pub bytes: Range<usize>,
+    /// The (zero based) line where the range starts.
+    pub start_line: usize,
+    /// The (zero based) column (in bytes) where the range starts.
+    pub start_column: usize,
+    /// The (zero based) line where the range ends.
+    pub end_line: usize,
+}
+
+impl SourceRange {
+    pub(crate) fn from_node(node: &Node) -> Self {
+        SourceRange {
+            bytes: node.byte_range(),
+            start_line: node.start_position().row,
+            start_column: node.start_position().column,
+            end_line: node.end_position().row,
+        }
+    }
+}
+
+/// The source ranges of the blocks of a converted document, ordered by their path.
+///
+/// Only the blocks that aren't nested in other blocks (before any wrapping in section `Div`s)
+/// are mapped, the blocks nested inside of them (like list items) share the range of their
+/// parent. Headers are mapped to the range of their title.
+pub type SourceMap = Vec<(BlockPath, SourceRange)>;
+
+/// Adds the `data-pos` attribute with the start of `source` (`line:column`, one based) to the
+/// block at `path` inside of `block` (or to `block` itself if the path is empty), see
+/// [`Config::emit_source_positions`].
+///
+/// Only headers, `Div`s, code blocks and tables have attributes, other blocks are left as is.
+///
+/// [`Config::emit_source_positions`]: crate::Config::emit_source_positions
+pub(crate) fn add_source_position(block: &mut PandocBlock, path: &[usize], source: &SourceRange) {
+    if let Some((idx, path)) = path.split_first() {
+        if let PandocBlock::Div(_, blocks) = block {
+            if let Some(block) = blocks.get_mut(*idx) {
+                add_source_position(block, path, source);
+            }
+        }
+        return;
+    }
+
+    let attr = match block {
+        PandocBlock::Header(_, attr, _)
+        | PandocBlock::Div(attr, _)
+        | PandocBlock::CodeBlock(attr, _) => attr,
+        PandocBlock::Table(table) => &mut table.attr,
+        _ => return,
+    };
+
+    let position = format!("{}:{}", source.start_line + 1, source.start_column + 1);
+    attr.attributes.push((String::from("data-pos"), position));
+}
+
+/// Adds the `data-pos` attribute to the blocks of a document, see [`add_source_position`].
+pub(crate) fn add_source_positions(blocks: &mut [PandocBlock], source_map: &SourceMap) {
+    for (path, source) in source_map {
+        let Some((idx, path)) = path.0.split_first() else {
+            continue;
+        };
+
+        if let Some(block) = blocks.get_mut(*idx) {
+            add_source_position(block, path, source);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{add_source_positions, BlockPath, SourceRange};
+    use pandoc_types::definition::{Attr, Block};
+
+    #[test]
+    fn source_positions() {
+        let mut blocks = vec![
+            Block::Div(
+                Attr::default(),
+                vec![Block::CodeBlock(Attr::default(), String::new())],
+            ),
+            Block::HorizontalRule,
+        ];
+        let source = SourceRange {
+            bytes: 10..20,
+            start_line: 2,
+            start_column: 4,
+            end_line: 3,
+        };
+        let source_map = vec![
+            (BlockPath(vec![0, 0]), source.clone()),
+            (BlockPath(vec![1]), source),
+        ];
+
+        add_source_positions(&mut blocks, &source_map);
+
+        let Block::Div(attr, content) = &blocks[0] else {
+            panic!("Expected a div");
+        };
+        assert!(attr.attributes.is_empty());
+        assert_eq!(
+            content[0],
+            Block::CodeBlock(
+                Attr {
+                    attributes: vec![(String::from("data-pos"), String::from("3:5"))],
+                    ..Default::default()
+                },
+                String::new()
+            )
+        );
+        assert_eq!(blocks[1], Block::HorizontalRule);
+    }
+}
@@ -0,0 +1,104 @@
+//! Helpers to extract the plain text of pandoc elements
+
+use pandoc_types::definition::{Block, Inline};
+
+/// Returns the inlines of a plain text, with a `Str` for each word.
+pub fn text_to_inlines(text: &str) -> Vec<Inline> {
+    let mut inlines = Vec::new();
+
+    for word in text.split_whitespace() {
+        if !inlines.is_empty() {
+            inlines.push(Inline::Space);
+        }
+        inlines.push(Inline::Str(word.to_string()));
+    }
+
+    inlines
+}
+
+/// Returns the plain text of a list of inlines.
+pub fn inlines_to_string(inlines: &[Inline]) -> String {
+    let mut text = String::new();
+    push_inlines_text(inlines, &mut text);
+    text
+}
+
+/// Appends the plain text of a list of inlines to `out`.
+pub fn push_inlines_text(inlines: &[Inline], out: &mut String) {
+    for inline in inlines {
+        match inline {
+            Inline::Str(str) => out.push_str(str),
+            Inline::Space | Inline::SoftBreak | Inline::LineBreak => out.push(' '),
+            Inline::Code(_, text) | Inline::Math(_, text) => out.push_str(text),
+            Inline::Emph(inlines)
+            | Inline::Underline(inlines)
+            | Inline::Strong(inlines)
+            | Inline::Strikeout(inlines)
+            | Inline::Superscript(inlines)
+            | Inline::Subscript(inlines)
+            | Inline::SmallCaps(inlines)
+            | Inline::Quoted(_, inlines)
+            | Inline::Cite(_, inlines)
+            | Inline::Link(_, inlines, _)
+            | Inline::Image(_, inlines, _)
+            | Inline::Span(_, inlines) => push_inlines_text(inlines, out),
+            Inline::RawInline(_, _) | Inline::Note(_) => {}
+        }
+    }
+}
+
+/// Appends the plain text of a list of blocks to `out`, each block is separated by a newline.
+///
+/// Code and raw blocks are skipped since they aren't considered to be prose.
+pub fn push_blocks_text(blocks: &[Block], out: &mut String) {
+    for block in blocks {
+        match block {
+            Block::Plain(inlines) | Block::Para(inlines) | Block::Header(_, _, inlines) => {
+                push_inlines_text(inlines, out)
+            }
+            Block::LineBlock(lines) => {
+                for line in lines {
+                    push_inlines_text(line, out);
+                    out.push('\n');
+                }
+            }
+            Block::BlockQuote(blocks) | Block::Div(_, blocks) | Block::Figure(_, _, blocks) => {
+                push_blocks_text(blocks, out)
+            }
+            Block::OrderedList(_, items) | Block::BulletList(items) => {
+                for item in items {
+                    push_blocks_text(item, out);
+                }
+            }
+            Block::DefinitionList(entries) => {
+                for (term, definitions) in entries {
+                    push_inlines_text(term, out);
+                    out.push('\n');
+
+                    for definition in definitions {
+                        push_blocks_text(definition, out);
+                    }
+                }
+            }
+            Block::Table(table) => {
+                let rows = table
+                    .head
+                    .rows
+                    .iter()
+                    .chain(table.bodies.iter().flat_map(|body| &body.body));
+
+                for row in rows {
+                    for cell in &row.cells {
+                        push_blocks_text(&cell.content, out);
+                    }
+                }
+            }
+            Block::CodeBlock(_, _)
+            | Block::RawBlock(_, _)
+            | Block::HorizontalRule
+            | Block::Null => {}
+        }
+
+        out.push('\n');
+    }
+}
@@ -1,5 +1,7 @@
 use std::panic;
 
+use pandoc_types::definition::Alignment;
+
 use crate::ir::{Block, Cell};
 use crate::Builder;
 
@@ -11,11 +13,13 @@ pub enum TableParsingError {
 type TableLocation = (usize, usize);
 
 impl<'builder, 'source> Builder<'builder, 'source> {
+    /// Parses a table built from single cells, the empty locations after a cell (in the same row
+    /// or in the rows below it) are merged into it by making the cell span them.
     pub fn handle_table(&mut self) {
         log::debug!("Parsing table");
 
-        let mut head = Vec::new();
-        let mut rows = Vec::new();
+        // The first row is the header
+        let mut grid: Vec<Vec<Option<Cell<'source>>>> = vec![Vec::new()];
         let mut num_cols = 0;
 
         self.visit_children(|this| {
@@ -23,41 +27,42 @@ impl<'builder, 'source> Builder<'builder, 'source> {
 
             match kind {
                 "single_table_cell" => {
-                    let (row_idx, col_idx, cell) = this.handle_single_cell();
+                    let Some(((row_idx, col_idx), cell)) = this.handle_single_cell() else {
+                        return;
+                    };
 
-                    if row_idx != 0 {
-                        while rows.len() < row_idx {
-                            rows.push(Vec::new());
-                        }
+                    while grid.len() <= row_idx {
+                        grid.push(Vec::new());
                     }
 
-                    let row = if row_idx == 0 {
-                        &mut head
-                    } else {
-                        &mut rows[row_idx - 1]
-                    };
-
+                    let row = &mut grid[row_idx];
                     while row.len() <= col_idx {
-                        row.push(Cell { blocks: Vec::new() });
+                        row.push(None);
                     }
 
-                    row[col_idx] = cell;
+                    row[col_idx] = Some(cell);
 
                     num_cols = num_cols.max(col_idx + 1);
                 }
-                _ => log::error!("Unknown node: {:?}", kind),
+                _ => this.error(format!("Unknown node: {:?}", kind)),
             }
         });
 
-        self.document.add_block(Block::Table(num_cols, head, rows))
+        let mut rows = merge_empty_cells(grid, num_cols);
+        let head = rows.remove(0);
+
+        let caption = self.take_carryover_caption();
+        let alignments = vec![Alignment::AlignDefault; num_cols];
+        self.document
+            .add_block(Block::Table(caption, alignments, Some(head), rows))
     }
 
-    fn handle_single_cell(&mut self) -> (usize, usize, Cell<'source>) {
+    /// Returns the location of the cell and the cell, or `None` if the location is invalid.
+    fn handle_single_cell(&mut self) -> Option<(TableLocation, Cell<'source>)> {
         log::trace!("Parsing table single cell");
 
         let mut blocks = Vec::new();
-        let mut row = 0;
-        let mut col = 0;
+        let mut location = None;
 
         self.visit_children(|this| {
             let id = this.cursor.field_id();
@@ -65,7 +70,10 @@ impl<'builder, 'source> Builder<'builder, 'source> {
                 let node = this.cursor.node();
                 let text = &this.source[node.start_byte()..node.end_byte()];
 
-                (row, col) = parse_table_location(text).unwrap();
+                location = parse_table_location(text).ok();
+                if location.is_none() {
+                    this.error(format!("Invalid table cell location: {}", text));
+                }
             } else if id == this.field_ids.content {
                 this.document.push_scope();
                 this.handle_paragraph();
@@ -73,15 +81,96 @@ impl<'builder, 'source> Builder<'builder, 'source> {
             } else {
                 match this.cursor.node().kind() {
                     "single_table_cell_prefix" | "_intersecting_modifier" => {}
-                    kind => log::error!("(table) unknown node: {:?}", kind),
+                    kind => this.error(format!("(table) unknown node: {:?}", kind)),
                 };
             }
         });
 
-        return (row, col, Cell { blocks });
+        Some((location?, Cell::new(blocks)))
     }
 }
 
+/// Turns the grid of cells (the first row being the header) into the rows of the table, with
+/// `num_cols` columns.
+///
+/// Each cell spans the empty locations after it in its row, and then the rows below it for as
+/// long as all the columns it spans are empty there. The spans never leave the grid and the cells
+/// of the header never span the body, empty locations that can't be merged into a cell (like the
+/// ones at the start of a row) are kept as empty cells.
+fn merge_empty_cells(mut grid: Vec<Vec<Option<Cell<'_>>>>, num_cols: usize) -> Vec<Vec<Cell<'_>>> {
+    let num_rows = grid.len();
+    // The locations that are part of a cell (including its own)
+    let mut covered = vec![vec![false; num_cols]; num_rows];
+
+    let mut rows = Vec::with_capacity(num_rows);
+    for row_idx in 0..num_rows {
+        let mut row = Vec::new();
+
+        for col_idx in 0..num_cols {
+            if covered[row_idx][col_idx] {
+                continue;
+            }
+
+            let Some(mut cell) = grid[row_idx].get_mut(col_idx).and_then(Option::take) else {
+                row.push(Cell::new(Vec::new()));
+                continue;
+            };
+
+            let is_empty = |row: usize, col: usize| {
+                !covered[row][col] && grid[row].get(col).map_or(true, Option::is_none)
+            };
+
+            let mut col_end = col_idx + 1;
+            while col_end < num_cols && is_empty(row_idx, col_end) {
+                col_end += 1;
+            }
+
+            let mut row_end = row_idx + 1;
+            if row_idx != 0 {
+                while row_end < num_rows && (col_idx..col_end).all(|col| is_empty(row_end, col)) {
+                    row_end += 1;
+                }
+            }
+
+            for covered_row in &mut covered[row_idx..row_end] {
+                covered_row[col_idx..col_end].fill(true);
+            }
+
+            cell.row_span = (row_end - row_idx) as i32;
+            cell.col_span = (col_end - col_idx) as i32;
+            row.push(cell);
+        }
+
+        rows.push(row);
+    }
+
+    rows
+}
+
+/// Parses a separator row of the `@table` tag (like `:--- | :---: | ---:`) into the alignment of
+/// each column, returns `None` if the line isn't a separator row.
+pub(crate) fn parse_alignment_row(line: &str) -> Option<Vec<Alignment>> {
+    line.split('|')
+        .map(|col| {
+            let col = col.trim();
+            let left = col.starts_with(':');
+            let right = col.len() > 1 && col.ends_with(':');
+            let dashes = col.trim_start_matches(':').trim_end_matches(':');
+
+            if dashes.is_empty() || dashes.chars().any(|c| c != '-') {
+                return None;
+            }
+
+            Some(match (left, right) {
+                (true, true) => Alignment::AlignCenter,
+                (true, false) => Alignment::AlignLeft,
+                (false, true) => Alignment::AlignRight,
+                (false, false) => Alignment::AlignDefault,
+            })
+        })
+        .collect()
+}
+
 fn consume_while(input: &str, mut predicate: impl FnMut(char) -> bool) -> (&str, &str) {
     let idx = input.find(|c| !predicate(c)).unwrap_or(input.len());
     input.split_at(idx)
@@ -184,7 +273,11 @@ fn parse_table_location(loc: &str) -> Result<TableLocation, TableParsingError> {
 
 #[cfg(test)]
 mod tests {
-    use super::{parse_row, parse_table_location, TableParsingError};
+    use super::{
+        merge_empty_cells, parse_alignment_row, parse_row, parse_table_location, TableParsingError,
+    };
+    use crate::ir::Cell;
+    use pandoc_types::definition::Alignment;
 
     #[test]
     fn test_parse_row() {
@@ -211,4 +304,50 @@ mod tests {
             Err(TableParsingError::InvalidLocation)
         );
     }
+
+    #[test]
+    fn test_parse_alignment_row() {
+        assert_eq!(
+            parse_alignment_row(":--- | :---: | ---: | ---"),
+            Some(vec![
+                Alignment::AlignLeft,
+                Alignment::AlignCenter,
+                Alignment::AlignRight,
+                Alignment::AlignDefault
+            ])
+        );
+        assert_eq!(parse_alignment_row("a | ---"), None);
+        assert_eq!(parse_alignment_row(":"), None);
+    }
+
+    #[test]
+    fn test_merge_empty_cells() {
+        let cell = || Some(Cell::new(Vec::new()));
+        let grid = vec![
+            vec![cell(), cell()],
+            vec![cell(), cell(), cell()],
+            vec![cell(), cell()],
+            vec![None, cell()],
+            vec![None, None, cell()],
+        ];
+
+        let spans: Vec<Vec<_>> = merge_empty_cells(grid, 3)
+            .iter()
+            .map(|row| {
+                row.iter()
+                    .map(|cell| (cell.row_span, cell.col_span))
+                    .collect()
+            })
+            .collect();
+        assert_eq!(
+            spans,
+            [
+                vec![(1, 1), (1, 2)],
+                vec![(1, 1), (1, 1), (1, 1)],
+                vec![(3, 1), (1, 2)],
+                vec![(1, 2)],
+                vec![(1, 1), (1, 1)],
+            ]
+        );
+    }
 }
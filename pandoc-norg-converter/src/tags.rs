@@ -1,5 +1,90 @@
+use std::borrow::Cow;
+#[cfg(feature = "ir")]
+use std::sync::Arc;
+
+use pandoc_types::definition::{Alignment, Attr, Format};
+
+use crate::document::{DefinitionKind, DocumentLinkType};
+use crate::inlines::{html_comment, split_words};
 use crate::ir::{Block, Cell, Inline};
-use crate::Builder;
+use crate::table::parse_alignment_row;
+use crate::tasks::TaskQuery;
+use crate::toc::TableOfContents;
+#[cfg(feature = "ir")]
+use crate::Config;
+use crate::{Builder, CommentStyle};
+
+/// A handler of a custom verbatim ranged tag (like `@mermaid`), see
+/// [`Config::register_verbatim_handler`].
+///
+/// The handler receives the parameters of the tag and it's content (without the indentation)
+/// and returns the blocks that replace the tag.
+#[cfg(feature = "ir")]
+pub type VerbatimHandler = Arc<dyn Fn(&[Cow<str>], &str) -> Vec<Block<'static>> + Send + Sync>;
+
+#[cfg(feature = "ir")]
+impl Config {
+    /// Registers the handler of the verbatim ranged tags with the given name, for example
+    /// `mermaid` for `@mermaid ... @end` tags.
+    ///
+    /// The handler replaces any handler previously registered with the same name and takes
+    /// precedence over the built-in tags (like `code`).
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use pandoc_norg_converter::{ir::Block, Config};
+    /// use pandoc_types::definition::Format;
+    ///
+    /// let mut config = Config::default();
+    /// config.register_verbatim_handler("mermaid", |_parameters, content| {
+    ///     let html = format!("<pre class=\"mermaid\">{content}</pre>");
+    ///     vec![Block::Raw(Format(String::from("html")), html)]
+    /// });
+    /// ```
+    pub fn register_verbatim_handler(
+        &mut self,
+        name: impl Into<String>,
+        handler: impl Fn(&[Cow<str>], &str) -> Vec<Block<'static>> + Send + Sync + 'static,
+    ) {
+        self.verbatim_handlers
+            .insert(name.into(), Arc::new(handler));
+    }
+
+    /// Registers the handler of the infirm tags with the given name, for example `youtube` for
+    /// `.youtube id` tags.
+    ///
+    /// The handler replaces any handler previously registered with the same name and takes
+    /// precedence over the built-in tags (like `image`).
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use pandoc_norg_converter::{ir::Block, Config};
+    /// use pandoc_types::definition::Format;
+    ///
+    /// let mut config = Config::default();
+    /// config.register_infirm_handler("youtube", |parameters| {
+    ///     let id = parameters.first().map(|id| id.as_ref()).unwrap_or_default();
+    ///     let html = format!("<iframe src=\"https://www.youtube.com/embed/{id}\"></iframe>");
+    ///     vec![Block::Raw(Format(String::from("html")), html)]
+    /// });
+    /// ```
+    pub fn register_infirm_handler(
+        &mut self,
+        name: impl Into<String>,
+        handler: impl Fn(&[Cow<str>]) -> Vec<Block<'static>> + Send + Sync + 'static,
+    ) {
+        self.infirm_handlers.insert(name.into(), Arc::new(handler));
+    }
+}
+
+/// A handler of a custom infirm tag (like `.youtube id`), see
+/// [`Config::register_infirm_handler`].
+///
+/// The handler receives the parameters of the tag and returns the blocks that replace the tag.
+#[cfg(feature = "ir")]
+pub type InfirmHandler = Arc<dyn Fn(&[Cow<str>]) -> Vec<Block<'static>> + Send + Sync>;
 
 impl<'builder, 'source> Builder<'builder, 'source>
 where
@@ -10,12 +95,17 @@ where
 
         let mut name = "";
         let mut parameters = Vec::new();
+        // The raw text of the parameters, used as the title of callouts
+        let mut title = None;
+        // Macro tags (`=name ... =end`) define variables instead
+        let mut is_macro = false;
 
         self.visit_children(|this| {
             let node = this.cursor.node();
 
             match node.kind() {
-                "_prefix" | "_space" | "_line_break" | "ranged_tag_end" => {}
+                "_prefix" => is_macro = this.source[node.byte_range()].trim() == "=",
+                "_space" | "_line_break" | "ranged_tag_end" => {}
                 "tag_name" => {
                     let text = node
                         .utf8_text(this.source.as_bytes())
@@ -24,16 +114,59 @@ where
                     name = text;
                 }
 
-                "tag_parameters" => this.handle_tag_parameters(&mut parameters),
+                "tag_parameters" => {
+                    title = Some(&this.source[node.byte_range()]);
+                    this.handle_tag_parameters(&mut parameters)
+                }
 
+                "ranged_tag_content" if is_macro => this.define_variable(name, &parameters),
                 "ranged_tag_content" => match name {
                     "example" => this.handle_example_block(&parameters),
-                    _ => log::error!("Unknown ranged tag name '{}'", name),
+                    "note" | "tip" | "important" | "warning" | "caution" => {
+                        this.handle_callout(name, title)
+                    }
+                    // The content of the old style table of contents is ignored
+                    "toc" => {}
+                    _ => this.error(format!("Unknown ranged tag name '{}'", name)),
                 },
 
-                kind => log::error!("(ranged_tag) unknown node: {:?}", kind),
+                kind => this.error(format!("(ranged_tag) unknown node: {:?}", kind)),
             }
         });
+
+        if name == "toc" && !is_macro {
+            let toc = TableOfContents::new(&parameters);
+            self.document.add_block(Block::TableOfContents(toc));
+        }
+    }
+
+    /// Defines the variable `name` with the content of a macro tag (with it's lines joined by
+    /// spaces) as it's value, which replaces the inline variables (`&name&`) of the document.
+    fn define_variable(&mut self, name: &str, parameters: &[Cow<'source, str>]) {
+        log::debug!("Parsing variable definition '{}'", name);
+
+        if !parameters.is_empty() {
+            self.warn(format!(
+                "The parameters of the macro '{}' are ignored",
+                name
+            ));
+        }
+
+        let node = self.cursor.node();
+        let value = self.source[node.byte_range()]
+            .split_whitespace()
+            .collect::<Vec<_>>()
+            .join(" ");
+
+        self.context.define(
+            DefinitionKind::Variable,
+            name.to_string(),
+            node.byte_range(),
+        );
+        self.context
+            .variables
+            .entry(name.to_string())
+            .or_insert(value);
     }
 
     pub fn handle_verbatim(&mut self) {
@@ -55,68 +188,361 @@ where
                     name = text;
                 }
                 "tag_parameters" => this.handle_tag_parameters(&mut parameters),
-                "ranged_verbatim_tag_content" => match name {
-                    "code" => this.handle_code_block(&parameters),
-                    "embed" => this.handle_embed_block(&parameters),
-                    "table" => this.handle_table_block(&parameters),
-                    "document.meta" => this.handle_document_meta_block(&parameters),
-                    "math" => this.handle_math_block(&parameters),
-                    "comment" => log::debug!("Parsing comment block"),
-                    _ => log::error!("Unknown verbatim name '{}'", name),
-                },
+                "ranged_verbatim_tag_content" => this.handle_verbatim_content(name, &parameters),
 
-                kind => log::error!("(verbatim) unknown node: {:?}", kind),
+                kind => this.error(format!("(verbatim) unknown node: {:?}", kind)),
             }
         });
     }
 
-    fn handle_tag_parameters(&mut self, parameters: &mut Vec<&'source str>) {
-        let node = self.cursor.node();
+    /// Handles the content of a verbatim ranged tag with the given name.
+    fn handle_verbatim_content(&mut self, name: &str, parameters: &[Cow<'source, str>]) {
+        #[cfg(feature = "ir")]
+        if let Some(handler) = self.config.verbatim_handlers.get(name) {
+            log::debug!("Parsing custom verbatim '{}'", name);
+
+            for block in handler(parameters, &self.code_content()) {
+                self.document.add_block(block);
+            }
+            return;
+        }
+
+        match name {
+            "code" => self.handle_code_block(parameters),
+            "embed" => self.handle_embed_block(parameters),
+            "image" => self.handle_image_block(parameters),
+            "table" => self.handle_table_block(parameters),
+            "document.meta" => self.handle_document_meta_block(parameters),
+            "math" => self.handle_math_block(parameters),
+            "pandoc.raw" => self.handle_raw_block(parameters),
+            "bibliography" => self.handle_bibliography_block(parameters),
+            "comment" => {
+                log::debug!("Parsing comment block");
+                let node = self.cursor.node();
+                self.add_comment_block(&self.source[node.byte_range()]);
+            }
+            _ => self.error(format!("Unknown verbatim name '{}'", name)),
+        }
+    }
+
+    pub fn handle_infirm_tag(&mut self) {
+        log::debug!("Parsing infirm tag");
+
+        let mut name = "";
+        let mut parameters = Vec::new();
+
+        self.visit_children(|this| {
+            let node = this.cursor.node();
+
+            match node.kind() {
+                "_prefix" | "_space" | "_line_break" => {}
+                "tag_name" => {
+                    let text = node
+                        .utf8_text(this.source.as_bytes())
+                        .expect("Invalid text");
+
+                    name = text;
+                }
+                "tag_parameters" => this.handle_tag_parameters(&mut parameters),
+                kind => this.error(format!("(infirm tag) unknown node: {:?}", kind)),
+            }
+        });
+
+        self.handle_infirm_tag_name(name, parameters);
+    }
+
+    /// Handles an infirm tag with the given name and parameters.
+    fn handle_infirm_tag_name(&mut self, name: &str, mut parameters: Vec<Cow<'source, str>>) {
+        #[cfg(feature = "ir")]
+        if let Some(handler) = self.config.infirm_handlers.get(name) {
+            log::debug!("Parsing custom infirm tag '{}'", name);
+
+            for block in handler(&parameters) {
+                self.document.add_block(block);
+            }
+            return;
+        }
+
+        match name {
+            "tasks" => {
+                let query = TaskQuery::parse(&parameters, |message| self.warn(message));
+                self.document.add_block(Block::TaskQuery(query));
+            }
+            "toc" => {
+                let toc = TableOfContents::new(&parameters);
+                self.document.add_block(Block::TableOfContents(toc));
+            }
+            "image" if parameters.is_empty() => self.error("Image infirm tag without a path"),
+            "image" => {
+                let url = parameters.remove(0);
+                self.add_image(url, &parameters);
+            }
+            _ => self.error(format!("Unknown infirm tag name '{}'", name)),
+        }
+    }
 
-        parameters.reserve(node.child_count());
+    /// Parses a carryover tag and stores it until the next element consumes it.
+    pub fn handle_carryover_tag(&mut self) {
+        log::debug!("Parsing carryover tag");
+
+        let mut name = "";
+        let mut parameters = Vec::new();
 
         self.visit_children(|this| {
-            let text = node
-                .utf8_text(this.source.as_bytes())
-                .expect("Invalid text");
+            let node = this.cursor.node();
 
-            parameters.push(text);
+            match node.kind() {
+                "_prefix" | "_space" | "_line_break" => {}
+                "tag_name" => {
+                    let text = node
+                        .utf8_text(this.source.as_bytes())
+                        .expect("Invalid text");
+
+                    name = text;
+                }
+                "tag_parameters" => this.handle_tag_parameters(&mut parameters),
+                kind => this.error(format!("(carryover) unknown node: {:?}", kind)),
+            }
         });
+
+        self.carryover_tags.push(CarryoverTag { name, parameters });
+    }
+
+    /// Parses a carryover set, the carryover tags and the element they apply to.
+    ///
+    /// The attributes defined by the tags are added to the element if it's a single block that
+    /// can have attributes (headings, code blocks and `Div`s), otherwise it's wrapped in a `Div`
+    /// with them (unless the element consumed them itself).
+    ///
+    /// Headings are handled differently, strong carryover tags (`#name`) apply to the whole
+    /// section which is wrapped in a `Div` (see [`CarryoverTag::apply_to_section`]) and weak
+    /// carryover tags (`+name`) only apply to the heading itself.
+    pub fn handle_carryover_set(&mut self) {
+        log::debug!("Parsing carryover set");
+
+        let strong = self.cursor.node().kind() == "strong_carryover_set";
+        self.document.push_scope();
+
+        let mut comment = None;
+        let mut private = false;
+        let mut section_tags = None;
+        self.visit_children(|this| match this.cursor.node().kind() {
+            "strong_carryover" | "weak_carryover" => this.handle_carryover_tag(),
+            // Private elements (and sections) are skipped, see `Config::include_private`
+            _ if !this.config.include_private
+                && this
+                    .carryover_tags
+                    .iter()
+                    .any(|tag| is_private_tag(tag.name)) =>
+            {
+                private = true;
+            }
+            // Elements commented out with `#comment` aren't converted unless they're kept as a
+            // `Div` (with the `comment` class added by the tag)
+            _ if this.config.block_comments != CommentStyle::Span
+                && this.carryover_tags.iter().any(|tag| tag.name == "comment") =>
+            {
+                let node = this.cursor.node();
+                comment = Some(&this.source[node.byte_range()]);
+            }
+            // The tags are kept aside so that the elements of the section don't take them
+            kind if kind.starts_with("heading") => {
+                section_tags = Some(std::mem::take(&mut this.carryover_tags));
+                this.handle_node();
+            }
+            _ => this.handle_node(),
+        });
+
+        if private {
+            self.document.pop_scope();
+            self.carryover_tags.clear();
+            return;
+        }
+
+        if let Some(text) = comment {
+            self.document.pop_scope();
+            self.carryover_tags.clear();
+            return self.add_comment_block(text);
+        }
+
+        if let Some(tags) = section_tags {
+            let blocks = self.document.pop_scope();
+            return self.add_section(blocks, tags, strong);
+        }
+
+        let mut blocks = self.document.pop_scope();
+        let attr = self.take_carryover_attr();
+
+        let attr = match blocks.as_mut_slice() {
+            _ if attr == Attr::default() => None,
+            // Speaker notes (`#notes`) are always a `Div`, which is what pandoc's slide writers
+            // expect
+            _ if attr.classes.iter().any(|class| class == "notes") => Some(attr),
+            [block] => block.add_attr(attr).err(),
+            _ => Some(attr),
+        };
+
+        match attr {
+            Some(attr) => self.document.add_block(Block::Div(attr, blocks)),
+            None => {
+                for block in blocks.drain(..) {
+                    self.document.add_block(block);
+                }
+            }
+        }
+    }
+
+    /// Adds the blocks of a section (it's heading and content) with the attributes of the
+    /// carryover tags, see [`Builder::handle_carryover_set`].
+    fn add_section(
+        &mut self,
+        mut blocks: Vec<Block<'source>>,
+        tags: Vec<CarryoverTag<'source>>,
+        strong: bool,
+    ) {
+        let mut attr = Attr::default();
+        for tag in tags {
+            let result = match strong {
+                true => tag.apply_to_section(&mut attr),
+                false => tag.apply_to(&mut attr),
+            };
+
+            if let Err(err) = result {
+                self.warn(err);
+            }
+        }
+
+        let attr = match blocks.first_mut() {
+            _ if attr == Attr::default() => None,
+            Some(heading) if !strong => heading.add_attr(attr).err(),
+            _ => Some(attr),
+        };
+
+        match attr {
+            Some(attr) => self.document.add_block(Block::Div(attr, blocks)),
+            None => {
+                for block in blocks.drain(..) {
+                    self.document.add_block(block);
+                }
+            }
+        }
     }
 
-    fn handle_example_block(&mut self, parameters: &[&str]) {
+    /// Removes the pending `#caption` carryover tag (if any) and returns the caption.
+    pub fn take_carryover_caption(&mut self) -> Option<String> {
+        let idx = self
+            .carryover_tags
+            .iter()
+            .position(|tag| tag.name == "caption")?;
+        let tag = self.carryover_tags.remove(idx);
+        Some(tag.parameters.join(" "))
+    }
+
+    /// Adds a commented out block with the given text, see [`Config::block_comments`].
+    fn add_comment_block(&mut self, text: &'source str) {
+        match self.config.block_comments {
+            CommentStyle::Drop => {}
+            CommentStyle::Span => {
+                let inlines = split_words(text);
+
+                let attr = Attr {
+                    classes: vec![String::from("comment")],
+                    ..Default::default()
+                };
+                self.document
+                    .add_block(Block::Div(attr, vec![Block::Plain(inlines)]));
+            }
+            CommentStyle::RawHtml => {
+                let format = Format(String::from("html"));
+                let comment = html_comment(text.trim());
+                self.document.add_block(Block::Raw(format, comment));
+            }
+        }
+    }
+
+    /// Returns the attributes defined by the pending carryover tags and clears them.
+    pub fn take_carryover_attr(&mut self) -> Attr {
+        let mut attr = Attr::default();
+
+        for tag in std::mem::take(&mut self.carryover_tags) {
+            if let Err(err) = tag.apply_to(&mut attr) {
+                self.warn(err);
+            }
+        }
+
+        attr
+    }
+
+    fn handle_tag_parameters(&mut self, parameters: &mut Vec<Cow<'source, str>>) {
+        let text = self
+            .cursor
+            .node()
+            .utf8_text(self.source.as_bytes())
+            .expect("Invalid text");
+
+        parameters.extend(split_parameters(text));
+    }
+
+    fn handle_example_block(&mut self, parameters: &[Cow<'source, str>]) {
         log::debug!("Parsing example block");
 
         if !parameters.is_empty() {
-            log::error!(
+            self.error(format!(
                 "Example block expected 0 parameter received: {}",
                 parameters.len()
-            );
-            log::error!("Extra parameters: {:?}", parameters);
+            ));
+            self.error(format!("Extra parameters: {:?}", parameters));
         }
 
         let content = self.code_content();
-        self.document
-            .add_block(Block::CodeBlock(Some("norg"), content))
+        let attr = Attr {
+            classes: vec![String::from("norg")],
+            ..Default::default()
+        };
+        self.document.add_block(Block::CodeBlock(attr, content))
     }
 
-    fn handle_code_block(&mut self, parameters: &[&'source str]) {
-        log::debug!("Parsing code block");
+    /// Converts a callout ranged tag (like `|warning`) to a `Div` with the tag's name as class,
+    /// the parameters (if any) are the title of the callout.
+    fn handle_callout(&mut self, name: &str, title: Option<&'source str>) {
+        log::debug!("Parsing {} callout", name);
 
-        if parameters.len() > 1 {
-            log::error!(
-                "Code block expected 1 parameter received: {}",
-                parameters.len()
-            );
-            log::error!("Extra parameters: {:?}", &parameters[1..]);
+        self.document.push_scope();
+
+        if let Some(title) = title {
+            let inlines = split_words(title);
+
+            let attr = Attr {
+                classes: vec![String::from("title")],
+                ..Default::default()
+            };
+            self.document
+                .add_block(Block::Div(attr, vec![Block::Plain(inlines)]));
         }
 
+        self.visit_children(Self::handle_node);
+
+        let blocks = self.document.pop_scope();
+        let attr = Attr {
+            classes: vec![name.to_string()],
+            ..Default::default()
+        };
+        self.document.add_block(Block::Div(attr, blocks));
+    }
+
+    fn handle_code_block(&mut self, parameters: &[Cow<'source, str>]) {
+        log::debug!("Parsing code block");
+
         let content = self.code_content();
-        let language = parameters.get(0).map(|s| *s);
-        self.document.add_block(Block::CodeBlock(language, content))
+        // The language is the first parameter and so the first class (as expected by pandoc)
+        let mut attr = parameters_attr(parameters);
+        if self.config.number_lines {
+            attr.classes.push(String::from("numberLines"));
+        }
+        self.document.add_block(Block::CodeBlock(attr, content))
     }
 
-    fn code_content(&self) -> String {
+    pub(crate) fn code_content(&self) -> String {
         let node = self.cursor.node();
         let start_indent = node.start_position().column;
         let mut min_indent = start_indent;
@@ -173,44 +599,45 @@ where
         content
     }
 
-    fn handle_embed_block(&mut self, parameters: &[&str]) {
+    /// Handles an embed block (`@embed image`).
+    ///
+    /// The parameters after the type and the carryover tags of the block define the image's
+    /// attributes (`key=value` parameters add attributes, the others add classes), the `caption`,
+    /// `alt` and `title` attributes are used as the image's caption, alternative text and title,
+    /// images with a caption are placed in a figure.
+    fn handle_embed_block(&mut self, parameters: &[Cow<'source, str>]) {
         log::debug!("Parsing embed block");
 
-        if parameters.len() != 1 {
-            log::error!(
-                "Embed block expected 1 parameter received: {}",
-                parameters.len()
-            );
-            if parameters.len() > 1 {
-                log::error!("Extra parameters: {:?}", &parameters[1..]);
-            }
-        }
-
         let text = self
             .cursor
             .node()
             .utf8_text(self.source.as_bytes())
             .expect("Invalid text");
 
-        match parameters.first().copied() {
-            Some("image") => {
-                let segment = vec![Inline::Image(text.trim())];
-                self.document.add_block(Block::Plain(segment));
-            }
-            Some(kind) => log::error!("Unknown embed type: {}", kind),
-            None => {}
+        match parameters.first().map(|s| s.as_ref()) {
+            Some("image") => self.add_image(Cow::Borrowed(text.trim()), &parameters[1..]),
+            Some("video") => self.add_media("video", text.trim(), &parameters[1..]),
+            Some("audio") => self.add_media("audio", text.trim(), &parameters[1..]),
+            Some("html") => self.add_raw_html(),
+            Some(kind) => self.error(format!("Unknown embed type: {}", kind)),
+            None => self.error("Embed block without a type"),
         }
     }
 
-    fn handle_table_block(&mut self, parameters: &[&str]) {
+    /// Parses the old table syntax, the cells of each line are separated by `|` and the first line
+    /// is the header (unless the `noheader` parameter is passed).
+    ///
+    /// The line after the header (or the first line) can be a separator row (like
+    /// `:--- | :---: | ---:`) which defines the alignment of each column.
+    fn handle_table_block(&mut self, parameters: &[Cow<'source, str>]) {
         log::debug!("Parsing table");
 
-        if !parameters.is_empty() {
-            log::error!(
-                "Table block expected 0 parameter received: {}",
-                parameters.len()
-            );
-            log::error!("Extra parameters: {:?}", parameters);
+        let mut has_header = true;
+        for parameter in parameters {
+            match parameter.as_ref() {
+                "noheader" => has_header = false,
+                parameter => self.error(format!("Unknown table parameter: {}", parameter)),
+            }
         }
 
         let text = self
@@ -226,9 +653,7 @@ where
 
             for col in line.split('|') {
                 let content = col.trim();
-                row.push(Cell {
-                    blocks: vec![Block::Plain(vec![Inline::Str(content)])],
-                });
+                row.push(Cell::new(vec![Block::Plain(vec![Inline::Str(content)])]));
             }
 
             cols = cols.max(row.len());
@@ -236,39 +661,327 @@ where
             row
         };
 
-        let mut head = Vec::new();
-        let mut body = Vec::new();
+        let mut lines = text.lines().peekable();
 
-        let mut lines = text.lines();
+        let head = match has_header {
+            true => lines.next().map(&mut parse_row),
+            false => None,
+        };
 
-        if let Some(line) = lines.next() {
-            head = parse_row(line);
-        }
+        let mut alignments = match lines.peek().and_then(|line| parse_alignment_row(line)) {
+            Some(alignments) => {
+                lines.next();
+                alignments
+            }
+            None => Vec::new(),
+        };
 
-        for line in lines {
-            body.push(parse_row(line))
-        }
+        let body = lines.map(parse_row).collect();
 
-        self.document.add_block(Block::Table(cols, head, body));
+        let caption = self.take_carryover_caption();
+        alignments.resize(cols, Alignment::AlignDefault);
+        self.document
+            .add_block(Block::Table(caption, alignments, head, body));
     }
 
-    fn handle_math_block(&mut self, parameters: &[&str]) {
-        log::debug!("Parsing math block");
+    /// Parses a `@pandoc.raw format` tag, it's content is passed to pandoc as is (only for the
+    /// given output format).
+    fn handle_raw_block(&mut self, parameters: &[Cow<'source, str>]) {
+        log::debug!("Parsing raw block");
 
-        if !parameters.is_empty() {
-            log::error!(
-                "Math block expected 0 parameter received: {}",
+        let [format] = parameters else {
+            return self.error(format!(
+                "Raw block expected 1 parameter (the format) received: {}",
                 parameters.len()
-            );
-            log::error!("Extra parameters: {:?}", parameters);
+            ));
+        };
+
+        if format == "html" && self.config.disable_raw_html {
+            return self.warn("Raw HTML is disabled, the raw block was removed");
         }
 
+        let content = self.code_content();
+        self.document
+            .add_block(Block::Raw(Format(format.to_string()), content));
+    }
+
+    /// Parses a math block, the first parameter that isn't a `key=value` pair is the LaTeX
+    /// environment that wraps the content (like `align`) and an `id` makes the equation a link
+    /// target.
+    fn handle_math_block(&mut self, parameters: &[Cow<'source, str>]) {
+        log::debug!("Parsing math block");
+
         let text = self
             .cursor
             .node()
             .utf8_text(self.source.as_bytes())
             .expect("Invalid text");
 
-        self.document.add_block(Block::MathBlock(text.to_string()));
+        let mut attr = self.take_carryover_attr();
+        let mut params = parameters_attr(parameters);
+        let environment = match params.classes.is_empty() {
+            true => None,
+            false => Some(params.classes.remove(0)),
+        };
+        attr.classes.append(&mut params.classes);
+        attr.attributes.append(&mut params.attributes);
+        if !params.identifier.is_empty() {
+            attr.identifier = params.identifier;
+        }
+
+        let math = match environment {
+            Some(env) => format!("\\begin{{{env}}}\n{}\n\\end{{{env}}}", text.trim()),
+            None => text.to_string(),
+        };
+
+        if !attr.identifier.is_empty() {
+            let url = format!("#{}", attr.identifier);
+            self.context.add_document_link(
+                attr.identifier.clone(),
+                DocumentLinkType::Equation,
+                url,
+            );
+        }
+
+        self.document.add_block(Block::MathBlock(attr, math));
+    }
+}
+
+/// Returns whether the carryover tag marks the element as private (`#private` or
+/// `#export.ignore`).
+fn is_private_tag(name: &str) -> bool {
+    matches!(name, "private" | "export.ignore")
+}
+
+/// A carryover tag (`#name parameters` or `+name parameters`) that applies to the element
+/// following it.
+pub struct CarryoverTag<'source> {
+    pub name: &'source str,
+    pub parameters: Vec<Cow<'source, str>>,
+}
+
+impl CarryoverTag<'_> {
+    /// Adds the attribute defined by the tag to `attr`.
+    ///
+    /// - `#id name` sets the identifier.
+    /// - `#class a b` adds the classes `a` and `b`.
+    /// - `#style Name` sets the `custom-style` attribute used by pandoc's docx writer.
+    /// - Tags with no parameters (`#name`) add a class with the tag name.
+    /// - Any other tag (`#name value`) adds a `name=value` key-value pair.
+    ///
+    /// Returns an error message if the tag is invalid.
+    fn apply_to(self, attr: &mut Attr) -> Result<(), &'static str> {
+        match self.name {
+            "id" => match self.parameters.into_iter().next() {
+                Some(id) => attr.identifier = id.into_owned(),
+                None => return Err("Carryover tag 'id' expects a parameter"),
+            },
+            "class" => attr
+                .classes
+                .extend(self.parameters.into_iter().map(Cow::into_owned)),
+            "style" if !self.parameters.is_empty() => attr
+                .attributes
+                .push((String::from("custom-style"), self.parameters.join(" "))),
+            name if self.parameters.is_empty() => attr.classes.push(name.to_string()),
+            name => attr
+                .attributes
+                .push((name.to_string(), self.parameters.join(" "))),
+        }
+
+        Ok(())
+    }
+
+    /// Adds the attributes defined by a strong carryover tag before a heading to the `Div` of
+    /// it's section.
+    ///
+    /// The `id`, `class` and `style` tags work like in [`CarryoverTag::apply_to`], any other tag
+    /// adds a class with it's name and it's parameters are converted like the parameters of
+    /// ranged tags (see [`parameters_attr`]).
+    fn apply_to_section(self, attr: &mut Attr) -> Result<(), &'static str> {
+        if let "id" | "class" | "style" = self.name {
+            return self.apply_to(attr);
+        }
+
+        let mut parameters = parameters_attr(&self.parameters);
+        attr.classes.push(self.name.to_string());
+        attr.classes.append(&mut parameters.classes);
+        attr.attributes.append(&mut parameters.attributes);
+        if !parameters.identifier.is_empty() {
+            attr.identifier = parameters.identifier;
+        }
+
+        Ok(())
+    }
+}
+
+/// Converts the parameters of a tag to attributes, `key=value` parameters are key-value pairs
+/// (except `id=name` which sets the identifier) and the other parameters are classes.
+pub(crate) fn parameters_attr(parameters: &[Cow<str>]) -> Attr {
+    let mut attr = Attr::default();
+
+    for parameter in parameters {
+        match parameter.split_once('=') {
+            Some(("id", id)) => attr.identifier = id.to_string(),
+            Some((key, value)) => attr.attributes.push((key.to_string(), value.to_string())),
+            None => attr.classes.push(parameter.to_string()),
+        }
+    }
+
+    attr
+}
+
+/// Splits the text of a tag's parameters into the individual parameters.
+///
+/// Parameters are separated by whitespace, a parameter can contain whitespace if it's surrounded
+/// by double quotes (`"`), and a backslash (`\`) escapes the next character (which allows quotes
+/// and whitespace to be part of a parameter).
+fn split_parameters(text: &str) -> Vec<Cow<'_, str>> {
+    let mut parameters = Vec::new();
+    let mut chars = text.char_indices().peekable();
+
+    while let Some(&(start, char)) = chars.peek() {
+        if char.is_whitespace() {
+            chars.next();
+            continue;
+        }
+
+        let mut end = text.len();
+        // Parameters without any quotes or escapes are borrowed from the source, the others
+        // need to be built character by character
+        let mut owned: Option<String> = None;
+
+        while let Some((idx, char)) = chars.next() {
+            match char {
+                c if c.is_whitespace() => {
+                    end = idx;
+                    break;
+                }
+                '\\' => {
+                    let param = owned.get_or_insert_with(|| text[start..idx].to_string());
+                    param.extend(chars.next().map(|(_, c)| c));
+                }
+                '"' => {
+                    let param = owned.get_or_insert_with(|| text[start..idx].to_string());
+
+                    while let Some((_, char)) = chars.next() {
+                        match char {
+                            '"' => break,
+                            '\\' => param.extend(chars.next().map(|(_, c)| c)),
+                            c => param.push(c),
+                        }
+                    }
+                }
+                c => {
+                    if let Some(param) = owned.as_mut() {
+                        param.push(c);
+                    }
+                }
+            }
+        }
+
+        parameters.push(match owned {
+            Some(param) => Cow::Owned(param),
+            None => Cow::Borrowed(&text[start..end]),
+        });
+    }
+
+    parameters
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parameters_attr, split_parameters, CarryoverTag};
+    use pandoc_types::definition::Attr;
+    use std::borrow::Cow;
+
+    #[test]
+    fn carryover_attributes() {
+        let mut attr = Attr::default();
+        let tags = [
+            ("id", vec!["intro"]),
+            ("class", vec!["a", "b"]),
+            ("style", vec!["Block", "Text"]),
+            ("important", vec![]),
+            ("lang", vec!["pt"]),
+        ];
+
+        for (name, parameters) in tags {
+            let parameters = parameters.into_iter().map(Cow::Borrowed).collect();
+            CarryoverTag { name, parameters }
+                .apply_to(&mut attr)
+                .unwrap();
+        }
+
+        assert_eq!(attr.identifier, "intro");
+        assert_eq!(attr.classes, vec!["a", "b", "important"]);
+        assert_eq!(
+            attr.attributes,
+            vec![
+                (String::from("custom-style"), String::from("Block Text")),
+                (String::from("lang"), String::from("pt"))
+            ]
+        );
+    }
+
+    #[test]
+    fn section_attributes() {
+        let mut attr = Attr::default();
+        let tags = [("id", vec!["intro"]), ("aside", vec!["wide", "author=me"])];
+
+        for (name, parameters) in tags {
+            let parameters = parameters.into_iter().map(Cow::Borrowed).collect();
+            CarryoverTag { name, parameters }
+                .apply_to_section(&mut attr)
+                .unwrap();
+        }
+
+        assert_eq!(attr.identifier, "intro");
+        assert_eq!(attr.classes, vec!["aside", "wide"]);
+        assert_eq!(
+            attr.attributes,
+            vec![(String::from("author"), String::from("me"))]
+        );
+    }
+
+    #[test]
+    fn attributes_from_parameters() {
+        let parameters = split_parameters("python title=example.py linenos=true id=main");
+        let attr = parameters_attr(&parameters);
+
+        assert_eq!(attr.identifier, "main");
+        assert_eq!(attr.classes, vec!["python"]);
+        assert_eq!(
+            attr.attributes,
+            vec![
+                (String::from("title"), String::from("example.py")),
+                (String::from("linenos"), String::from("true"))
+            ]
+        );
+    }
+
+    #[test]
+    fn simple_parameters() {
+        assert_eq!(split_parameters("rust"), vec!["rust"]);
+        assert_eq!(split_parameters("  image  png "), vec!["image", "png"]);
+        assert!(split_parameters("").is_empty());
+    }
+
+    #[test]
+    fn quoted_parameters() {
+        assert_eq!(
+            split_parameters(r#"image "A caption with spaces" path"#),
+            vec!["image", "A caption with spaces", "path"]
+        );
+        assert_eq!(split_parameters(r#"title="a b""#), vec!["title=a b"]);
+        assert_eq!(split_parameters(r#""" x"#), vec!["", "x"]);
+    }
+
+    #[test]
+    fn escaped_parameters() {
+        assert_eq!(
+            split_parameters(r#"my\ file.txt "say \"hi\"""#),
+            vec!["my file.txt", r#"say "hi""#]
+        );
+        assert_eq!(split_parameters(r"a\\b"), vec![r"a\b"]);
     }
 }
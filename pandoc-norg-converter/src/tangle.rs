@@ -0,0 +1,253 @@
+//! Extraction of the code blocks of a document into files (tangling), like neorg's
+//! `core.tangle` module
+//!
+//! The file of each code block is chosen by (in order of priority):
+//!
+//! - A `#tangle <path>` carryover tag (or a `tangle=<path>` parameter of the `@code` tag),
+//!   `#tangle.append <path>` also marks the file to be appended to instead of overwritten.
+//! - The `tangle` field of the document's metadata, which is either the path of the file of all
+//!   the code blocks, a list of paths (the code blocks are written to the path with their language
+//!   as extension) or an object with a `languages` object mapping languages to paths.
+//!
+//! Code blocks with the `#tangle.none` carryover tag are never tangled, and if the metadata
+//! object has `scope: tagged` only the code blocks with a `#tangle` tag are tangled.
+
+use std::collections::HashMap;
+
+use pandoc_types::definition::{Attr, MetaValue};
+
+use crate::ir::Block;
+use crate::stringify::inlines_to_string;
+
+/// A file produced by tangling a document, see [`Frontend::tangle`].
+///
+/// [`Frontend::tangle`]: crate::Frontend::tangle
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TangledFile {
+    /// The path of the file as written in the document, relative paths are relative to the
+    /// document's directory.
+    pub path: String,
+    /// The content of the code blocks of the file in the document order, separated by an empty
+    /// line.
+    pub content: String,
+    /// Whether the content should be appended to the file (if it exists) instead of replacing it.
+    pub append: bool,
+}
+
+/// The files of the code blocks defined by the document's metadata.
+#[derive(Default)]
+struct Targets {
+    /// The file of the code blocks of any language.
+    default: Option<String>,
+    /// The file of the code blocks of each language.
+    languages: HashMap<String, String>,
+    /// Only the code blocks with a `#tangle` tag are tangled.
+    tagged_only: bool,
+}
+
+fn meta_string(value: &MetaValue) -> Option<String> {
+    match value {
+        MetaValue::MetaString(text) => Some(text.clone()),
+        MetaValue::MetaInlines(inlines) => Some(inlines_to_string(inlines)),
+        _ => None,
+    }
+    .filter(|text| !text.is_empty())
+}
+
+impl Targets {
+    fn new(value: Option<&MetaValue>) -> Self {
+        let mut targets = Targets::default();
+
+        match value {
+            Some(MetaValue::MetaList(paths)) => {
+                for path in paths.iter().filter_map(meta_string) {
+                    if let Some((_, extension)) = path.rsplit_once('.') {
+                        targets
+                            .languages
+                            .insert(extension.to_string(), path.clone());
+                    }
+                }
+            }
+            Some(MetaValue::MetaMap(map)) => {
+                if let Some(MetaValue::MetaMap(languages)) = map.get("languages") {
+                    for (language, path) in languages {
+                        if let Some(path) = meta_string(path) {
+                            targets.languages.insert(language.clone(), path);
+                        }
+                    }
+                }
+                targets.tagged_only =
+                    map.get("scope").and_then(meta_string).as_deref() == Some("tagged");
+            }
+            Some(value) => targets.default = meta_string(value),
+            None => {}
+        }
+
+        targets
+    }
+
+    /// Returns the path of the code block's file and whether it's appended to, or `None` if the
+    /// code block isn't tangled.
+    fn file(&self, attr: &Attr) -> Option<(String, bool)> {
+        let has_class = |name: &str| attr.classes.iter().any(|class| class == name);
+        if has_class("tangle.none") {
+            return None;
+        }
+
+        for (key, value) in &attr.attributes {
+            match key.as_str() {
+                "tangle" => return Some((value.clone(), false)),
+                "tangle.append" => return Some((value.clone(), true)),
+                _ => {}
+            }
+        }
+
+        if self.tagged_only && !has_class("tangle") {
+            return None;
+        }
+
+        // The language is the first class of code blocks
+        let language = attr.classes.first();
+        let path = language
+            .and_then(|language| self.languages.get(language))
+            .or(self.default.as_ref())?;
+        Some((path.clone(), false))
+    }
+}
+
+/// Returns the files of the code blocks in `blocks` (and the blocks nested in them), in the order
+/// they first appear, `tangle` is the `tangle` field of the document's metadata.
+pub(crate) fn tangle_blocks<'a, 'source: 'a>(
+    blocks: impl IntoIterator<Item = &'a Block<'source>>,
+    tangle: Option<&MetaValue>,
+) -> Vec<TangledFile> {
+    let targets = Targets::new(tangle);
+    let mut files = Vec::new();
+
+    for block in blocks {
+        tangle_block(block, &targets, &mut files);
+    }
+
+    files
+}
+
+fn tangle_block(block: &Block, targets: &Targets, files: &mut Vec<TangledFile>) {
+    let children: Vec<&Block> = match block {
+        Block::CodeBlock(attr, code) => {
+            let Some((path, append)) = targets.file(attr) else {
+                return;
+            };
+
+            let file = match files.iter().position(|file| file.path == path) {
+                Some(idx) => &mut files[idx],
+                None => {
+                    files.push(TangledFile {
+                        path,
+                        ..Default::default()
+                    });
+                    files.last_mut().expect("The file was just added")
+                }
+            };
+
+            if !file.content.is_empty() {
+                file.content.push('\n');
+            }
+            file.content.push_str(code);
+            if !code.ends_with('\n') {
+                file.content.push('\n');
+            }
+            file.append |= append;
+            return;
+        }
+        Block::BlockQuote(blocks) | Block::Div(_, blocks) => blocks.iter().collect(),
+        Block::BulletList(entries) | Block::OrderedList(_, entries) => {
+            entries.iter().flat_map(|entry| &entry.blocks).collect()
+        }
+        Block::DefinitionList(entries) => entries.iter().flat_map(|(_, blocks)| blocks).collect(),
+        Block::Table(_, _, head, body) => head
+            .iter()
+            .chain(body)
+            .flatten()
+            .flat_map(|cell| &cell.blocks)
+            .collect(),
+        _ => return,
+    };
+
+    for child in children {
+        tangle_block(child, targets, files);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{tangle_blocks, TangledFile};
+    use crate::ir::Block;
+    use pandoc_types::definition::{Attr, MetaValue};
+    use std::collections::HashMap;
+
+    fn code(classes: &[&str], attributes: &[(&str, &str)], code: &str) -> Block<'static> {
+        let attr = Attr {
+            identifier: String::new(),
+            classes: classes.iter().map(|class| class.to_string()).collect(),
+            attributes: attributes
+                .iter()
+                .map(|(key, value)| (key.to_string(), value.to_string()))
+                .collect(),
+        };
+        Block::CodeBlock(attr, code.to_string())
+    }
+
+    #[test]
+    fn tangling() {
+        let blocks = vec![
+            code(&["lua"], &[], "print(1)"),
+            Block::Div(
+                Attr::default(),
+                vec![code(&["lua"], &[("tangle", "other.lua")], "print(2)\n")],
+            ),
+            code(&["lua", "tangle.none"], &[], "print(3)"),
+            code(&["lua"], &[], "print(4)"),
+            code(&["sh"], &[("tangle.append", "run.sh")], "echo 5"),
+        ];
+
+        let tangle = MetaValue::MetaString(String::from("init.lua"));
+        let files = tangle_blocks(&blocks, Some(&tangle));
+        assert_eq!(
+            files,
+            [
+                TangledFile {
+                    path: String::from("init.lua"),
+                    content: String::from("print(1)\n\nprint(4)\n"),
+                    append: false,
+                },
+                TangledFile {
+                    path: String::from("other.lua"),
+                    content: String::from("print(2)\n"),
+                    append: false,
+                },
+                TangledFile {
+                    path: String::from("run.sh"),
+                    content: String::from("echo 5\n"),
+                    append: true,
+                },
+            ]
+        );
+
+        let languages = HashMap::from([(
+            String::from("sh"),
+            MetaValue::MetaString(String::from("setup.sh")),
+        )]);
+        let tangle = MetaValue::MetaMap(HashMap::from([
+            (String::from("languages"), MetaValue::MetaMap(languages)),
+            (
+                String::from("scope"),
+                MetaValue::MetaString(String::from("tagged")),
+            ),
+        ]));
+        let blocks = vec![code(&["sh", "tangle"], &[], "ls"), code(&["sh"], &[], "rm")];
+        let files = tangle_blocks(&blocks, Some(&tangle));
+        assert_eq!(files.len(), 1);
+        assert_eq!(files[0].path, "setup.sh");
+        assert_eq!(files[0].content, "ls\n");
+    }
+}
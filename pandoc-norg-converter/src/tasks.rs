@@ -0,0 +1,253 @@
+//! Task queries, lists of tasks generated from the tasks collected in the document
+
+use std::borrow::Cow;
+
+use pandoc_types::definition::{Attr, Block, Inline, Target};
+
+use crate::document::{DocumentContext, Task};
+use crate::extensions::TodoStatus;
+use crate::stringify::text_to_inlines;
+
+/// A query defined by a `.tasks` infirm tag.
+///
+/// The tag's parameters are filters in the form `key=value`:
+/// - `status=undone,pending` only includes the tasks with one of the statuses.
+/// - `heading="Project X"` only includes the tasks inside the heading with that title (case
+///   insensitive), or the heading itself.
+///
+/// The query is expanded into a bullet list of the matching tasks when the document is built, so
+/// that tasks defined after the tag are also included.
+#[derive(Debug, Default)]
+pub struct TaskQuery {
+    statuses: Vec<TodoStatus>,
+    heading: Option<String>,
+}
+
+impl TaskQuery {
+    /// Parses the query defined by the tag's parameters, invalid filters are passed to `warn`.
+    pub(crate) fn parse(parameters: &[Cow<str>], mut warn: impl FnMut(String)) -> Self {
+        let mut query = TaskQuery::default();
+
+        for parameter in parameters {
+            match parameter.split_once('=') {
+                Some(("status", statuses)) => {
+                    for name in statuses.split(',') {
+                        match TodoStatus::from_name(name.trim()) {
+                            Some(status) => query.statuses.push(status),
+                            None => warn(format!("Unknown task status in query: {}", name)),
+                        }
+                    }
+                }
+                Some(("heading", heading)) => query.heading = Some(heading.to_lowercase()),
+                _ => warn(format!("Unknown task query filter: {}", parameter)),
+            }
+        }
+
+        query
+    }
+
+    fn matches(&self, task: &Task, context: &DocumentContext) -> bool {
+        if !self.statuses.is_empty() && !self.statuses.contains(&task.status) {
+            return false;
+        }
+
+        let Some(ref title) = self.heading else {
+            return true;
+        };
+
+        // The task's heading is only known to be inside the queried heading if it's the heading
+        // itself or one of the headings that follow it with a higher level
+        let Some(heading) = task.heading else {
+            return false;
+        };
+
+        context.headings[..=heading]
+            .iter()
+            .rev()
+            .scan(i32::MAX, |level, candidate| {
+                if candidate.level >= *level {
+                    return Some(false);
+                }
+
+                *level = candidate.level;
+                Some(candidate.title.to_lowercase() == *title)
+            })
+            .any(|found| found)
+    }
+
+    /// Returns the bullet list with the tasks matching the query.
+    pub(crate) fn into_pandoc(self, context: &DocumentContext) -> Block {
+        let mut items = Vec::new();
+
+        for task in &context.tasks {
+            if !self.matches(task, context) {
+                continue;
+            }
+
+            let symbol = context.config.todo_symbols.symbol(task.status);
+            let mut inlines = vec![Inline::Str(symbol.to_string()), Inline::Space];
+
+            let text = text_to_inlines(&task.text);
+
+            match task.heading.map(|idx| &context.headings[idx]) {
+                Some(heading) if !heading.id.is_empty() => inlines.push(Inline::Link(
+                    Attr::default(),
+                    text,
+                    Target {
+                        url: format!("#{}", heading.id),
+                        title: String::new(),
+                    },
+                )),
+                _ => inlines.extend(text),
+            }
+
+            items.push(vec![Block::Plain(inlines)]);
+        }
+
+        if items.is_empty() {
+            return Block::Null;
+        }
+
+        Block::BulletList(items)
+    }
+}
+
+/// A task with a start or due date, see [`Frontend::dated_tasks`].
+///
+/// [`Frontend::dated_tasks`]: crate::Frontend::dated_tasks
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DatedTask {
+    pub status: TodoStatus,
+    /// The first line of the task's text as written in the source.
+    pub text: String,
+    /// The plain text of the title of the heading containing the task, or of the heading itself
+    /// if the status belongs to one (if any).
+    pub heading: Option<String>,
+    /// The ISO 8601 start date, with the time if it has one (`2023-04-01` or `2023-04-01T16:00`).
+    pub start: Option<String>,
+    /// The ISO 8601 due date, with the time if it has one.
+    pub due: Option<String>,
+}
+
+/// Returns the tasks of the document with a start or due date, in the order they were defined.
+pub(crate) fn dated_tasks(context: &DocumentContext) -> Vec<DatedTask> {
+    context
+        .tasks
+        .iter()
+        .filter(|task| task.start.is_some() || task.due.is_some())
+        .map(|task| DatedTask {
+            status: task.status,
+            text: task.text.clone(),
+            heading: task.heading.map(|idx| context.headings[idx].title.clone()),
+            start: task.start.map(|date| date.iso()),
+            due: task.due.map(|date| date.iso()),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{dated_tasks, DatedTask, TaskQuery};
+    use crate::document::{DocumentContext, Heading, Task};
+    use crate::extensions::TodoStatus;
+    use crate::timestamps::Timestamp;
+    use crate::Config;
+    use pandoc_types::definition::Block;
+    use std::borrow::Cow;
+
+    #[test]
+    fn query_filters() {
+        let config = Config::default();
+        let mut context = DocumentContext::new(&config);
+
+        for (title, level) in [("Project X", 1), ("Nested", 2), ("Other", 1)] {
+            context.headings.push(Heading {
+                title: title.to_string(),
+                id: title.to_string(),
+                level,
+            });
+        }
+
+        for (status, heading) in [
+            (TodoStatus::Undone, Some(0)),
+            (TodoStatus::Done, Some(1)),
+            (TodoStatus::Undone, Some(1)),
+            (TodoStatus::Undone, Some(2)),
+            (TodoStatus::Undone, None),
+        ] {
+            context.tasks.push(Task {
+                status,
+                text: String::from("Task"),
+                section: None,
+                heading,
+                start: None,
+                due: None,
+            });
+        }
+
+        let count = |parameters: &[&str]| {
+            let parameters: Vec<_> = parameters.iter().map(|p| Cow::Borrowed(*p)).collect();
+            match TaskQuery::parse(&parameters, |_| {}).into_pandoc(&context) {
+                Block::BulletList(items) => items.len(),
+                _ => 0,
+            }
+        };
+
+        assert_eq!(count(&[]), 5);
+        assert_eq!(count(&["status=undone"]), 4);
+        assert_eq!(count(&["status=done,undone", "heading=project x"]), 3);
+        assert_eq!(count(&["status=undone", "heading=Nested"]), 1);
+        assert_eq!(count(&["status=on-hold"]), 0);
+    }
+
+    #[test]
+    fn dated() {
+        let config = Config::default();
+        let mut context = DocumentContext::new(&config);
+        context.headings.push(Heading {
+            title: String::from("Project X"),
+            id: String::from("project-x"),
+            level: 1,
+        });
+
+        for (text, heading, start, due) in [
+            ("Undated", Some(0), None, None),
+            ("Due", Some(0), None, Some("2024-06-01")),
+            (
+                "Both",
+                None,
+                Some("Sat 1st Jun 2024 16:00"),
+                Some("2024-06-03"),
+            ),
+        ] {
+            context.tasks.push(Task {
+                status: TodoStatus::Undone,
+                text: text.to_string(),
+                section: None,
+                heading,
+                start: start.and_then(Timestamp::parse),
+                due: due.and_then(Timestamp::parse),
+            });
+        }
+
+        assert_eq!(
+            dated_tasks(&context),
+            vec![
+                DatedTask {
+                    status: TodoStatus::Undone,
+                    text: String::from("Due"),
+                    heading: Some(String::from("Project X")),
+                    start: None,
+                    due: Some(String::from("2024-06-01")),
+                },
+                DatedTask {
+                    status: TodoStatus::Undone,
+                    text: String::from("Both"),
+                    heading: None,
+                    start: Some(String::from("2024-06-01T16:00")),
+                    due: Some(String::from("2024-06-03")),
+                },
+            ]
+        );
+    }
+}
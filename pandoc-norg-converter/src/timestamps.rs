@@ -0,0 +1,294 @@
+//! Timestamps (`{@ 2023-04-01}` links and the `created` and `updated` metadata fields)
+//!
+//! Both ISO 8601 dates (`2023-04-01`, `2023-04-01T16:00`) and neorg's timestamps
+//! (`Sat 1st Apr 2023 16:00 CET`) are understood, the timezone (if any) is ignored.
+
+use pandoc_types::definition::{Attr, Inline as PandocInline};
+#[cfg(feature = "serde")]
+use serde::Deserialize;
+
+use crate::document::DocumentContext;
+use crate::ir::{convert_inlines_to_pandoc, Inline};
+use crate::stringify::text_to_inlines;
+
+const MONTHS: [&str; 12] = [
+    "January",
+    "February",
+    "March",
+    "April",
+    "May",
+    "June",
+    "July",
+    "August",
+    "September",
+    "October",
+    "November",
+    "December",
+];
+
+const WEEKDAYS: [&str; 7] = [
+    "Monday",
+    "Tuesday",
+    "Wednesday",
+    "Thursday",
+    "Friday",
+    "Saturday",
+    "Sunday",
+];
+
+/// How timestamps (`{@ 2023-04-01}`) are rendered, see [`Config::timestamps`].
+///
+/// [`Config::timestamps`]: crate::Config::timestamps
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum TimestampStyle {
+    /// The text of the timestamp as written in the document (default).
+    #[default]
+    Text,
+    /// The timestamp formatted with a `strftime` like format, the supported specifiers are
+    /// `%Y`, `%m`, `%d`, `%e`, `%B`, `%b`, `%A`, `%a`, `%H`, `%M`, `%F` and `%%`.
+    Format(String),
+    /// A `Span` with the `timestamp` class and the ISO 8601 date in it's `datetime` attribute.
+    Span,
+}
+
+/// A date with an optional time (hours and minutes).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct Timestamp {
+    year: i32,
+    month: u32,
+    day: u32,
+    time: Option<(u32, u32)>,
+}
+
+impl Timestamp {
+    /// Parses an ISO 8601 date or a neorg timestamp, returns `None` if the text isn't a date.
+    pub fn parse(text: &str) -> Option<Self> {
+        let text = text.trim();
+        Self::parse_iso(text).or_else(|| Self::parse_norg(text))
+    }
+
+    fn parse_iso(text: &str) -> Option<Self> {
+        let date = text.get(..10)?;
+        let mut parts = date.split('-');
+        let year = parts.next().filter(|year| year.len() == 4)?.parse().ok()?;
+        let month = parts
+            .next()
+            .filter(|month| month.len() == 2)?
+            .parse()
+            .ok()?;
+        let day = parts.next().filter(|day| day.len() == 2)?.parse().ok()?;
+
+        let rest = &text[10..];
+        let time = match rest.chars().next() {
+            None => None,
+            Some('T' | ' ') => parse_time(&rest[1..]),
+            Some(_) => return None,
+        };
+
+        Self::new(year, month, day, time)
+    }
+
+    fn parse_norg(text: &str) -> Option<Self> {
+        let mut day = None;
+        let mut month = None;
+        let mut year = None;
+        let mut time = None;
+
+        for token in text.split(|c: char| c.is_whitespace() || c == ',') {
+            if token.is_empty() {
+                continue;
+            }
+
+            if token.contains(':') {
+                time = time.or(parse_time(token));
+            } else if token.starts_with(|c: char| c.is_ascii_digit()) {
+                let number = token.trim_end_matches(|c: char| c.is_ascii_alphabetic());
+                let number: u32 = number.parse().ok()?;
+
+                match day {
+                    None if token.len() <= 4 && number <= 31 => day = Some(number),
+                    _ => year = Some(number as i32),
+                }
+            } else if month.is_none() && token.len() >= 3 {
+                month = MONTHS
+                    .iter()
+                    .position(|name| name.to_lowercase().starts_with(&token.to_lowercase()))
+                    .map(|idx| idx as u32 + 1);
+            }
+        }
+
+        Self::new(year?, month?, day?, time)
+    }
+
+    fn new(year: i32, month: u32, day: u32, time: Option<(u32, u32)>) -> Option<Self> {
+        let valid = (1..=12).contains(&month) && day >= 1 && day <= days_in_month(year, month);
+        valid.then_some(Self {
+            year,
+            month,
+            day,
+            time,
+        })
+    }
+
+    /// Returns the ISO 8601 date (`2023-04-01`).
+    pub fn date(&self) -> String {
+        format!("{:04}-{:02}-{:02}", self.year, self.month, self.day)
+    }
+
+    /// Returns the ISO 8601 date and time (`2023-04-01T16:00`), or only the date if the
+    /// timestamp has no time.
+    pub fn iso(&self) -> String {
+        match self.time {
+            Some((hour, minute)) => format!("{}T{:02}:{:02}", self.date(), hour, minute),
+            None => self.date(),
+        }
+    }
+
+    /// Returns the day of the week, starting at monday (0).
+    fn weekday(&self) -> usize {
+        // Sakamoto's method, returns 0 for sunday
+        const OFFSETS: [i32; 12] = [0, 3, 2, 5, 0, 3, 5, 1, 4, 6, 2, 4];
+        let year = if self.month < 3 {
+            self.year - 1
+        } else {
+            self.year
+        };
+        let day = (year + year / 4 - year / 100
+            + year / 400
+            + OFFSETS[self.month as usize - 1]
+            + self.day as i32)
+            .rem_euclid(7);
+
+        (day as usize + 6) % 7
+    }
+
+    /// Formats the timestamp with a `strftime` like format, see [`TimestampStyle::Format`].
+    pub fn format(&self, format: &str) -> String {
+        let (hour, minute) = self.time.unwrap_or_default();
+        let month = MONTHS[self.month as usize - 1];
+        let weekday = WEEKDAYS[self.weekday()];
+        let mut out = String::with_capacity(format.len());
+        let mut chars = format.chars();
+
+        while let Some(char) = chars.next() {
+            if char != '%' {
+                out.push(char);
+                continue;
+            }
+
+            match chars.next() {
+                Some('Y') => out.push_str(&self.year.to_string()),
+                Some('m') => out.push_str(&format!("{:02}", self.month)),
+                Some('d') => out.push_str(&format!("{:02}", self.day)),
+                Some('e') => out.push_str(&self.day.to_string()),
+                Some('B') => out.push_str(month),
+                Some('b') => out.push_str(&month[..3]),
+                Some('A') => out.push_str(weekday),
+                Some('a') => out.push_str(&weekday[..3]),
+                Some('H') => out.push_str(&format!("{:02}", hour)),
+                Some('M') => out.push_str(&format!("{:02}", minute)),
+                Some('F') => out.push_str(&self.date()),
+                Some('%') => out.push('%'),
+                Some(other) => {
+                    out.push('%');
+                    out.push(other);
+                }
+                None => out.push('%'),
+            }
+        }
+
+        out
+    }
+}
+
+/// Parses a `HH:MM` time (the seconds and the timezone are ignored).
+fn parse_time(text: &str) -> Option<(u32, u32)> {
+    let mut parts = text.split(':');
+    let hour = parts.next()?.parse().ok()?;
+    let minute: String = parts.next()?.chars().take(2).collect();
+    let minute = minute.parse().ok()?;
+
+    (hour < 24 && minute < 60).then_some((hour, minute))
+}
+
+fn days_in_month(year: i32, month: u32) -> u32 {
+    match month {
+        2 if year % 4 == 0 && (year % 100 != 0 || year % 400 == 0) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+/// Returns the pandoc inline of a timestamp, the `description` (if not empty) replaces it's text.
+pub(crate) fn timestamp_to_pandoc(
+    description: Vec<Inline>,
+    text: &str,
+    context: &DocumentContext,
+) -> PandocInline {
+    let timestamp = Timestamp::parse(text);
+    if timestamp.is_none() {
+        context.warn(format!("Invalid timestamp {}", text));
+    }
+
+    let mut content = match (&context.config.timestamps, timestamp) {
+        _ if !description.is_empty() => convert_inlines_to_pandoc(description, context),
+        (TimestampStyle::Format(format), Some(timestamp)) => {
+            text_to_inlines(&timestamp.format(format))
+        }
+        _ => text_to_inlines(text.trim()),
+    };
+
+    match (&context.config.timestamps, timestamp) {
+        (TimestampStyle::Span, Some(timestamp)) => {
+            let attr = Attr {
+                classes: vec![String::from("timestamp")],
+                attributes: vec![(String::from("datetime"), timestamp.iso())],
+                ..Default::default()
+            };
+            PandocInline::Span(attr, content)
+        }
+        _ if content.len() == 1 => content.remove(0),
+        _ => PandocInline::Span(Attr::default(), content),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Timestamp;
+
+    #[test]
+    fn iso_timestamps() {
+        let timestamp = Timestamp::parse("2023-04-01").unwrap();
+        assert_eq!(timestamp.iso(), "2023-04-01");
+
+        let timestamp = Timestamp::parse("2023-05-01T10:20:30+0100").unwrap();
+        assert_eq!(timestamp.iso(), "2023-05-01T10:20");
+        assert_eq!(timestamp.date(), "2023-05-01");
+
+        assert_eq!(Timestamp::parse("2023-02-30"), None);
+        assert_eq!(Timestamp::parse("tomorrow"), None);
+    }
+
+    #[test]
+    fn norg_timestamps() {
+        let timestamp = Timestamp::parse("Sat 1st Apr 2023 16:00 CET").unwrap();
+        assert_eq!(timestamp.iso(), "2023-04-01T16:00");
+
+        let timestamp = Timestamp::parse("29th February 2024").unwrap();
+        assert_eq!(timestamp.iso(), "2024-02-29");
+
+        assert_eq!(Timestamp::parse("29th February 2023"), None);
+    }
+
+    #[test]
+    fn formatting() {
+        let timestamp = Timestamp::parse("2023-04-01T16:05").unwrap();
+
+        assert_eq!(timestamp.format("%A %e %B %Y"), "Saturday 1 April 2023");
+        assert_eq!(timestamp.format("%a, %d %b %H:%M"), "Sat, 01 Apr 16:05");
+        assert_eq!(timestamp.format("%F (100%%) %q"), "2023-04-01 (100%) %q");
+    }
+}
@@ -0,0 +1,94 @@
+//! Promotion of the only top level heading of a document to it's title, see
+//! [`Config::promote_title`].
+//!
+//! [`Config::promote_title`]: crate::Config::promote_title
+
+use crate::ir::{Block, Inline};
+use crate::source_map::SourceRange;
+
+/// Removes the level 1 heading of the blocks and returns it's title if it's the only one, the
+/// other headings are shifted up one level (headings nested in `Div`s included).
+pub(crate) fn take_title<'source>(
+    blocks: &mut Vec<Block<'source>>,
+    sources: &mut Vec<Option<SourceRange>>,
+) -> Option<Vec<Inline<'source>>> {
+    let mut headings = blocks
+        .iter()
+        .enumerate()
+        .filter(|(_, block)| matches!(block, Block::Header(1, ..)));
+
+    let idx = match (headings.next(), headings.next()) {
+        (Some((idx, _)), None) => idx,
+        _ => return None,
+    };
+
+    let Block::Header(_, _, title) = blocks.remove(idx) else {
+        unreachable!("The title must be a heading")
+    };
+    if idx < sources.len() {
+        sources.remove(idx);
+    }
+
+    shift_headings(blocks);
+    Some(title)
+}
+
+fn shift_headings(blocks: &mut [Block]) {
+    for block in blocks {
+        match block {
+            Block::Header(level, ..) => *level = (*level - 1).max(1),
+            Block::Div(_, blocks) => shift_headings(blocks),
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::take_title;
+    use crate::ir::{Block, Inline};
+    use pandoc_types::definition::Attr;
+
+    fn header(level: i32, title: &str) -> Block<'_> {
+        Block::Header(level, Attr::default(), vec![Inline::Str(title)])
+    }
+
+    fn levels(blocks: &[Block]) -> Vec<i32> {
+        let mut out = Vec::new();
+        for block in blocks {
+            match block {
+                Block::Header(level, ..) => out.push(*level),
+                Block::Div(_, blocks) => out.extend(levels(blocks)),
+                _ => {}
+            }
+        }
+        out
+    }
+
+    #[test]
+    fn promotion() {
+        let mut blocks = vec![
+            Block::Plain(vec![Inline::Str("intro")]),
+            header(1, "Title"),
+            header(2, "Section"),
+            Block::Div(Attr::default(), vec![header(3, "Subsection")]),
+        ];
+        let mut sources = vec![None; blocks.len()];
+
+        let title = take_title(&mut blocks, &mut sources).unwrap();
+        let mut text = String::new();
+        title.iter().for_each(|inline| inline.push_text(&mut text));
+
+        assert_eq!(text, "Title");
+        assert_eq!(levels(&blocks), [1, 2]);
+        assert_eq!(sources.len(), blocks.len());
+    }
+
+    #[test]
+    fn multiple_top_level_headings() {
+        let mut blocks = vec![header(1, "One"), header(2, "Nested"), header(1, "Two")];
+
+        assert!(take_title(&mut blocks, &mut Vec::new()).is_none());
+        assert_eq!(levels(&blocks), [1, 2, 1]);
+    }
+}
@@ -0,0 +1,116 @@
+//! Tables of contents generated from the headings of the document
+
+use std::borrow::Cow;
+
+use pandoc_types::definition::{Attr, Block, Inline, Target};
+
+use crate::document::{DocumentContext, Heading};
+use crate::stringify::text_to_inlines;
+
+/// A table of contents defined by a `.toc` infirm tag (or a `=toc` ranged tag).
+///
+/// The tag's parameters are used as the title of the table of contents (if any), the table is
+/// generated when the document is built, so that it includes all the headings of the document,
+/// as a `Div` with the `toc` class containing the title and a nested bullet list of links to the
+/// headings.
+#[derive(Debug, Default)]
+pub struct TableOfContents {
+    title: Option<String>,
+}
+
+impl TableOfContents {
+    pub(crate) fn new(parameters: &[Cow<str>]) -> Self {
+        let title = parameters.join(" ");
+
+        TableOfContents {
+            title: (!title.is_empty()).then_some(title),
+        }
+    }
+
+    /// Returns the table of contents of the document.
+    pub(crate) fn into_pandoc(self, context: &DocumentContext) -> Block {
+        let mut blocks = Vec::new();
+
+        if let Some(title) = self.title {
+            blocks.push(Block::Plain(text_to_inlines(&title)));
+        }
+
+        let items = heading_items(&context.headings);
+        if !items.is_empty() {
+            blocks.push(Block::BulletList(items));
+        }
+
+        let attr = Attr {
+            classes: vec![String::from("toc")],
+            ..Default::default()
+        };
+        Block::Div(attr, blocks)
+    }
+}
+
+/// Returns the list items of the headings, the headings with an higher level than the heading
+/// before them are nested in it's item.
+fn heading_items(headings: &[Heading]) -> Vec<Vec<Block>> {
+    let mut items = Vec::new();
+    let mut idx = 0;
+
+    while let Some(heading) = headings.get(idx) {
+        let end = headings[idx + 1..]
+            .iter()
+            .position(|next| next.level <= heading.level)
+            .map_or(headings.len(), |offset| idx + 1 + offset);
+
+        let text = text_to_inlines(&heading.title);
+        let entry = match heading.id.is_empty() {
+            true => text,
+            false => vec![Inline::Link(
+                Attr::default(),
+                text,
+                Target {
+                    url: format!("#{}", heading.id),
+                    title: String::new(),
+                },
+            )],
+        };
+
+        let mut item = vec![Block::Plain(entry)];
+        let children = heading_items(&headings[idx + 1..end]);
+        if !children.is_empty() {
+            item.push(Block::BulletList(children));
+        }
+
+        items.push(item);
+        idx = end;
+    }
+
+    items
+}
+
+#[cfg(test)]
+mod tests {
+    use super::heading_items;
+    use crate::document::Heading;
+    use pandoc_types::definition::Block;
+
+    #[test]
+    fn nested_headings() {
+        let headings: Vec<_> = [("A", 1), ("B", 2), ("C", 3), ("D", 2), ("E", 1)]
+            .into_iter()
+            .map(|(title, level)| Heading {
+                title: title.to_string(),
+                id: title.to_string(),
+                level,
+            })
+            .collect();
+
+        let items = heading_items(&headings);
+        assert_eq!(items.len(), 2);
+
+        let Block::BulletList(ref nested) = items[0][1] else {
+            panic!("Expected the nested headings of A");
+        };
+        assert_eq!(nested.len(), 2);
+        assert_eq!(nested[0].len(), 2);
+        assert_eq!(items[1].len(), 1);
+    }
+}
@@ -0,0 +1,136 @@
+//! Transforms of the intermediate representation (requires the `ir` feature)
+
+use std::sync::PoisonError;
+
+use crate::ir::{Block, Inline};
+use crate::Frontend;
+
+/// A transform applied to the intermediate representation of each document before it's converted
+/// to pandoc, see [`Frontend::add_transform`].
+///
+/// The default implementations of the methods visit the children of the element (with
+/// [`walk_block`] and [`walk_inline`]), so implementations only need to override the methods for
+/// the elements they change and call the walk function to keep visiting the children.
+///
+/// # Example
+///
+/// ```rust
+/// use pandoc_norg_converter::{ir::Inline, walk_inline, Frontend, Transform};
+///
+/// /// Removes the TODO statuses from the document.
+/// struct StripTodos;
+///
+/// impl Transform for StripTodos {
+///     fn transform_inline(&mut self, inline: &mut Inline) {
+///         if let Inline::TodoStatus(_) = inline {
+///             *inline = Inline::Space;
+///         }
+///
+///         walk_inline(self, inline)
+///     }
+/// }
+///
+/// let mut frontend = Frontend::default();
+/// frontend.add_transform(StripTodos);
+/// ```
+pub trait Transform: Send {
+    /// Transforms a block of the document.
+    fn transform_block(&mut self, block: &mut Block) {
+        walk_block(self, block)
+    }
+
+    /// Transforms an inline of the document.
+    fn transform_inline(&mut self, inline: &mut Inline) {
+        walk_inline(self, inline)
+    }
+}
+
+/// Applies the transform to the children (blocks and inlines) of the block.
+pub fn walk_block<T: Transform + ?Sized>(transform: &mut T, block: &mut Block) {
+    match block {
+        Block::Plain(segment) | Block::Header(_, _, segment) => walk_inlines(transform, segment),
+        Block::Paragraph(segments) => {
+            for segment in segments {
+                walk_inlines(transform, segment);
+            }
+        }
+        Block::BlockQuote(blocks) | Block::Div(_, blocks) => walk_blocks(transform, blocks),
+        Block::Table(_, _, head, body) => {
+            for cell in head.iter_mut().flatten().chain(body.iter_mut().flatten()) {
+                walk_blocks(transform, &mut cell.blocks);
+            }
+        }
+        Block::BulletList(entries) | Block::OrderedList(_, entries) => {
+            for entry in entries {
+                walk_blocks(transform, &mut entry.blocks);
+            }
+        }
+        Block::DefinitionList(entries) => {
+            for (term, blocks) in entries {
+                walk_inlines(transform, term);
+                walk_blocks(transform, blocks);
+            }
+        }
+        Block::Null
+        | Block::MathBlock(_, _)
+        | Block::HorizontalRule
+        | Block::Figure(_, _)
+        | Block::CodeBlock(_, _)
+        | Block::Raw(_, _)
+        | Block::TaskQuery(_)
+        | Block::TableOfContents(_)
+        | Block::CategoryLinks(_) => {}
+    }
+}
+
+/// Applies the transform to the children of the inline.
+pub fn walk_inline<T: Transform + ?Sized>(transform: &mut T, inline: &mut Inline) {
+    match inline {
+        Inline::Emph(inlines)
+        | Inline::Strong(inlines)
+        | Inline::Underline(inlines)
+        | Inline::Strikeout(inlines)
+        | Inline::Subscript(inlines)
+        | Inline::Superscript(inlines)
+        | Inline::SmallCaps(inlines)
+        | Inline::Span(inlines, _)
+        | Inline::Link(inlines, _)
+        | Inline::Anchor(inlines, _)
+        | Inline::Cite(_, inlines)
+        | Inline::Identified(inlines, _)
+        | Inline::Timestamp(inlines, _) => walk_inlines(transform, inlines),
+        Inline::Space
+        | Inline::Str(_)
+        | Inline::Code(_)
+        | Inline::Math(_)
+        | Inline::Variable(_)
+        | Inline::Comment(_)
+        | Inline::TodoStatus(_)
+        | Inline::Image(_) => {}
+    }
+}
+
+/// Applies the transform to each of the blocks.
+pub(crate) fn walk_blocks<T: Transform + ?Sized>(transform: &mut T, blocks: &mut [Block]) {
+    for block in blocks {
+        transform.transform_block(block);
+    }
+}
+
+/// Applies the transform to each of the inlines.
+pub(crate) fn walk_inlines<T: Transform + ?Sized>(transform: &mut T, inlines: &mut [Inline]) {
+    for inline in inlines {
+        transform.transform_inline(inline);
+    }
+}
+
+impl Frontend {
+    /// Adds a transform that's applied to the intermediate representation of the documents
+    /// converted after this call, the transforms are applied in the order they were added.
+    pub fn add_transform(&mut self, transform: impl Transform + 'static) {
+        self.transforms
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .push(Box::new(transform));
+    }
+}
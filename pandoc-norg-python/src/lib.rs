@@ -0,0 +1,43 @@
+//! Python bindings of [`pandoc_norg_converter`], built with [`maturin`]:
+//!
+//! ```sh
+//! cd pandoc-norg-python && maturin build --release
+//! ```
+//!
+//! ```python
+//! import pandoc_norg
+//!
+//! document = pandoc_norg.convert("* A heading", {"id_style": "pandoc"})
+//! print(document["blocks"])
+//! ```
+//!
+//! [`maturin`]: https://www.maturin.rs/
+
+use pandoc_norg_converter::{Config, Frontend};
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use pyo3::types::PyDict;
+
+/// Converts the neorg source code to the pandoc AST as Python dictionaries and lists (the same
+/// structure as pandoc's json format).
+///
+/// The keys of the `config` dictionary are the fields of the converter's `Config`, missing
+/// fields use their default value.
+#[pyfunction]
+#[pyo3(signature = (source, config = None))]
+fn convert(py: Python<'_>, source: &str, config: Option<&PyDict>) -> PyResult<PyObject> {
+    let config: Config = match config {
+        None => Config::default(),
+        Some(config) => pythonize::depythonize(config.as_ref())
+            .map_err(|err| PyValueError::new_err(format!("Invalid configuration: {err}")))?,
+    };
+
+    let document = Frontend::new(config).convert(source);
+    Ok(pythonize::pythonize(py, &document)?)
+}
+
+#[pymodule]
+fn pandoc_norg(_py: Python<'_>, module: &PyModule) -> PyResult<()> {
+    module.add_function(wrap_pyfunction!(convert, module)?)?;
+    Ok(())
+}
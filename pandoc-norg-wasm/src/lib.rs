@@ -0,0 +1,27 @@
+//! WebAssembly bindings of [`pandoc_norg_converter`], to convert neorg documents in the browser.
+//!
+//! The bindings are built with [`wasm-pack`], which requires a C compiler that targets
+//! WebAssembly (like clang) to build the tree-sitter grammar:
+//!
+//! ```sh
+//! wasm-pack build pandoc-norg-wasm --target web
+//! ```
+//!
+//! [`wasm-pack`]: https://rustwasm.github.io/wasm-pack/
+
+use pandoc_norg_converter::Frontend;
+use serde::Serialize;
+use wasm_bindgen::prelude::*;
+
+/// Converts the neorg source code to the pandoc AST, the returned object is the same as the one
+/// of pandoc's json format (`pandoc -f json`).
+#[wasm_bindgen]
+pub fn convert(source: &str) -> Result<JsValue, JsError> {
+    let document = Frontend::default().convert(source);
+
+    // Maps are serialized as plain objects instead of `Map`s, like `JSON.parse` would
+    let serializer = serde_wasm_bindgen::Serializer::json_compatible();
+    document
+        .serialize(&serializer)
+        .map_err(|err| JsError::new(&err.to_string()))
+}
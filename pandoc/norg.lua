@@ -0,0 +1,17 @@
+-- Pandoc custom reader for neorg documents, which converts them with `pandoc-norg-rs` (that must
+-- be in the PATH):
+--
+--     pandoc -f pandoc/norg.lua notes.norg -o notes.html
+--
+-- Extra arguments for `pandoc-norg-rs` (like `--id-style pandoc` or `--config config.json`) can
+-- be passed in the `PANDOC_NORG_ARGS` environment variable, separated by spaces.
+
+function Reader(input, opts)
+  local args = {}
+  for arg in string.gmatch(os.getenv("PANDOC_NORG_ARGS") or "", "%S+") do
+    table.insert(args, arg)
+  end
+
+  local json = pandoc.pipe("pandoc-norg-rs", args, tostring(input))
+  return pandoc.read(json, "json", opts)
+end
@@ -1,41 +1,1225 @@
-use clap::Parser;
+use clap::{CommandFactory, Parser, Subcommand, ValueEnum};
+use pandoc_types::definition::{Attr, Block, Inline, MetaValue, Pandoc};
 use std::{
+    collections::{BTreeMap, HashMap},
     fs,
-    io::{self, Read},
+    io::{self, Read, Write},
     path::{Path, PathBuf},
+    process::{self, Stdio},
+    time::SystemTime,
 };
 
 /// Converts a neorg file to pandoc json
 #[derive(Parser, Debug)]
+#[command(name = "pandoc-norg-rs", args_conflicts_with_subcommands = true)]
 struct Args {
-    /// Path of the neorg file to process
-    file: Option<PathBuf>,
+    #[command(subcommand)]
+    command: Option<Command>,
+
+    /// Paths of the neorg files to process (`-` or no path reads from stdin). Files named like a
+    /// subcommand (`generate`, `tangle`, `calendar` or `help`) must be passed after `--` or with a
+    /// directory (like `./generate`), otherwise the subcommand is run
+    files: Vec<PathBuf>,
+
+    /// Increases the logging verbosity (`-v` shows debug messages, `-vv` shows trace messages)
+    #[arg(
+        short,
+        long,
+        global = true,
+        action = clap::ArgAction::Count,
+        conflicts_with = "quiet"
+    )]
+    verbose: u8,
+
+    /// Only report errors
+    #[arg(short, long, global = true)]
+    quiet: bool,
+
+    /// Outputs the document's flashcards (definitions and uncertain `(?)` list items with nested
+    /// answers) in the given format instead of the pandoc json
+    #[arg(long, value_name = "FORMAT", conflicts_with = "to")]
+    flashcards: Option<FlashcardFormat>,
+
+    /// Outputs the tree-sitter syntax tree of the document (as an S-expression) instead of the
+    /// pandoc json, useful to report bugs about unhandled nodes
+    #[arg(long, conflicts_with_all = ["to", "flashcards"])]
+    dump_tree: bool,
+
+    /// Outputs the debug representation of the converter's intermediate representation of the
+    /// document instead of the pandoc json (after the syntax tree if `--dump-tree` is also passed)
+    #[arg(long, conflicts_with_all = ["to", "flashcards"])]
+    dump_ir: bool,
+
+    /// Converts the output to the given format (for example `html`) by piping it to `pandoc`,
+    /// which must be installed. `markdown-native` uses the built-in markdown writer instead,
+    /// which doesn't need pandoc
+    #[arg(short, long, value_name = "FORMAT")]
+    to: Option<String>,
+
+    /// Writes the output to the given file instead of stdout
+    #[arg(short, long, value_name = "FILE", conflicts_with = "output_template")]
+    output: Option<PathBuf>,
+
+    /// Writes the output of each file to the path produced by the template instead of stdout
+    /// (required to process multiple files). `{dir}` and `{stem}` are replaced with the input's
+    /// directory and file name without the extension, `{ext}` with the extension of the output
+    /// format (`json` or `csv`, or the usual extension of the format passed to `--to`, like `md`
+    /// for `markdown`), for example `{dir}/{stem}.{ext}`
+    #[arg(long, value_name = "TEMPLATE")]
+    output_template: Option<String>,
+
+    /// Only converts the heading with the given path of titles separated by `/` (for example
+    /// `Chapters/Introduction`) and it's content
+    #[arg(long, value_name = "PATH")]
+    section: Option<String>,
+
+    /// Path of a json file with the converter's configuration, the fields of the file are the
+    /// fields of the `Config` structure of the `pandoc-norg-converter` library (for example
+    /// `{ "todo_symbols": { "done": "✓" } }`), missing fields use their default value
+    #[arg(long, global = true, value_name = "FILE")]
+    config: Option<PathBuf>,
+
+    /// Shifts the level of all the headings by the given amount (like pandoc's flag of the same
+    /// name), overriding the configuration
+    #[arg(
+        long,
+        global = true,
+        value_name = "NUMBER",
+        allow_negative_numbers = true
+    )]
+    shift_heading_level_by: Option<i32>,
+
+    /// The algorithm used to generate the identifiers of the headings, overriding the
+    /// configuration
+    #[arg(long, global = true, value_name = "STYLE")]
+    id_style: Option<IdStyle>,
+
+    /// Converts all the `.norg` files in the directory (and it's subdirectories), the outputs are
+    /// written to the `--out-dir` with the same layout. The files share the identifiers, so
+    /// links between them don't collide
+    #[arg(
+        long,
+        value_name = "DIR",
+        requires = "out_dir",
+        conflicts_with_all = ["files", "output", "output_template"]
+    )]
+    in_dir: Option<PathBuf>,
+
+    /// The directory where the outputs of the files in `--in-dir` are written
+    #[arg(long, value_name = "DIR", requires = "in_dir")]
+    out_dir: Option<PathBuf>,
+
+    /// Also writes a list of the converted files (`sitemap.json` or `sitemap.xml`) to the
+    /// `--out-dir`, with the path of each output, it's title (the `title` metadata field or the
+    /// file name) and when it was last modified (the `date` metadata field or the modification
+    /// date of the file)
+    #[arg(long, value_name = "FORMAT", requires = "in_dir")]
+    sitemap: Option<SitemapFormat>,
+
+    /// Also writes a `redirects.json` file to the `--out-dir`, mapping the identifiers of the
+    /// converted files to the output that contains them (like `chapters/intro.html#Setup`). It can
+    /// be used to redirect the links to the sections of a document that was split into many files.
+    /// Only available with `--in-dir`, where each converted file is a chapter of the output
+    #[arg(long, requires = "in_dir")]
+    redirects: bool,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+enum FlashcardFormat {
+    /// Comma separated values with the front and back of each card (importable in Anki)
+    Csv,
+    /// A json array of objects with the `front` and `back` of each card
+    Json,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+enum SitemapFormat {
+    /// A json array of objects with the `path`, `title` and `modified` date of each output
+    Json,
+    /// A `sitemap` element with a `document` element for each output, with the same fields as
+    /// the json format
+    Xml,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+enum IdStyle {
+    /// The text with whitespace replaced with `-`
+    Raw,
+    /// The same identifiers as pandoc's `auto_identifiers` extension
+    Pandoc,
+    /// The same identifiers as GitHub
+    Github,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Generates files used to integrate the program with the system
+    Generate {
+        #[command(subcommand)]
+        target: GenerateTarget,
+    },
+    /// Writes the code blocks of the neorg files to the files set by their `#tangle` tags or the
+    /// `tangle` metadata field, relative paths are relative to the directory of the neorg file
+    Tangle {
+        /// Paths of the neorg files to tangle
+        #[arg(required = true)]
+        files: Vec<PathBuf>,
+
+        /// Only prints the paths of the files that would be written
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// Outputs an iCalendar file with the tasks of the neorg files that have a start or due date
+    /// (`(> 2024-06-01)` or `(< 2024-06-01)`), to import them in calendar applications
+    Calendar {
+        /// Paths of the neorg files, the `.norg` files in directories (and their subdirectories)
+        /// are also included
+        #[arg(required = true)]
+        files: Vec<PathBuf>,
+
+        /// Writes the calendar to the given file instead of stdout
+        #[arg(short, long, value_name = "FILE")]
+        output: Option<PathBuf>,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum GenerateTarget {
+    /// Outputs the completion script for the given shell
+    Completions {
+        /// The shell to generate completions for
+        shell: clap_complete::Shell,
+    },
+    /// Outputs the roff manpage
+    Man,
+}
+
+impl FlashcardFormat {
+    fn extension(self) -> &'static str {
+        match self {
+            FlashcardFormat::Csv => "csv",
+            FlashcardFormat::Json => "json",
+        }
+    }
+}
+
+impl SitemapFormat {
+    fn extension(self) -> &'static str {
+        match self {
+            SitemapFormat::Json => "json",
+            SitemapFormat::Xml => "xml",
+        }
+    }
+}
+
+impl From<IdStyle> for pandoc_norg_converter::IdStyle {
+    fn from(style: IdStyle) -> Self {
+        match style {
+            IdStyle::Raw => Self::Raw,
+            IdStyle::Pandoc => Self::Pandoc,
+            IdStyle::Github => Self::GitHub,
+        }
+    }
+}
+
+impl Args {
+    /// Returns the log level selected by the verbosity flags, or `None` if no flag was passed.
+    fn log_level(&self) -> Option<log::LevelFilter> {
+        if self.quiet {
+            return Some(log::LevelFilter::Error);
+        }
+
+        match self.verbose {
+            0 => None,
+            1 => Some(log::LevelFilter::Debug),
+            _ => Some(log::LevelFilter::Trace),
+        }
+    }
+}
+
+/// The maximum size of the input in bytes, tree-sitter uses 32 bit byte offsets so bigger inputs
+/// can't be parsed.
+const MAX_INPUT_SIZE: u64 = u32::MAX as u64;
+
+/// The `--to` format of the built-in markdown writer.
+const NATIVE_MARKDOWN: &str = "markdown-native";
+
+/// The contents of an input, validated to be UTF-8.
+enum Input {
+    /// A regular file mapped into memory, so big files aren't copied into a buffer.
+    Mapped(memmap2::Mmap),
+    /// An input read into a buffer (like stdin or a pipe).
+    Read(String),
+}
+
+impl std::ops::Deref for Input {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        match self {
+            // SAFETY: The contents were validated to be UTF-8 when the file was mapped
+            Input::Mapped(map) => unsafe { std::str::from_utf8_unchecked(map) },
+            Input::Read(text) => text,
+        }
+    }
+}
+
+fn too_big(size: u64) -> String {
+    format!("Input is too big ({size} bytes, the maximum is {MAX_INPUT_SIZE})")
+}
+
+fn invalid_utf8(err: std::str::Utf8Error) -> String {
+    let offset = err.valid_up_to();
+    format!("Input isn't valid UTF-8 (invalid byte at offset {offset})")
 }
 
-fn read_from_stdin() -> String {
+/// Reads the whole input in chunks, failing as soon as it's bigger than [`MAX_INPUT_SIZE`] or if
+/// it isn't valid UTF-8.
+fn read_input(reader: impl Read) -> Result<Input, String> {
     let mut input = Vec::new();
-    io::stdin()
+
+    // One extra byte is read so that inputs bigger than the maximum can be detected
+    reader
+        .take(MAX_INPUT_SIZE + 1)
         .read_to_end(&mut input)
-        .expect("Failed to run from stdin");
-    String::from_utf8(input).expect("Non UTF8 input on stdin")
+        .map_err(|err| format!("Failed to read the input: {err}"))?;
+
+    if input.len() as u64 > MAX_INPUT_SIZE {
+        return Err(too_big(input.len() as u64));
+    }
+
+    String::from_utf8(input)
+        .map(Input::Read)
+        .map_err(|err| invalid_utf8(err.utf8_error()))
+}
+
+fn read_from_stdin() -> Result<Input, String> {
+    read_input(io::stdin().lock())
+}
+
+/// Reads the file at `path`, regular files are mapped into memory instead of being read.
+///
+/// Fails if the file is bigger than [`MAX_INPUT_SIZE`] or isn't valid UTF-8.
+fn read_from_file(path: &Path) -> Result<Input, String> {
+    let error = |err| format!("Failed to open {}: {err}", path.display());
+    let input_error = |err| format!("{}: {err}", path.display());
+
+    let file = fs::File::open(path).map_err(error)?;
+    let metadata = file.metadata().map_err(error)?;
+
+    // Pipes and devices have no size, and empty files can't be mapped
+    if !metadata.is_file() || metadata.len() == 0 {
+        return read_input(file).map_err(input_error);
+    }
+
+    if metadata.len() > MAX_INPUT_SIZE {
+        return Err(input_error(too_big(metadata.len())));
+    }
+
+    // SAFETY: The file could be modified by another process while it's mapped, in which case
+    // the contents might change after being validated. Like other tools that map their inputs,
+    // this is accepted, the files are only expected to change between runs
+    let map = unsafe { memmap2::Mmap::map(&file) }
+        .map_err(|err| format!("Failed to read {}: {err}", path.display()))?;
+
+    std::str::from_utf8(&map).map_err(|err| input_error(invalid_utf8(err)))?;
+    Ok(Input::Mapped(map))
+}
+
+/// Quotes a CSV field, doubling any quotes inside it.
+fn csv_field(text: &str) -> String {
+    format!("\"{}\"", text.replace('"', "\"\""))
+}
+
+fn output_flashcards(
+    cards: &[pandoc_norg_converter::Flashcard],
+    format: FlashcardFormat,
+    mut output: impl Write,
+) -> io::Result<()> {
+    match format {
+        FlashcardFormat::Csv => {
+            for card in cards {
+                let front = csv_field(&card.front_text());
+                let back = csv_field(&card.back_text());
+                writeln!(output, "{front},{back}")?;
+            }
+        }
+        FlashcardFormat::Json => {
+            let cards: Vec<_> = cards
+                .iter()
+                .map(|card| {
+                    serde_json::json!({
+                        "front": card.front_text(),
+                        "back": card.back_text(),
+                    })
+                })
+                .collect();
+            serde_json::to_writer(output, &cards)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Returns the path of the output of `input` produced by the `--output-template`.
+fn output_path(template: &str, input: &Path, extension: &str) -> PathBuf {
+    let dir = match input.parent() {
+        Some(dir) if dir != Path::new("") => dir.to_string_lossy(),
+        _ => ".".into(),
+    };
+    let stem = input.file_stem().unwrap_or_default().to_string_lossy();
+
+    let path = template
+        .replace("{dir}", &dir)
+        .replace("{stem}", &stem)
+        .replace("{ext}", extension);
+    PathBuf::from(path)
+}
+
+/// Returns the extension of the files produced with the selected output format.
+fn output_extension(args: &Args) -> &str {
+    match (&args.to, args.flashcards) {
+        _ if args.dump_tree || args.dump_ir => "txt",
+        (Some(_), None) if native_markdown(args) => "md",
+        (Some(format), _) => format_extension(format),
+        (None, Some(format)) => format.extension(),
+        (None, None) => "json",
+    }
+}
+
+/// Returns the extension of the files produced by pandoc for the `--to` format, the format's
+/// extensions (`+ext` and `-ext`) are ignored and unknown formats use their name.
+fn format_extension(format: &str) -> &str {
+    let name = format.split(['+', '-']).next().unwrap_or(format);
+
+    match name {
+        "markdown" | "markdown_strict" | "markdown_phpextra" | "markdown_mmd" | "gfm"
+        | "commonmark" | "commonmark_x" | "markua" => "md",
+        "html" | "html4" | "html5" | "revealjs" | "s5" | "slidy" | "slideous" | "dzslides" => {
+            "html"
+        }
+        "latex" | "beamer" | "context" => "tex",
+        "epub" | "epub2" | "epub3" => "epub",
+        "asciidoc" | "asciidoctor" | "asciidoc_legacy" => "adoc",
+        "docbook"
+        | "docbook4"
+        | "docbook5"
+        | "jats"
+        | "jats_archiving"
+        | "jats_articleauthoring"
+        | "jats_publishing"
+        | "tei"
+        | "opml"
+        | "icml" => "xml",
+        "biblatex" | "bibtex" => "bib",
+        "chunkedhtml" => "zip",
+        "csljson" => "json",
+        "man" => "1",
+        "mediawiki" | "dokuwiki" | "xwiki" | "zimwiki" => "wiki",
+        "native" => "hs",
+        "plain" => "txt",
+        "texinfo" => "texi",
+        "typst" => "typ",
+        _ => name,
+    }
+}
+
+/// Converts a single input (`None` is stdin) and writes the result to it's output.
+fn process(input: Option<&Path>, args: &Args) -> Result<(), String> {
+    let file_contents = match input {
+        None => read_from_stdin()?,
+        Some(path) => read_from_file(path)?,
+    };
+
+    let path = match (&args.output, &args.output_template, input) {
+        (Some(path), _, _) => Some(path.clone()),
+        (None, None, _) => None,
+        (None, Some(_), None) => {
+            return Err(String::from("The output template can't be used with stdin"))
+        }
+        (None, Some(template), Some(path)) => {
+            Some(output_path(template, path, output_extension(args)))
+        }
+    };
+
+    let mut frontend = pandoc_norg_converter::Frontend::new(load_config(args)?);
+    write_output(path.as_deref(), args, |output| {
+        convert(&file_contents, &mut frontend, args, output).map(|_| ())
+    })
+}
+
+/// Converts all the neorg files in `in_dir` (and it's subdirectories) with the same `Frontend`,
+/// the outputs are written to `out_dir` with the same directory layout.
+fn process_dir(in_dir: &Path, out_dir: &Path, args: &Args) -> Result<(), String> {
+    let mut inputs = Vec::new();
+    find_norg_files(in_dir, &mut inputs)?;
+    // The files are always converted in the same order so that the identifiers are stable
+    inputs.sort();
+
+    let mut frontend = pandoc_norg_converter::Frontend::new(load_config(args)?);
+    let mut sitemap = Vec::new();
+    let mut redirects = BTreeMap::new();
+    let mut failed = false;
+
+    for input in inputs {
+        let relative = input
+            .strip_prefix(in_dir)
+            .expect("File outside of the input directory");
+        let path = out_dir
+            .join(relative)
+            .with_extension(output_extension(args));
+
+        let result = read_from_file(&input).and_then(|file_contents| {
+            if let Some(parent) = path.parent() {
+                fs::create_dir_all(parent)
+                    .map_err(|err| format!("Failed to create {}: {err}", parent.display()))?;
+            }
+
+            let mut document = None;
+            write_output(Some(&path), args, |output| {
+                document = convert(&file_contents, &mut frontend, args, output)?;
+                Ok(())
+            })?;
+            Ok(document)
+        });
+
+        match result {
+            Ok(document) => {
+                let relative = path
+                    .strip_prefix(out_dir)
+                    .expect("Output outside of out_dir");
+                let meta = document.as_ref().map(|document| &document.meta);
+
+                if args.sitemap.is_some() {
+                    sitemap.push(sitemap_entry(&input, relative, meta));
+                }
+                if let (true, Some(document)) = (args.redirects, &document) {
+                    add_redirects(&mut redirects, &output_url(relative), &document.blocks);
+                }
+            }
+            Err(err) => {
+                log::error!("{err}");
+                failed = true;
+            }
+        }
+    }
+
+    if let Some(format) = args.sitemap {
+        let path = out_dir.join("sitemap").with_extension(format.extension());
+        write_sitemap(&path, &sitemap, format)?;
+    }
+
+    if args.redirects {
+        let path = out_dir.join("redirects.json");
+        fs::File::create(&path)
+            .map(io::BufWriter::new)
+            .and_then(|mut output| {
+                serde_json::to_writer_pretty(&mut output, &redirects)?;
+                output.flush()
+            })
+            .map_err(|err| format!("Failed to write {}: {err}", path.display()))?;
+    }
+
+    match failed {
+        true => Err(String::from("Some files failed to be converted")),
+        false => Ok(()),
+    }
+}
+
+/// An output of the directory mode listed in the `--sitemap`.
+struct SitemapEntry {
+    /// The path of the output relative to the output directory, with `/` as separator.
+    path: String,
+    title: String,
+    /// The date the input was last modified, in `YYYY-MM-DD` form unless it's taken from a
+    /// `date` metadata field that isn't a valid date.
+    modified: String,
+}
+
+/// Returns the path of the output at `relative` (to the output directory) with `/` as separator.
+fn output_url(relative: &Path) -> String {
+    relative
+        .components()
+        .map(|component| component.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+/// Adds the identifiers of the blocks to the `--redirects` map, each mapped to the anchor in the
+/// output at `url`.
+fn add_redirects(redirects: &mut BTreeMap<String, String>, url: &str, blocks: &[Block]) {
+    let mut ids = Vec::new();
+    collect_block_ids(blocks, &mut ids);
+    redirects.extend(ids.into_iter().map(|id| {
+        let target = format!("{url}#{id}");
+        (id, target)
+    }));
+}
+
+/// Appends the identifiers of the blocks (and the spans inside them) to `ids`, in the order they
+/// appear in the document.
+fn collect_block_ids(blocks: &[Block], ids: &mut Vec<String>) {
+    fn push_id(attr: &Attr, ids: &mut Vec<String>) {
+        if !attr.identifier.is_empty() {
+            ids.push(attr.identifier.clone());
+        }
+    }
+
+    fn push_inline_ids(inlines: &[Inline], ids: &mut Vec<String>) {
+        for inline in inlines {
+            match inline {
+                Inline::Span(attr, inlines) => {
+                    push_id(attr, ids);
+                    push_inline_ids(inlines, ids);
+                }
+                Inline::Emph(inlines)
+                | Inline::Underline(inlines)
+                | Inline::Strong(inlines)
+                | Inline::Strikeout(inlines)
+                | Inline::Superscript(inlines)
+                | Inline::Subscript(inlines)
+                | Inline::SmallCaps(inlines)
+                | Inline::Quoted(_, inlines)
+                | Inline::Link(_, inlines, _) => push_inline_ids(inlines, ids),
+                _ => {}
+            }
+        }
+    }
+
+    for block in blocks {
+        match block {
+            Block::Plain(inlines) | Block::Para(inlines) => push_inline_ids(inlines, ids),
+            Block::Header(_, attr, inlines) => {
+                push_id(attr, ids);
+                push_inline_ids(inlines, ids);
+            }
+            Block::CodeBlock(attr, _) => push_id(attr, ids),
+            Block::BlockQuote(blocks) => collect_block_ids(blocks, ids),
+            Block::OrderedList(_, items) | Block::BulletList(items) => {
+                for item in items {
+                    collect_block_ids(item, ids);
+                }
+            }
+            Block::DefinitionList(items) => {
+                for (term, definitions) in items {
+                    push_inline_ids(term, ids);
+                    for definition in definitions {
+                        collect_block_ids(definition, ids);
+                    }
+                }
+            }
+            Block::Table(table) => {
+                push_id(&table.attr, ids);
+                let rows = table.head.rows.iter().chain(
+                    table
+                        .bodies
+                        .iter()
+                        .flat_map(|body| body.head.iter().chain(&body.body)),
+                );
+                for cell in rows.flat_map(|row| &row.cells) {
+                    collect_block_ids(&cell.content, ids);
+                }
+            }
+            Block::Figure(attr, _, blocks) | Block::Div(attr, blocks) => {
+                push_id(attr, ids);
+                collect_block_ids(blocks, ids);
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Returns the sitemap entry of the output at `relative` (to the output directory) converted from
+/// `input`, the title and the date are taken from the document's metadata if available.
+fn sitemap_entry(
+    input: &Path,
+    relative: &Path,
+    meta: Option<&HashMap<String, MetaValue>>,
+) -> SitemapEntry {
+    let path = output_url(relative);
+    let meta_field = |name| meta.and_then(|meta| meta.get(name)).and_then(meta_text);
+
+    let title = meta_field("title").unwrap_or_else(|| {
+        input
+            .file_stem()
+            .unwrap_or_default()
+            .to_string_lossy()
+            .into()
+    });
+
+    let modified = meta_field("date").or_else(|| {
+        let time = fs::metadata(input).and_then(|metadata| metadata.modified());
+        time.ok().map(|time| {
+            let (year, month, day, _) = utc_date_time(time);
+            format!("{year:04}-{month:02}-{day:02}")
+        })
+    });
+
+    SitemapEntry {
+        path,
+        title,
+        modified: modified.unwrap_or_default(),
+    }
+}
+
+/// Returns the plain text of a text metadata field, or `None` if it's empty or not text.
+fn meta_text(value: &MetaValue) -> Option<String> {
+    fn push_inlines(inlines: &[Inline], text: &mut String) {
+        for inline in inlines {
+            match inline {
+                Inline::Str(str) | Inline::Code(_, str) | Inline::Math(_, str) => {
+                    text.push_str(str)
+                }
+                Inline::Space | Inline::SoftBreak | Inline::LineBreak => text.push(' '),
+                Inline::Emph(inlines)
+                | Inline::Underline(inlines)
+                | Inline::Strong(inlines)
+                | Inline::Strikeout(inlines)
+                | Inline::Superscript(inlines)
+                | Inline::Subscript(inlines)
+                | Inline::SmallCaps(inlines)
+                | Inline::Quoted(_, inlines)
+                | Inline::Link(_, inlines, _)
+                | Inline::Span(_, inlines) => push_inlines(inlines, text),
+                _ => {}
+            }
+        }
+    }
+
+    let text = match value {
+        MetaValue::MetaString(text) => text.clone(),
+        MetaValue::MetaInlines(inlines) => {
+            let mut text = String::new();
+            push_inlines(inlines, &mut text);
+            text
+        }
+        _ => return None,
+    };
+
+    (!text.trim().is_empty()).then(|| text.trim().to_string())
+}
+
+/// Returns the UTC date and time of `time` as the year, month, day and the seconds since the
+/// start of the day.
+fn utc_date_time(time: SystemTime) -> (i64, u32, u32, u64) {
+    let seconds = match time.duration_since(SystemTime::UNIX_EPOCH) {
+        Ok(duration) => duration.as_secs() as i64,
+        Err(err) => -(err.duration().as_secs() as i64),
+    };
+    let days = seconds.div_euclid(86400);
+
+    // Converts the days since the epoch to a date of the proleptic gregorian calendar, the
+    // eras are the 400 years cycles starting at the 1st of March of the year 0
+    let days = days + 719468;
+    let era = days.div_euclid(146097);
+    let day_of_era = days.rem_euclid(146097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month = (5 * day_of_year + 2) / 153;
+    let day = (day_of_year - (153 * month + 2) / 5 + 1) as u32;
+    let month = if month < 10 { month + 3 } else { month - 9 } as u32;
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+
+    (year, month, day, seconds.rem_euclid(86400) as u64)
+}
+
+/// Escapes the characters with a special meaning in XML text and attributes.
+fn xml_escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// Writes the `--sitemap` of the outputs in the directory mode to `path`.
+fn write_sitemap(
+    path: &Path,
+    entries: &[SitemapEntry],
+    format: SitemapFormat,
+) -> Result<(), String> {
+    let error = |err| format!("Failed to write {}: {err}", path.display());
+    let mut output = io::BufWriter::new(fs::File::create(path).map_err(error)?);
+
+    let result = match format {
+        SitemapFormat::Json => {
+            let entries: Vec<_> = entries
+                .iter()
+                .map(|entry| {
+                    serde_json::json!({
+                        "path": entry.path,
+                        "title": entry.title,
+                        "modified": entry.modified,
+                    })
+                })
+                .collect();
+            serde_json::to_writer_pretty(&mut output, &entries).map_err(io::Error::from)
+        }
+        SitemapFormat::Xml => {
+            let mut xml = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<sitemap>\n");
+            for entry in entries {
+                xml.push_str("  <document>\n");
+                xml.push_str(&format!("    <path>{}</path>\n", xml_escape(&entry.path)));
+                xml.push_str(&format!(
+                    "    <title>{}</title>\n",
+                    xml_escape(&entry.title)
+                ));
+                xml.push_str(&format!(
+                    "    <modified>{}</modified>\n",
+                    xml_escape(&entry.modified)
+                ));
+                xml.push_str("  </document>\n");
+            }
+            xml.push_str("</sitemap>\n");
+            output.write_all(xml.as_bytes())
+        }
+    };
+
+    result.and_then(|_| output.flush()).map_err(error)
+}
+
+/// Appends the paths of the `.norg` files in `dir` (and it's subdirectories) to `files`.
+fn find_norg_files(dir: &Path, files: &mut Vec<PathBuf>) -> Result<(), String> {
+    let error = |err| format!("Failed to read {}: {err}", dir.display());
+
+    for entry in fs::read_dir(dir).map_err(error)? {
+        let entry = entry.map_err(error)?;
+        let path = entry.path();
+
+        if entry.file_type().map_err(error)?.is_dir() {
+            find_norg_files(&path, files)?;
+        } else if path.extension() == Some("norg".as_ref()) {
+            files.push(path);
+        }
+    }
+
+    Ok(())
+}
+
+/// Writes the output produced by `write` to `path` (or stdout if `None`), converting it with
+/// pandoc if an output format was selected.
+fn write_output(
+    path: Option<&Path>,
+    args: &Args,
+    write: impl FnOnce(&mut dyn Write) -> io::Result<()>,
+) -> Result<(), String> {
+    if let Some(format) = args.to.as_ref().filter(|_| !native_markdown(args)) {
+        return run_pandoc(format, path, write);
+    }
+
+    let (mut output, name): (Box<dyn Write>, _) = match path {
+        None => (Box::new(io::stdout().lock()), Path::new("stdout")),
+        Some(name) => {
+            let file = fs::File::create(name)
+                .map_err(|err| format!("Failed to create {}: {err}", name.display()))?;
+            (Box::new(io::BufWriter::new(file)), name)
+        }
+    };
+
+    let result = write(&mut output).and_then(|_| output.flush());
+    result.map_err(|err| format!("Failed to output to {}: {err}", name.display()))
+}
+
+/// Returns the converter's configuration, loaded from the `--config` file (if any) and the
+/// options passed as flags.
+fn load_config(args: &Args) -> Result<pandoc_norg_converter::Config, String> {
+    let mut config = match &args.config {
+        None => pandoc_norg_converter::Config::default(),
+        Some(path) => {
+            let text = fs::read_to_string(path)
+                .map_err(|err| format!("Failed to read {}: {err}", path.display()))?;
+            serde_json::from_str(&text)
+                .map_err(|err| format!("Invalid configuration in {}: {err}", path.display()))?
+        }
+    };
+
+    if let Some(path) = &args.section {
+        config.section_filter = Some(path.split('/').map(str::to_string).collect());
+    }
+    if let Some(offset) = args.shift_heading_level_by {
+        config.heading_offset = offset;
+    }
+    if let Some(style) = args.id_style {
+        config.id_style = style.into();
+    }
+
+    Ok(config)
+}
+
+/// Converts the neorg source and writes the result (the pandoc json, the flashcards or the
+/// debug dumps) to `output`.
+///
+/// Returns the pandoc document, which is only kept for the `--sitemap` and the `--redirects`
+/// (the debug dumps and the streamed pandoc json return no document).
+fn convert(
+    source: &str,
+    frontend: &mut pandoc_norg_converter::Frontend,
+    args: &Args,
+    output: &mut dyn Write,
+) -> io::Result<Option<Pandoc>> {
+    if args.dump_tree || args.dump_ir {
+        if args.dump_tree {
+            writeln!(output, "{}", pandoc_norg_converter::syntax_tree(source))?;
+        }
+        if args.dump_ir {
+            writeln!(output, "{}", frontend.debug_ir(source))?;
+        }
+        return Ok(None);
+    }
+
+    match args.flashcards {
+        Some(format) => {
+            let (document, cards) = frontend.convert_with_flashcards(source);
+            output_flashcards(&cards, format, output)?;
+            Ok(Some(document))
+        }
+        None if native_markdown(args) => {
+            let document = frontend.convert(source);
+            let markdown = pandoc_norg_converter::write_markdown(&document);
+            output.write_all(markdown.as_bytes())?;
+            Ok(Some(document))
+        }
+        // The whole document is only built when it's needed after being written
+        None if args.sitemap.is_some() || args.redirects => {
+            let document = frontend.convert(source);
+            serde_json::to_writer(output, &document)?;
+            Ok(Some(document))
+        }
+        None => frontend.convert_to_writer(source, output).map(|_| None),
+    }
+}
+
+/// Returns whether the output is converted with the built-in markdown writer instead of pandoc.
+fn native_markdown(args: &Args) -> bool {
+    args.to.as_deref() == Some(NATIVE_MARKDOWN)
+}
+
+/// Runs pandoc to convert the json written by `write` to `format`, the result is written to
+/// `output` (or stdout if `None`).
+fn run_pandoc(
+    format: &str,
+    output: Option<&Path>,
+    write: impl FnOnce(&mut dyn Write) -> io::Result<()>,
+) -> Result<(), String> {
+    let mut command = process::Command::new("pandoc");
+    command
+        .args(["--from", "json", "--to", format])
+        .stdin(Stdio::piped());
+    if let Some(path) = output {
+        command.arg("--output").arg(path);
+    }
+
+    let mut child = command.spawn().map_err(|err| match err.kind() {
+        io::ErrorKind::NotFound => String::from("pandoc wasn't found, is it installed?"),
+        _ => format!("Failed to run pandoc: {err}"),
+    })?;
+
+    let stdin = child.stdin.take().expect("pandoc's stdin isn't piped");
+    let mut stdin = io::BufWriter::new(stdin);
+    let result = write(&mut stdin).and_then(|_| stdin.flush());
+    // Closes pandoc's stdin so that it stops waiting for more input
+    drop(stdin);
+
+    let status = child
+        .wait()
+        .map_err(|err| format!("Failed to wait for pandoc: {err}"))?;
+
+    // Pandoc already reported why it failed (which might have also caused the write to fail)
+    if !status.success() {
+        return Err(format!("pandoc failed ({status})"));
+    }
+
+    result.map_err(|err| format!("Failed to output to pandoc: {err}"))
+}
+
+fn generate(target: GenerateTarget) {
+    let mut command = Args::command();
+    let mut stdout = io::stdout().lock();
+
+    match target {
+        GenerateTarget::Completions { shell } => {
+            let name = command.get_name().to_string();
+            clap_complete::generate(shell, &mut command, name, &mut stdout);
+        }
+        GenerateTarget::Man => clap_mangen::Man::new(command)
+            .render(&mut stdout)
+            .expect("Failed to output to stdout"),
+    }
+}
+
+/// Writes the code blocks of the neorg file at `input` to their files, the file is converted with
+/// the `frontend` (which is reset first).
+fn tangle(
+    input: &Path,
+    dry_run: bool,
+    frontend: &mut pandoc_norg_converter::Frontend,
+) -> Result<(), String> {
+    let source = read_from_file(input)?;
+    frontend.reset();
+    let files = frontend.tangle(&source);
+
+    if files.is_empty() {
+        log::warn!("{} has no code blocks to tangle", input.display());
+    }
+
+    let dir = input.parent().unwrap_or(Path::new(""));
+    for file in files {
+        let path = dir.join(&file.path);
+
+        if dry_run {
+            println!("{}", path.display());
+            continue;
+        }
+
+        let error = |err| format!("Failed to write {}: {err}", path.display());
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).map_err(error)?;
+        }
+
+        fs::OpenOptions::new()
+            .create(true)
+            .write(true)
+            .append(file.append)
+            .truncate(!file.append)
+            .open(&path)
+            .and_then(|mut output| output.write_all(file.content.as_bytes()))
+            .map_err(error)?;
+        log::info!("Tangled {}", path.display());
+    }
+
+    Ok(())
+}
+
+/// Writes the iCalendar file with the dated tasks of the neorg files (and the neorg files in the
+/// directories) in `paths` to `output` (or stdout if `None`).
+///
+/// The files are converted with the same configuration as the main conversion, the files in a
+/// directory share the identifiers like in the directory mode.
+fn calendar(paths: &[PathBuf], output: Option<&Path>, args: &Args) -> Result<(), String> {
+    use pandoc_norg_converter::TodoStatus;
+
+    let mut frontend = pandoc_norg_converter::Frontend::new(load_config(args)?);
+
+    let (year, month, day, seconds) = utc_date_time(SystemTime::now());
+    let (hour, minute, second) = (seconds / 3600, seconds / 60 % 60, seconds % 60);
+    let stamp = format!("{year:04}{month:02}{day:02}T{hour:02}{minute:02}{second:02}Z");
+
+    let mut ics = String::new();
+    push_ics_line(&mut ics, "BEGIN:VCALENDAR");
+    push_ics_line(&mut ics, "VERSION:2.0");
+    push_ics_line(&mut ics, "PRODID:-//pandoc-norg-rs//EN");
+
+    for path in paths {
+        let mut inputs = Vec::new();
+        match path.is_dir() {
+            true => {
+                find_norg_files(path, &mut inputs)?;
+                inputs.sort();
+            }
+            false => inputs.push(path.clone()),
+        }
+
+        // Each path gets it's own identifiers, like the files passed to the main conversion
+        frontend.reset();
+
+        for input in &inputs {
+            let source = read_from_file(input)?;
+            let tasks = frontend.dated_tasks(&source);
+
+            for (idx, task) in tasks.into_iter().enumerate() {
+                // The identifiers stay the same as long as the tasks of the file aren't
+                // reordered, so that calendars update the tasks instead of duplicating them
+                let uid = format!("{}-{}@pandoc-norg-rs", idx + 1, input.display());
+
+                push_ics_line(&mut ics, "BEGIN:VTODO");
+                push_ics_line(&mut ics, &format!("UID:{}", ics_escape(&uid)));
+                push_ics_line(&mut ics, &format!("DTSTAMP:{stamp}"));
+                push_ics_line(&mut ics, &format!("SUMMARY:{}", ics_escape(&task.text)));
+                if let Some(heading) = &task.heading {
+                    push_ics_line(&mut ics, &format!("DESCRIPTION:{}", ics_escape(heading)));
+                }
+                if let Some(start) = &task.start {
+                    push_ics_line(&mut ics, &ics_date("DTSTART", start));
+                }
+                if let Some(due) = &task.due {
+                    push_ics_line(&mut ics, &ics_date("DUE", due));
+                }
+
+                let status = match task.status {
+                    TodoStatus::Done => "COMPLETED",
+                    TodoStatus::Cancelled => "CANCELLED",
+                    TodoStatus::Pending => "IN-PROCESS",
+                    _ => "NEEDS-ACTION",
+                };
+                push_ics_line(&mut ics, &format!("STATUS:{status}"));
+                if task.status == TodoStatus::Urgent {
+                    push_ics_line(&mut ics, "PRIORITY:1");
+                }
+                push_ics_line(&mut ics, "END:VTODO");
+            }
+        }
+    }
+
+    push_ics_line(&mut ics, "END:VCALENDAR");
+
+    match output {
+        None => io::stdout().lock().write_all(ics.as_bytes()),
+        Some(path) => fs::write(path, ics),
+    }
+    .map_err(|err| {
+        let name = output.unwrap_or(Path::new("stdout"));
+        format!("Failed to output to {}: {err}", name.display())
+    })
+}
+
+/// Appends an iCalendar content line to `ics`, lines longer than 75 bytes are folded.
+fn push_ics_line(ics: &mut String, line: &str) {
+    let mut length = 0;
+    for char in line.chars() {
+        if length + char.len_utf8() > 75 {
+            ics.push_str("\r\n ");
+            length = 1;
+        }
+        ics.push(char);
+        length += char.len_utf8();
+    }
+    ics.push_str("\r\n");
+}
+
+/// Escapes the characters with a special meaning in iCalendar text values.
+fn ics_escape(text: &str) -> String {
+    text.replace('\\', "\\\\")
+        .replace(';', "\\;")
+        .replace(',', "\\,")
+        .replace('\n', "\\n")
+}
+
+/// Returns the iCalendar property `name` with the ISO 8601 date (`2024-06-01`) or date and time
+/// (`2024-06-01T16:00`), times are in the local time of the calendar.
+fn ics_date(name: &str, iso: &str) -> String {
+    let value: String = iso.chars().filter(|c| *c != '-' && *c != ':').collect();
+    match value.contains('T') {
+        true => format!("{name}:{value}00"),
+        false => format!("{name};VALUE=DATE:{value}"),
+    }
 }
 
 fn main() {
-    let args = Args::parse();
+    let mut args = Args::parse();
     let mut builder = env_logger::Builder::new();
     builder.filter_level(log::LevelFilter::Info);
     builder.parse_default_env();
+    // Explicit flags take precedence over the default level set by `RUST_LOG`
+    if let Some(level) = args.log_level() {
+        builder.filter_level(level);
+    }
     builder.init();
 
-    let file_contents = match args.file {
-        None => read_from_stdin(),
-        Some(p) if p == Path::new("-") => read_from_stdin(),
-        Some(path) => fs::read_to_string(path).expect("Failed to open neorg file"),
+    match args.command.take() {
+        Some(Command::Generate { target }) => return generate(target),
+        Some(Command::Tangle { files, dry_run }) => {
+            let mut frontend = match load_config(&args) {
+                Ok(config) => pandoc_norg_converter::Frontend::new(config),
+                Err(err) => {
+                    log::error!("{err}");
+                    process::exit(1);
+                }
+            };
+
+            let mut failed = false;
+            for input in files {
+                if let Err(err) = tangle(&input, dry_run, &mut frontend) {
+                    log::error!("{err}");
+                    failed = true;
+                }
+            }
+
+            if failed {
+                process::exit(1);
+            }
+            return;
+        }
+        Some(Command::Calendar { files, output }) => {
+            if let Err(err) = calendar(&files, output.as_deref(), &args) {
+                log::error!("{err}");
+                process::exit(1);
+            }
+            return;
+        }
+        None => {}
+    }
+
+    if let (Some(in_dir), Some(out_dir)) = (&args.in_dir, &args.out_dir) {
+        if let Err(err) = process_dir(in_dir, out_dir, &args) {
+            log::error!("{err}");
+            process::exit(1);
+        }
+        return;
+    }
+
+    let inputs: Vec<Option<&Path>> = match args.files.is_empty() {
+        true => vec![None],
+        false => args
+            .files
+            .iter()
+            .map(|path| (path != Path::new("-")).then_some(path.as_path()))
+            .collect(),
     };
 
-    let mut frontend = pandoc_norg_converter::Frontend::default();
-    let document = frontend.convert(&file_contents);
+    if inputs.len() > 1 && args.output_template.is_none() {
+        log::error!("An output template is required to process multiple files");
+        process::exit(1);
+    }
+
+    let mut failed = false;
+    for input in inputs {
+        if let Err(err) = process(input, &args) {
+            log::error!("{err}");
+            failed = true;
+        }
+    }
+
+    if failed {
+        process::exit(1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{add_redirects, collect_block_ids};
+    use pandoc_types::definition::{Attr, Block, Inline};
+    use std::collections::BTreeMap;
+
+    fn attr(id: &str) -> Attr {
+        Attr {
+            identifier: id.to_string(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn redirects() {
+        let blocks = vec![
+            Block::Header(1, attr("Setup"), vec![Inline::Str("Setup".to_string())]),
+            Block::CodeBlock(attr(""), String::from("print()")),
+            Block::Div(
+                attr("notes"),
+                vec![Block::Para(vec![Inline::Span(
+                    attr("term"),
+                    vec![Inline::Str("Term".to_string())],
+                )])],
+            ),
+            Block::CodeBlock(attr("listing"), String::from("print()")),
+        ];
+
+        let mut ids = Vec::new();
+        collect_block_ids(&blocks, &mut ids);
+        assert_eq!(ids, ["Setup", "notes", "term", "listing"]);
 
-    let stdout = std::io::stdout().lock();
-    serde_json::to_writer(stdout, &document).expect("Failed to output to stdout");
+        let mut redirects = BTreeMap::new();
+        add_redirects(&mut redirects, "chapters/intro.html", &blocks);
+        assert_eq!(redirects.len(), 4);
+        assert_eq!(redirects["Setup"], "chapters/intro.html#Setup");
+        assert_eq!(redirects["term"], "chapters/intro.html#term");
+    }
 }
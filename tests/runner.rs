@@ -14,7 +14,14 @@ fn convert() {
 
         let content = fs::read_to_string(entry.path()).expect("Couldn't read test file");
 
-        let mut frontend = pandoc_norg_converter::Frontend::default();
+        // The features that are disabled by default are tested with the configuration in
+        // `tests/config/<name>.json`
+        let config = match fs::read_to_string(format!("{root}/tests/config/{file_name}.json")) {
+            Ok(config) => serde_json::from_str(&config).expect("Invalid test configuration"),
+            Err(_) => pandoc_norg_converter::Config::default(),
+        };
+
+        let mut frontend = pandoc_norg_converter::Frontend::new(config);
         let document = frontend.convert(&content);
 
         let json_out = fs::File::create(format!("{root}/tests/out/{file_name}.json"))
@@ -38,3 +45,36 @@ fn convert() {
 
     assert!(pandoc_exists, "Tests require the pandoc executable");
 }
+
+#[test]
+fn parallel() {
+    let root = env!("CARGO_MANIFEST_DIR");
+
+    // Top level sections wrapped in carryover tags and tasks before and after them
+    let mut sources = vec![String::from(
+        "- ( ) Before\n\n\
+         #id intro\n\
+         * Introduction\n  - (x) Done\n\n\
+         +class wide\n\
+         * Chapter\n  - ( ) Undone\n\
+         ** Introduction\n   - (!) Urgent\n\n\
+         * Introduction\n  - (-) Pending\n",
+    )];
+
+    for entry in fs::read_dir(format!("{root}/tests/in")).unwrap() {
+        sources.push(fs::read_to_string(entry.unwrap().path()).expect("Couldn't read test file"));
+    }
+
+    for source in sources.iter().map(|source| source.repeat(20)) {
+        let mut sequential = pandoc_norg_converter::Frontend::default();
+        let mut parallel = pandoc_norg_converter::Frontend::new(pandoc_norg_converter::Config {
+            parallel: true,
+            ..Default::default()
+        });
+
+        assert_eq!(
+            sequential.convert_with_sections(&source),
+            parallel.convert_with_sections(&source)
+        );
+    }
+}